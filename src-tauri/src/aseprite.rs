@@ -0,0 +1,625 @@
+//! Aseprite documents (`.ase` / `.aseprite`).
+//!
+//! Parses the binary format into layers, per-frame cels, frame durations,
+//! tags and the palette. Cel pixels stay in the file's own color depth:
+//! RGBA (4 bytes), grayscale + alpha (2 bytes) or palette indices (1 byte),
+//! so indexed documents keep their indices. Linked cels are resolved to the
+//! cel they point at and share its pixels in the framed IPC response.
+//!
+//! Format reference: aseprite's `docs/ase-file-specs.md`.
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+
+const HEADER_MAGIC: u16 = 0xA5E0;
+const FRAME_MAGIC: u16 = 0xF1FA;
+const HEADER_LEN: usize = 128;
+const FRAME_HEADER_LEN: usize = 16;
+
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+const CHUNK_OLD_PALETTE_64: u16 = 0x0011;
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_TAGS: u16 = 0x2018;
+const CHUNK_PALETTE: u16 = 0x2019;
+
+/// Header flag: layer opacity fields are meaningful.
+const FLAG_LAYER_OPACITY: u32 = 1;
+const LAYER_VISIBLE: u16 = 1;
+const LAYER_BACKGROUND: u16 = 8;
+
+const MAX_PIXELS: u64 = 1 << 26;
+/// Aseprite allows up to 65536 palette entries in RGBA documents.
+const MAX_PALETTE_COLORS: usize = 1 << 16;
+/// Limit on all decoded cel pixels together.
+const MAX_DECODED_BYTES: usize = 1 << 29;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerKind {
+    Normal,
+    Group,
+    Tilemap,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AseLayer {
+    pub name: String,
+    pub kind: LayerKind,
+    /// Nesting depth; a layer belongs to the closest group above it with a
+    /// lower level.
+    pub child_level: u16,
+    pub visible: bool,
+    pub background: bool,
+    pub opacity: u8,
+    pub blend_mode: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AseCel {
+    /// Index into `layers`.
+    pub layer: usize,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub opacity: u8,
+    pub z_index: i16,
+    /// Frame this cel was linked to in the file, if it was a linked cel.
+    pub linked_frame: Option<usize>,
+    /// Location of the pixels in a framed IPC response (see `take_cel_pixels`).
+    pub pixel_offset: usize,
+    pub pixel_len: usize,
+    #[serde(skip)]
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AseFrame {
+    pub duration_ms: u16,
+    pub cels: Vec<AseCel>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AseTag {
+    pub name: String,
+    pub from: u16,
+    pub to: u16,
+    pub direction: &'static str,
+    /// 0 means repeat forever.
+    pub repeat: u16,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AsepriteDocument {
+    pub width: u16,
+    pub height: u16,
+    /// Bits per pixel: 32 (RGBA), 16 (grayscale) or 8 (indexed).
+    pub color_depth: u16,
+    /// Palette index drawn as transparent on non-background layers.
+    pub transparent_index: u8,
+    /// RGBA entries.
+    pub palette: Vec<[u8; 4]>,
+    pub layers: Vec<AseLayer>,
+    pub frames: Vec<AseFrame>,
+    pub tags: Vec<AseTag>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let out = self
+            .pos
+            .checked_add(n)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| "Aseprite file is truncated".to_string())?;
+        self.pos += n;
+        Ok(out)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<(), String> {
+        self.bytes(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        self.u16().map(|v| v as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+}
+
+fn blend_mode(mode: u16) -> &'static str {
+    match mode {
+        1 => "multiply",
+        2 => "screen",
+        3 => "overlay",
+        4 => "darken",
+        5 => "lighten",
+        6 => "color-dodge",
+        7 => "color-burn",
+        8 => "hard-light",
+        9 => "soft-light",
+        10 => "difference",
+        11 => "exclusion",
+        12 => "hue",
+        13 => "saturation",
+        14 => "color",
+        15 => "luminosity",
+        16 => "addition",
+        17 => "subtract",
+        18 => "divide",
+        _ => "normal",
+    }
+}
+
+fn tag_direction(direction: u8) -> &'static str {
+    match direction {
+        1 => "reverse",
+        2 => "ping-pong",
+        3 => "ping-pong-reverse",
+        _ => "forward",
+    }
+}
+
+/// Parse an Aseprite file. Tilemap cels are skipped; everything else the
+/// format can hold in a cel is returned.
+pub fn parse(bytes: &[u8]) -> Result<AsepriteDocument, String> {
+    let mut header = Reader::new(bytes.get(..HEADER_LEN).ok_or("not an Aseprite file")?);
+    header.skip(4)?;
+    if header.u16()? != HEADER_MAGIC {
+        return Err("not an Aseprite file".into());
+    }
+    let frame_count = header.u16()? as usize;
+    let width = header.u16()?;
+    let height = header.u16()?;
+    let color_depth = header.u16()?;
+    let flags = header.u32()?;
+    header.skip(2 + 4 + 4)?;
+    let transparent_index = header.u8()?;
+    header.skip(3)?;
+    let color_count = header.u16()?;
+    if !matches!(color_depth, 8 | 16 | 32) {
+        return Err(format!("Aseprite file has unknown color depth {}", color_depth));
+    }
+    if width == 0 || height == 0 {
+        return Err("Aseprite file has zero width or height".into());
+    }
+    let bpp = color_depth as usize / 8;
+
+    let mut doc = AsepriteDocument {
+        width,
+        height,
+        color_depth,
+        transparent_index,
+        palette: Vec::new(),
+        layers: Vec::new(),
+        frames: Vec::with_capacity(frame_count.min(1024)),
+        tags: Vec::new(),
+    };
+    let mut old_palette: Option<Vec<[u8; 4]>> = None;
+    let mut decoded_bytes = 0usize;
+
+    let mut r = Reader::new(bytes);
+    r.skip(HEADER_LEN)?;
+    for _ in 0..frame_count {
+        let frame_start = r.pos;
+        let frame_len = r.u32()? as usize;
+        if r.u16()? != FRAME_MAGIC {
+            return Err("Aseprite frame header is corrupt".into());
+        }
+        let old_chunks = r.u16()? as usize;
+        let duration_ms = r.u16()?;
+        r.skip(2)?;
+        let new_chunks = r.u32()? as usize;
+        let chunk_count = if new_chunks == 0 { old_chunks } else { new_chunks };
+        if frame_len < FRAME_HEADER_LEN {
+            return Err("Aseprite frame header is corrupt".into());
+        }
+        let frame_end = frame_start
+            .checked_add(frame_len)
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(|| "Aseprite file is truncated".to_string())?;
+
+        let mut cels = Vec::new();
+        for _ in 0..chunk_count {
+            let chunk_start = r.pos;
+            let chunk_len = r.u32()? as usize;
+            let kind = r.u16()?;
+            let chunk_end = chunk_start
+                .checked_add(chunk_len)
+                .filter(|&end| chunk_len >= 6 && end <= frame_end)
+                .ok_or_else(|| "Aseprite chunk is truncated".to_string())?;
+            let mut c = Reader::new(&bytes[chunk_start + 6..chunk_end]);
+            match kind {
+                CHUNK_LAYER => doc.layers.push(parse_layer(&mut c, flags)?),
+                CHUNK_CEL => {
+                    if let Some(cel) = parse_cel(&mut c, bpp, &doc.frames, doc.layers.len())? {
+                        decoded_bytes += cel.pixels.len();
+                        if decoded_bytes > MAX_DECODED_BYTES {
+                            return Err("Aseprite file is too large to decode".into());
+                        }
+                        cels.push(cel);
+                    }
+                }
+                CHUNK_TAGS => doc.tags = parse_tags(&mut c)?,
+                CHUNK_PALETTE => parse_palette(&mut c, &mut doc.palette)?,
+                CHUNK_OLD_PALETTE | CHUNK_OLD_PALETTE_64 if doc.frames.is_empty() => {
+                    old_palette = Some(parse_old_palette(&mut c, kind == CHUNK_OLD_PALETTE_64)?);
+                }
+                _ => {}
+            }
+            r.pos = chunk_end;
+        }
+        r.pos = frame_end;
+        doc.frames.push(AseFrame { duration_ms, cels });
+    }
+
+    // Files from before the 0x2019 chunk only carry the old palette chunks.
+    if doc.palette.is_empty() {
+        doc.palette = old_palette.unwrap_or_default();
+    }
+    if color_depth == 8 {
+        // Entries past 255 can't be reached by 8-bit indices.
+        let limit = if color_count > 0 { (color_count as usize).min(256) } else { 256 };
+        doc.palette.truncate(limit);
+        check_indices(&doc)?;
+    }
+    Ok(doc)
+}
+
+/// Indexed cels may only use colors the palette has (the transparent index
+/// is never looked up on layers that use it).
+fn check_indices(doc: &AsepriteDocument) -> Result<(), String> {
+    let colors = doc.palette.len();
+    for cel in doc.frames.iter().flat_map(|f| &f.cels) {
+        let transparent = (!doc.layers[cel.layer].background).then_some(doc.transparent_index);
+        if let Some(&index) = cel.pixels.iter().find(|&&i| i as usize >= colors && Some(i) != transparent) {
+            return Err(format!(
+                "Aseprite cel uses palette index {} but the palette has {} colors",
+                index, colors
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn parse_layer(c: &mut Reader<'_>, header_flags: u32) -> Result<AseLayer, String> {
+    let flags = c.u16()?;
+    let kind = match c.u16()? {
+        1 => LayerKind::Group,
+        2 => LayerKind::Tilemap,
+        _ => LayerKind::Normal,
+    };
+    let child_level = c.u16()?;
+    c.skip(4)?;
+    let blend = c.u16()?;
+    let opacity = c.u8()?;
+    c.skip(3)?;
+    let name = c.string()?;
+    Ok(AseLayer {
+        name,
+        kind,
+        child_level,
+        visible: flags & LAYER_VISIBLE != 0,
+        background: flags & LAYER_BACKGROUND != 0,
+        opacity: if header_flags & FLAG_LAYER_OPACITY != 0 { opacity } else { 255 },
+        blend_mode: blend_mode(blend),
+    })
+}
+
+fn parse_cel(c: &mut Reader<'_>, bpp: usize, frames: &[AseFrame], layer_count: usize) -> Result<Option<AseCel>, String> {
+    let layer = c.u16()? as usize;
+    let x = c.i16()?;
+    let y = c.i16()?;
+    let opacity = c.u8()?;
+    let cel_type = c.u16()?;
+    let z_index = c.i16()?;
+    c.skip(5)?;
+    if layer >= layer_count {
+        return Err("Aseprite cel refers to a missing layer".into());
+    }
+    let (width, height, pixels) = match cel_type {
+        0 | 2 => {
+            let width = c.u16()?;
+            let height = c.u16()?;
+            if width as u64 * height as u64 > MAX_PIXELS {
+                return Err("Aseprite cel is too large".into());
+            }
+            let expected = width as usize * height as usize * bpp;
+            let pixels = if cel_type == 0 {
+                c.bytes(expected)?.to_vec()
+            } else {
+                let rest = &c.data[c.pos..];
+                let mut out = Vec::with_capacity(expected);
+                flate2::read::ZlibDecoder::new(rest)
+                    .take(expected as u64 + 1)
+                    .read_to_end(&mut out)
+                    .map_err(|e| format!("Aseprite cel inflate failed: {}", e))?;
+                if out.len() != expected {
+                    return Err("Aseprite cel has the wrong amount of pixel data".into());
+                }
+                out
+            };
+            (width, height, pixels)
+        }
+        1 => {
+            let linked = c.u16()? as usize;
+            let source = frames
+                .get(linked)
+                .and_then(|f| f.cels.iter().find(|cel| cel.layer == layer))
+                .ok_or_else(|| "Aseprite linked cel points at a missing cel".to_string())?;
+            return Ok(Some(AseCel {
+                linked_frame: Some(linked),
+                z_index,
+                ..source.clone()
+            }));
+        }
+        // Compressed tilemaps need the tileset; not supported.
+        _ => return Ok(None),
+    };
+    Ok(Some(AseCel {
+        layer,
+        x,
+        y,
+        width,
+        height,
+        opacity,
+        z_index,
+        linked_frame: None,
+        pixel_offset: 0,
+        pixel_len: 0,
+        pixels,
+    }))
+}
+
+fn parse_tags(c: &mut Reader<'_>) -> Result<Vec<AseTag>, String> {
+    let count = c.u16()?;
+    c.skip(8)?;
+    let mut tags = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let from = c.u16()?;
+        let to = c.u16()?;
+        let direction = tag_direction(c.u8()?);
+        let repeat = c.u16()?;
+        c.skip(6 + 3 + 1)?;
+        let name = c.string()?;
+        tags.push(AseTag {
+            name,
+            from,
+            to,
+            direction,
+            repeat,
+        });
+    }
+    Ok(tags)
+}
+
+fn parse_palette(c: &mut Reader<'_>, palette: &mut Vec<[u8; 4]>) -> Result<(), String> {
+    let size = c.u32()? as usize;
+    let first = c.u32()? as usize;
+    let last = c.u32()? as usize;
+    c.skip(8)?;
+    if size > MAX_PALETTE_COLORS || first > last || last >= size {
+        return Err("Aseprite palette chunk is corrupt".into());
+    }
+    palette.resize(size, [0, 0, 0, 255]);
+    for index in first..=last {
+        let flags = c.u16()?;
+        let rgba = c.bytes(4)?;
+        if flags & 1 != 0 {
+            c.string()?;
+        }
+        if let Some(entry) = palette.get_mut(index) {
+            *entry = [rgba[0], rgba[1], rgba[2], rgba[3]];
+        }
+    }
+    Ok(())
+}
+
+fn parse_old_palette(c: &mut Reader<'_>, six_bit: bool) -> Result<Vec<[u8; 4]>, String> {
+    let mut palette = Vec::new();
+    let packets = c.u16()?;
+    let mut index = 0usize;
+    for _ in 0..packets {
+        index += c.u8()? as usize;
+        let count = match c.u8()? {
+            0 => 256,
+            n => n as usize,
+        };
+        for _ in 0..count {
+            let rgb = c.bytes(3)?;
+            let scale = |v: u8| if six_bit { (v << 2) | (v >> 4) } else { v };
+            if index < 256 {
+                if palette.len() <= index {
+                    palette.resize(index + 1, [0, 0, 0, 255]);
+                }
+                palette[index] = [scale(rgb[0]), scale(rgb[1]), scale(rgb[2]), 255];
+            }
+            index += 1;
+        }
+    }
+    Ok(palette)
+}
+
+/// Move every cel's pixels out of the document, recording where each lands
+/// in the concatenated blob area. Linked cels point at their source's bytes.
+pub fn take_cel_pixels(doc: &mut AsepriteDocument) -> Vec<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut offset = 0usize;
+    let mut placed: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+    for frame_index in 0..doc.frames.len() {
+        for cel in &mut doc.frames[frame_index].cels {
+            let pixels = std::mem::take(&mut cel.pixels);
+            if let Some(&(at, len)) = cel.linked_frame.and_then(|f| placed.get(&(f, cel.layer))) {
+                cel.pixel_offset = at;
+                cel.pixel_len = len;
+            } else {
+                cel.pixel_offset = offset;
+                cel.pixel_len = pixels.len();
+                offset += pixels.len();
+                out.push(pixels);
+            }
+            placed.insert((frame_index, cel.layer), (cel.pixel_offset, cel.pixel_len));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn chunk(kind: u16, body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 6) as u32).to_le_bytes().to_vec();
+        out.extend_from_slice(&kind.to_le_bytes());
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn frame(duration: u16, chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = chunks.concat();
+        let mut out = ((body.len() + FRAME_HEADER_LEN) as u32).to_le_bytes().to_vec();
+        out.extend_from_slice(&FRAME_MAGIC.to_le_bytes());
+        out.extend_from_slice(&(chunks.len() as u16).to_le_bytes());
+        out.extend_from_slice(&duration.to_le_bytes());
+        out.extend_from_slice(&[0; 2]);
+        out.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+
+    fn cel_header(layer: u16, x: i16, y: i16, cel_type: u16) -> Vec<u8> {
+        let mut out = layer.to_le_bytes().to_vec();
+        out.extend_from_slice(&x.to_le_bytes());
+        out.extend_from_slice(&y.to_le_bytes());
+        out.push(255);
+        out.extend_from_slice(&cel_type.to_le_bytes());
+        out.extend_from_slice(&[0; 7]);
+        out
+    }
+
+    fn document(frame_count: u16, color_depth: u16, frames: &[u8]) -> Vec<u8> {
+        let mut file = ((HEADER_LEN + frames.len()) as u32).to_le_bytes().to_vec();
+        file.extend_from_slice(&HEADER_MAGIC.to_le_bytes());
+        for v in [frame_count, 4, 4, color_depth] {
+            file.extend_from_slice(&v.to_le_bytes());
+        }
+        file.extend_from_slice(&FLAG_LAYER_OPACITY.to_le_bytes());
+        file.resize(HEADER_LEN, 0);
+        file.extend_from_slice(frames);
+        file
+    }
+
+    fn palette_chunk(size: u32) -> Vec<u8> {
+        let mut body = Vec::new();
+        for v in [size, 0, size - 1, 0, 0] {
+            body.extend_from_slice(&v.to_le_bytes());
+        }
+        for i in 0..size {
+            body.extend_from_slice(&[0, 0, i as u8, 0, 0, 255]);
+        }
+        chunk(CHUNK_PALETTE, &body)
+    }
+
+    #[test]
+    fn large_palettes_load_and_used_indices_are_checked() {
+        let mut layer = vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 255, 0, 0, 0];
+        layer.extend_from_slice(&0u16.to_le_bytes());
+        let raw_cel = |index: u8| {
+            let mut cel = cel_header(0, 0, 0, 0);
+            cel.extend_from_slice(&[1, 0, 1, 0, index]);
+            chunk(CHUNK_CEL, &cel)
+        };
+
+        let rgba = document(1, 32, &frame(100, &[palette_chunk(300), chunk(CHUNK_LAYER, &layer)]));
+        assert_eq!(parse(&rgba).unwrap().palette.len(), 300);
+
+        let indexed = |size: u32, index: u8| {
+            document(1, 8, &frame(100, &[palette_chunk(size), chunk(CHUNK_LAYER, &layer), raw_cel(index)]))
+        };
+        assert_eq!(parse(&indexed(300, 200)).unwrap().palette.len(), 256);
+        assert!(parse(&indexed(4, 3)).is_ok());
+        assert!(parse(&indexed(4, 9)).unwrap_err().contains("palette index 9"));
+    }
+
+    #[test]
+    fn indexed_document_keeps_indices_and_links() {
+        let mut layer = vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 128, 0, 0, 0];
+        layer.extend_from_slice(&5u16.to_le_bytes());
+        layer.extend_from_slice(b"Inked");
+
+        let mut palette = Vec::new();
+        for v in [3u32, 0, 2, 0, 0] {
+            palette.extend_from_slice(&v.to_le_bytes());
+        }
+        for rgba in [[0, 0, 0, 0], [255, 0, 0, 255], [0, 0, 255, 255]] {
+            palette.extend_from_slice(&[0, 0]);
+            palette.extend_from_slice(&rgba);
+        }
+
+        let mut compressed = cel_header(0, 1, 2, 2);
+        compressed.extend_from_slice(&[2, 0, 1, 0]);
+        let mut z = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        z.write_all(&[1, 2]).unwrap();
+        compressed.extend_from_slice(&z.finish().unwrap());
+
+        let mut linked = cel_header(0, 0, 0, 1);
+        linked.extend_from_slice(&0u16.to_le_bytes());
+
+        let mut tags = vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        tags.extend_from_slice(&[0, 0, 1, 0, 2, 0, 0]);
+        tags.extend_from_slice(&[0; 10]);
+        tags.extend_from_slice(&4u16.to_le_bytes());
+        tags.extend_from_slice(b"walk");
+
+        let frames = [
+            frame(100, &[chunk(CHUNK_PALETTE, &palette), chunk(CHUNK_LAYER, &layer), chunk(CHUNK_CEL, &compressed)]),
+            frame(150, &[chunk(CHUNK_CEL, &linked), chunk(CHUNK_TAGS, &tags)]),
+        ]
+        .concat();
+        let mut file = document(2, 8, &frames);
+
+        let mut doc = parse(&file).unwrap();
+        assert_eq!(doc.palette, [[0, 0, 0, 0], [255, 0, 0, 255], [0, 0, 255, 255]]);
+        assert_eq!((doc.layers[0].name.as_str(), doc.layers[0].opacity), ("Inked", 128));
+        let durations: Vec<u16> = doc.frames.iter().map(|f| f.duration_ms).collect();
+        assert_eq!(durations, [100, 150]);
+        assert_eq!((doc.tags[0].name.as_str(), doc.tags[0].direction), ("walk", "ping-pong"));
+
+        let linked = &doc.frames[1].cels[0];
+        assert_eq!((linked.linked_frame, linked.x, linked.y, linked.width), (Some(0), 1, 2, 2));
+        let blobs = take_cel_pixels(&mut doc);
+        assert_eq!(blobs, [vec![1, 2]]);
+        assert_eq!(doc.frames[1].cels[0].pixel_offset, doc.frames[0].cels[0].pixel_offset);
+        assert_eq!(doc.frames[1].cels[0].pixel_len, 2);
+
+        file[4] = 0;
+        assert!(parse(&file).is_err());
+    }
+}
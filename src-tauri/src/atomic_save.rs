@@ -0,0 +1,358 @@
+//! Crash-safe file saves with an optional rotating `.bak` history.
+//!
+//! Every save goes to a temp file next to the target, is fsynced, and is then
+//! renamed over the original, so a crash (the release profile aborts on panic)
+//! leaves either the old or the new file on disk, never a truncated one.
+//! `save_all_with_backups` extends that to a bundle of files replaced as one.
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Upper bound on backup generations, so a bad settings file can't make a
+/// single save rotate hundreds of files.
+pub const MAX_BACKUP_GENERATIONS: u32 = 20;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackupSettings {
+    /// Number of `.bak` generations kept per file; 0 disables backups.
+    pub generations: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileBackupEntry {
+    pub generation: u32,
+    pub path: String,
+    pub size: u64,
+    /// Last modification time in milliseconds since the Unix epoch.
+    pub modified_ms: Option<u64>,
+}
+
+/// `front.png` -> `front.png.<generation>.bak`, generation 1 being the newest.
+pub fn backup_path(target: &Path, generation: u32) -> Option<PathBuf> {
+    let name = target.file_name()?.to_string_lossy();
+    Some(target.with_file_name(format!("{}.{}.bak", name, generation)))
+}
+
+/// Hidden sibling of `target`: `.front.png.<uuid>.<ext>`.
+fn sibling_path(target: &Path, ext: &str) -> Result<PathBuf, String> {
+    let name = target
+        .file_name()
+        .ok_or_else(|| "invalid target path".to_string())?
+        .to_string_lossy();
+    Ok(target.with_file_name(format!(".{}.{}.{}", name, uuid::Uuid::new_v4().simple(), ext)))
+}
+
+/// Write `data` to a fresh temp file next to `target` and fsync it. The temp
+/// file is removed if any step fails.
+fn stage(target: &Path, data: &[u8]) -> Result<PathBuf, String> {
+    let tmp = sibling_path(target, "tmp")?;
+    let result = (|| {
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)
+            .map_err(|e| format!("create temp file failed: {}", e))?;
+        file.write_all(data)
+            .map_err(|e| format!("write failed: {}", e))?;
+        file.sync_all()
+            .map_err(|e| format!("fsync failed: {}", e))
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    Ok(tmp)
+}
+
+/// Write `data` to a temp file in the target's directory, fsync it and rename
+/// it over `target`. The temp file is removed if any step fails.
+pub fn write_atomic(target: &Path, data: &[u8]) -> Result<(), String> {
+    let parent = target
+        .parent()
+        .ok_or_else(|| "invalid target path".to_string())?;
+    let tmp = stage(target, data)?;
+    if let Err(e) = std::fs::rename(&tmp, target) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(format!("replace failed: {}", e));
+    }
+    sync_dir(parent);
+    Ok(())
+}
+
+/// Persist the rename itself. Directories can't be opened for fsync on
+/// Windows, where NTFS journals the rename anyway.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) {
+    if let Ok(d) = std::fs::File::open(dir) {
+        let _ = d.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) {}
+
+/// Shift `<file>.1.bak ..` up one generation, deleting generations beyond
+/// `generations`, and return the now free `<file>.1.bak` (`None` when
+/// backups are off).
+fn shift_backups(target: &Path, generations: u32) -> Result<Option<PathBuf>, String> {
+    let generations = generations.min(MAX_BACKUP_GENERATIONS);
+    if generations == 0 {
+        return Ok(None);
+    }
+    let bak = |n: u32| backup_path(target, n).ok_or_else(|| "invalid target path".to_string());
+    // Drop generations that fell off the end, including leftovers from a
+    // previously larger setting.
+    for n in generations..=MAX_BACKUP_GENERATIONS {
+        let p = bak(n)?;
+        if p.exists() {
+            std::fs::remove_file(&p).map_err(|e| format!("remove old backup failed: {}", e))?;
+        }
+    }
+    for n in (1..generations).rev() {
+        let from = bak(n)?;
+        if from.exists() {
+            std::fs::rename(&from, bak(n + 1)?).map_err(|e| format!("rotate backup failed: {}", e))?;
+        }
+    }
+    bak(1).map(Some)
+}
+
+/// Shift the backups up one generation and copy the current target into
+/// `<file>.1.bak`. The target is copied rather than moved so it stays in
+/// place until the atomic rename.
+pub fn rotate_backups(target: &Path, generations: u32) -> Result<(), String> {
+    if !target.is_file() {
+        return Ok(());
+    }
+    if let Some(first) = shift_backups(target, generations)? {
+        std::fs::copy(target, &first).map_err(|e| format!("backup failed: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Rotate backups (if enabled) and atomically replace `target` with `data`.
+pub fn save_with_backups(target: &Path, data: &[u8], generations: u32) -> Result<(), String> {
+    rotate_backups(target, generations)?;
+    write_atomic(target, data)
+}
+
+fn file_label(target: &Path) -> String {
+    target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Rename a staged temp over `target`, first linking (or copying) any
+/// existing file to an aside path so it can be put back. `target` holds a
+/// complete file throughout. Returns the aside path.
+fn replace_keeping_original(target: &Path, tmp: &Path) -> Result<Option<PathBuf>, String> {
+    let aside = if target.exists() {
+        let aside = sibling_path(target, "orig")?;
+        if std::fs::hard_link(target, &aside).is_err() {
+            if let Err(e) = std::fs::copy(target, &aside) {
+                let _ = std::fs::remove_file(&aside);
+                return Err(format!("keep original failed: {}", e));
+            }
+        }
+        Some(aside)
+    } else {
+        None
+    };
+    if let Err(e) = std::fs::rename(tmp, target) {
+        if let Some(aside) = &aside {
+            let _ = std::fs::remove_file(aside);
+        }
+        return Err(format!("replace failed: {}", e));
+    }
+    Ok(aside)
+}
+
+/// Undo `replace_keeping_original` calls, newest first.
+fn roll_back(replaced: &[(&Path, Option<PathBuf>)]) {
+    for (target, aside) in replaced.iter().rev() {
+        let _ = match aside {
+            Some(aside) => std::fs::rename(aside, target),
+            None => std::fs::remove_file(target),
+        };
+    }
+}
+
+/// Replace several files as one unit. Every file is staged (written and
+/// fsynced next to its target) before any target is touched; if replacing
+/// one target fails, the ones already replaced get their originals back;
+/// and backups rotate only once every target has been replaced, with the
+/// kept original becoming `.1.bak`. A crash part-way through the final
+/// renames can leave `.orig` siblings holding the originals.
+pub fn save_all_with_backups(files: &[(&Path, &[u8])], generations: u32) -> Result<(), String> {
+    let discard = |staged: &[(&Path, PathBuf)]| {
+        for (_, tmp) in staged {
+            let _ = std::fs::remove_file(tmp);
+        }
+    };
+    let mut staged: Vec<(&Path, PathBuf)> = Vec::with_capacity(files.len());
+    for (target, data) in files {
+        match stage(target, data) {
+            Ok(tmp) => staged.push((target, tmp)),
+            Err(e) => {
+                discard(&staged);
+                return Err(format!("{} ({})", e, file_label(target)));
+            }
+        }
+    }
+    let mut replaced: Vec<(&Path, Option<PathBuf>)> = Vec::with_capacity(staged.len());
+    for (i, (target, tmp)) in staged.iter().enumerate() {
+        match replace_keeping_original(target, tmp) {
+            Ok(aside) => replaced.push((target, aside)),
+            Err(e) => {
+                roll_back(&replaced);
+                discard(&staged[i..]);
+                return Err(format!("{} ({})", e, file_label(target)));
+            }
+        }
+    }
+    let mut dirs: Vec<&Path> = Vec::new();
+    let mut backup_error = None;
+    for (target, aside) in &replaced {
+        if let Some(aside) = aside {
+            let kept = shift_backups(target, generations).and_then(|first| match first {
+                Some(first) => std::fs::rename(aside, first).map_err(|e| format!("backup failed: {}", e)),
+                None => Ok(()),
+            });
+            if let Err(e) = kept {
+                backup_error.get_or_insert_with(|| format!("{} ({})", e, file_label(target)));
+            }
+            let _ = std::fs::remove_file(aside);
+        }
+        if let Some(parent) = target.parent().filter(|p| !dirs.contains(p)) {
+            dirs.push(parent);
+        }
+    }
+    for dir in dirs {
+        sync_dir(dir);
+    }
+    match backup_error {
+        Some(e) => Err(format!("files were saved, but {}", e)),
+        None => Ok(()),
+    }
+}
+
+pub fn list_backups(target: &Path) -> Vec<FileBackupEntry> {
+    let mut out = Vec::new();
+    for generation in 1..=MAX_BACKUP_GENERATIONS {
+        let Some(p) = backup_path(target, generation) else { break };
+        let Ok(meta) = std::fs::metadata(&p) else { continue };
+        if !meta.is_file() {
+            continue;
+        }
+        let modified_ms = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64);
+        out.push(FileBackupEntry {
+            generation,
+            path: p.to_string_lossy().to_string(),
+            size: meta.len(),
+            modified_ms,
+        });
+    }
+    out
+}
+
+/// Put backup `generation` back in place of `target`. The version being
+/// replaced goes through the normal rotation, so a restore can be undone.
+pub fn restore_backup(target: &Path, generation: u32, generations: u32) -> Result<(), String> {
+    if generation == 0 || generation > MAX_BACKUP_GENERATIONS {
+        return Err("invalid backup generation".into());
+    }
+    let src = backup_path(target, generation).ok_or_else(|| "invalid target path".to_string())?;
+    if !src.is_file() {
+        return Err("backup does not exist".into());
+    }
+    let data = std::fs::read(&src).map_err(|e| format!("read backup failed: {}", e))?;
+    save_with_backups(target, &data, generations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cdpaint-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn write_atomic_replaces_without_leaving_temp_files() {
+        let dir = scratch_dir();
+        let target = dir.join("front.png");
+        write_atomic(&target, b"old").unwrap();
+        write_atomic(&target, b"new").unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn backups_rotate_and_restore() {
+        let dir = scratch_dir();
+        let target = dir.join("front.png");
+        for v in ["v1", "v2", "v3", "v4"] {
+            save_with_backups(&target, v.as_bytes(), 2).unwrap();
+        }
+        let backups = list_backups(&target);
+        assert_eq!(backups.iter().map(|b| b.generation).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(std::fs::read(backup_path(&target, 1).unwrap()).unwrap(), b"v3");
+        assert_eq!(std::fs::read(backup_path(&target, 2).unwrap()).unwrap(), b"v2");
+
+        restore_backup(&target, 2, 2).unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"v2");
+        assert_eq!(std::fs::read(backup_path(&target, 1).unwrap()).unwrap(), b"v4");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn bundle_save_is_all_or_nothing() {
+        let dir = scratch_dir();
+        let front = dir.join("front.png");
+        let pal = dir.join("normal.pal");
+        std::fs::write(&front, b"old front").unwrap();
+        std::fs::write(&pal, b"old pal").unwrap();
+
+        // Staging fails for the second file, so neither target changes.
+        let missing = dir.join("missing").join("back.png");
+        let files: [(&Path, &[u8]); 2] = [(&front, b"new front"), (&missing, b"back")];
+        assert!(save_all_with_backups(&files, 1).is_err());
+        assert_eq!(std::fs::read(&front).unwrap(), b"old front");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        // A failure after some targets were replaced puts them back.
+        let tmp = stage(&front, b"new front").unwrap();
+        let aside = replace_keeping_original(&front, &tmp).unwrap();
+        let created = dir.join("back.png");
+        std::fs::write(&created, b"back").unwrap();
+        roll_back(&[(&front, aside), (&created, None)]);
+        assert_eq!(std::fs::read(&front).unwrap(), b"old front");
+        assert!(!created.exists());
+
+        let files: [(&Path, &[u8]); 2] = [(&front, b"new front"), (&pal, b"new pal")];
+        save_all_with_backups(&files, 1).unwrap();
+        assert_eq!(std::fs::read(&pal).unwrap(), b"new pal");
+        assert_eq!(std::fs::read(backup_path(&front, 1).unwrap()).unwrap(), b"old front");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 4);
+
+        // A target that can't be replaced (a non-empty folder) leaves the
+        // other files and their backup history as they were.
+        let blocked = dir.join("shiny.pal");
+        std::fs::create_dir(&blocked).unwrap();
+        std::fs::write(blocked.join("keep"), b"x").unwrap();
+        let files: [(&Path, &[u8]); 2] = [(&front, b"newer front"), (&blocked, b"shiny")];
+        assert!(save_all_with_backups(&files, 1).is_err());
+        assert_eq!(std::fs::read(&front).unwrap(), b"new front");
+        assert_eq!(std::fs::read(backup_path(&front, 1).unwrap()).unwrap(), b"old front");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 5);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,246 @@
+//! Crash-recovery autosave snapshots under `app_data_dir()/autosave/`.
+//!
+//! Each window writes into its own slot (`autosave/<window label>/`) holding
+//! the document bytes plus a JSON manifest. A slot is cleared when its window
+//! is destroyed, so any slot still present at startup belongs to a session
+//! that crashed. `adopt_orphaned_slots` moves those into `autosave/recovered/`
+//! before this session's windows (which may reuse the `main` label) start
+//! writing, and the frontend offers them back through
+//! `list_recoverable_documents` / `restore_document`.
+use crate::atomic_save;
+use crate::ipc;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Manager;
+
+const RECOVERED_DIR: &str = "recovered";
+const DATA_FILE: &str = "document.bin";
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutosaveManifest {
+    /// Path the document was opened from or last saved to, if any.
+    pub original_path: Option<String>,
+    /// Bit depth mode the canvas was locked to (e.g. "15bpp", "8bpp").
+    pub mode: String,
+    /// Encoding of the snapshot bytes (e.g. "png", "ora").
+    pub format: String,
+    /// Milliseconds since the Unix epoch when the snapshot was written.
+    pub timestamp_ms: u64,
+    pub byte_len: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoverableDocument {
+    pub id: String,
+    pub manifest: AutosaveManifest,
+}
+
+/// Root of the autosave store, resolved once in `setup()`.
+pub struct AutosaveStore(pub Mutex<Option<PathBuf>>);
+
+impl AutosaveStore {
+    fn root(&self) -> Result<PathBuf, String> {
+        self.0
+            .lock()
+            .ok()
+            .and_then(|g| g.clone())
+            .ok_or_else(|| "autosave store unavailable".to_string())
+    }
+}
+
+pub fn store_root(app: &tauri::AppHandle) -> Option<PathBuf> {
+    let dir = app.path().app_data_dir().ok()?;
+    Some(dir.join("autosave"))
+}
+
+/// Window labels are `main` or `file-<uuid>`; anything else is rejected so a
+/// label can never name a directory outside the store.
+fn slot_name(label: &str) -> Result<&str, String> {
+    let ok = !label.is_empty()
+        && label != RECOVERED_DIR
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if ok {
+        Ok(label)
+    } else {
+        Err("invalid window label".into())
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn read_manifest(dir: &Path) -> Option<AutosaveManifest> {
+    let raw = std::fs::read(dir.join(MANIFEST_FILE)).ok()?;
+    let manifest = serde_json::from_slice::<AutosaveManifest>(&raw).ok()?;
+    let len = std::fs::metadata(dir.join(DATA_FILE)).ok()?.len();
+    // A manifest whose byte count doesn't match the data belongs to a
+    // snapshot that was interrupted between the two writes.
+    (len == manifest.byte_len).then_some(manifest)
+}
+
+pub fn write_slot(
+    root: &Path,
+    label: &str,
+    data: &[u8],
+    original_path: Option<String>,
+    mode: String,
+    format: String,
+) -> Result<AutosaveManifest, String> {
+    let dir = root.join(slot_name(label)?);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("create autosave dir failed: {}", e))?;
+    let manifest = AutosaveManifest {
+        original_path,
+        mode,
+        format,
+        timestamp_ms: now_ms(),
+        byte_len: data.len() as u64,
+    };
+    let bytes = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| format!("serialize autosave manifest failed: {}", e))?;
+    atomic_save::write_atomic(&dir.join(DATA_FILE), data)?;
+    atomic_save::write_atomic(&dir.join(MANIFEST_FILE), &bytes)?;
+    Ok(manifest)
+}
+
+pub fn clear_slot(root: &Path, label: &str) -> Result<(), String> {
+    let dir = root.join(slot_name(label)?);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).map_err(|e| format!("clear autosave failed: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Move every slot left behind by a previous session into `recovered/`.
+/// Slots without a consistent manifest are discarded.
+pub fn adopt_orphaned_slots(root: &Path) -> Result<(), String> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Ok(());
+    };
+    let recovered = root.join(RECOVERED_DIR);
+    for entry in entries.flatten() {
+        let p = entry.path();
+        if !p.is_dir() || entry.file_name() == RECOVERED_DIR {
+            continue;
+        }
+        if read_manifest(&p).is_none() {
+            let _ = std::fs::remove_dir_all(&p);
+            continue;
+        }
+        std::fs::create_dir_all(&recovered)
+            .map_err(|e| format!("create recovery dir failed: {}", e))?;
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        std::fs::rename(&p, recovered.join(id))
+            .map_err(|e| format!("move orphaned autosave failed: {}", e))?;
+    }
+    Ok(())
+}
+
+fn recovered_dir(root: &Path, id: &str) -> Result<PathBuf, String> {
+    let id = uuid::Uuid::parse_str(id).map_err(|_| "invalid recovery id".to_string())?;
+    Ok(root.join(RECOVERED_DIR).join(id.simple().to_string()))
+}
+
+pub fn list_recovered(root: &Path) -> Vec<RecoverableDocument> {
+    let Ok(entries) = std::fs::read_dir(root.join(RECOVERED_DIR)) else {
+        return Vec::new();
+    };
+    let mut out: Vec<RecoverableDocument> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let manifest = read_manifest(&entry.path())?;
+            Some(RecoverableDocument {
+                id: entry.file_name().to_string_lossy().to_string(),
+                manifest,
+            })
+        })
+        .collect();
+    out.sort_by_key(|d| std::cmp::Reverse(d.manifest.timestamp_ms));
+    out
+}
+
+pub fn read_recovered(root: &Path, id: &str) -> Result<Vec<u8>, String> {
+    let dir = recovered_dir(root, id)?;
+    if read_manifest(&dir).is_none() {
+        return Err("recoverable document not found".into());
+    }
+    std::fs::read(dir.join(DATA_FILE)).map_err(|e| format!("read failed: {}", e))
+}
+
+pub fn discard_recovered(root: &Path, id: &str) -> Result<(), String> {
+    let dir = recovered_dir(root, id)?;
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).map_err(|e| format!("discard failed: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Body: the document bytes. Headers: `mode`, `format` and `original-path`
+/// (optional).
+#[tauri::command]
+pub fn write_autosave(
+    window: tauri::Window,
+    store: tauri::State<'_, AutosaveStore>,
+    request: tauri::ipc::Request<'_>,
+) -> Result<AutosaveManifest, String> {
+    write_slot(
+        &store.root()?,
+        window.label(),
+        ipc::raw_body(&request)?,
+        ipc::header(&request, "original-path")?,
+        ipc::required_header(&request, "mode")?,
+        ipc::required_header(&request, "format")?,
+    )
+}
+
+#[tauri::command]
+pub fn clear_autosave(window: tauri::Window, store: tauri::State<'_, AutosaveStore>) -> Result<(), String> {
+    clear_slot(&store.root()?, window.label())
+}
+
+#[tauri::command]
+pub fn list_recoverable_documents(store: tauri::State<'_, AutosaveStore>) -> Result<Vec<RecoverableDocument>, String> {
+    Ok(list_recovered(&store.root()?))
+}
+
+#[tauri::command]
+pub fn restore_document(store: tauri::State<'_, AutosaveStore>, id: String) -> Result<tauri::ipc::Response, String> {
+    read_recovered(&store.root()?, &id).map(tauri::ipc::Response::new)
+}
+
+#[tauri::command]
+pub fn discard_recoverable_document(store: tauri::State<'_, AutosaveStore>, id: String) -> Result<(), String> {
+    discard_recovered(&store.root()?, &id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orphaned_slots_become_recoverable() {
+        let root = std::env::temp_dir().join(format!("cdpaint-test-{}", uuid::Uuid::new_v4()));
+        write_slot(&root, "main", b"pixels", Some("/a/front.png".into()), "8bpp".into(), "png".into()).unwrap();
+        write_slot(&root, "file-1", b"gone", None, "15bpp".into(), "png".into()).unwrap();
+        clear_slot(&root, "file-1").unwrap();
+        assert!(write_slot(&root, "../escape", b"x", None, String::new(), String::new()).is_err());
+
+        adopt_orphaned_slots(&root).unwrap();
+        let docs = list_recovered(&root);
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0].manifest.original_path.as_deref(), Some("/a/front.png"));
+        assert_eq!(read_recovered(&root, &docs[0].id).unwrap(), b"pixels");
+        assert!(!root.join("main").exists());
+
+        discard_recovered(&root, &docs[0].id).unwrap();
+        assert!(list_recovered(&root).is_empty());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
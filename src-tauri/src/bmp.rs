@@ -0,0 +1,360 @@
+//! Paletted BMP files.
+//!
+//! The webview only decodes BMPs to RGBA and only ever writes 24/32-bit
+//! ones, so indexed BMPs (still required by some older hacking tools) lose
+//! their palette on import. This reads 1/4/8-bit BMPs, uncompressed or
+//! RLE4/RLE8, into one index per pixel plus the palette, and writes 4/8-bit
+//! uncompressed BMPs back with exactly the palette given.
+use serde::{Deserialize, Serialize};
+
+/// Refuse images whose index buffer would exceed this many pixels.
+const MAX_PIXELS: u64 = 1 << 28;
+
+const FILE_HEADER_LEN: usize = 14;
+const INFO_HEADER_LEN: usize = 40;
+const BI_RGB: u32 = 0;
+const BI_RLE8: u32 = 1;
+const BI_RLE4: u32 = 2;
+/// 72 DPI, what Windows writes by default.
+const PIXELS_PER_METER: u32 = 2835;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BmpCompression {
+    None,
+    Rle4,
+    Rle8,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexedBmpInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Bits per index as stored: 1, 4 or 8.
+    pub bit_depth: u8,
+    pub compression: BmpCompression,
+    /// Every palette entry, used or not, in file order.
+    pub palette: Vec<[u8; 3]>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexedBmp {
+    pub info: IndexedBmpInfo,
+    /// One index per pixel, row-major, top row first.
+    pub indices: Vec<u8>,
+}
+
+fn u16_at(b: &[u8], at: usize) -> Result<u16, String> {
+    b.get(at..at + 2)
+        .map(|s| u16::from_le_bytes([s[0], s[1]]))
+        .ok_or_else(|| "BMP header is truncated".to_string())
+}
+
+fn u32_at(b: &[u8], at: usize) -> Result<u32, String> {
+    b.get(at..at + 4)
+        .map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+        .ok_or_else(|| "BMP header is truncated".to_string())
+}
+
+fn row_stride(width: u32, bit_depth: u8) -> usize {
+    (width as usize * bit_depth as usize).div_ceil(32) * 4
+}
+
+/// Decode a paletted BMP without touching colors.
+pub fn decode_indexed(bytes: &[u8]) -> Result<IndexedBmp, String> {
+    if !bytes.starts_with(b"BM") {
+        return Err("not a BMP file".into());
+    }
+    let data_offset = u32_at(bytes, 10)? as usize;
+    let dib_len = u32_at(bytes, FILE_HEADER_LEN)? as usize;
+    let core = dib_len == 12;
+    let (width, height, bit_depth, compression, colors_used) = if core {
+        let w = u16_at(bytes, 18)? as i64;
+        let h = u16_at(bytes, 20)? as i64;
+        (w, h, u16_at(bytes, 24)?, BI_RGB, 0)
+    } else if dib_len >= INFO_HEADER_LEN {
+        let w = u32_at(bytes, 18)? as i32 as i64;
+        let h = u32_at(bytes, 22)? as i32 as i64;
+        (w, h, u16_at(bytes, 28)?, u32_at(bytes, 30)?, u32_at(bytes, 46)?)
+    } else {
+        return Err("BMP has an unknown header size".into());
+    };
+    if !matches!(bit_depth, 1 | 4 | 8) {
+        return Err(format!("BMP is not paletted ({} bits per pixel)", bit_depth));
+    }
+    let bit_depth = bit_depth as u8;
+    let compression = match (compression, bit_depth) {
+        (BI_RGB, _) => BmpCompression::None,
+        (BI_RLE8, 8) => BmpCompression::Rle8,
+        (BI_RLE4, 4) => BmpCompression::Rle4,
+        (c, _) => return Err(format!("BMP uses unsupported compression {}", c)),
+    };
+    let top_down = height < 0;
+    if top_down && compression != BmpCompression::None {
+        return Err("top-down BMPs cannot be RLE compressed".into());
+    }
+    let (width, height) = (width, height.abs());
+    if width <= 0 || height == 0 {
+        return Err("BMP has zero width or height".into());
+    }
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err("BMP dimensions are too large".into());
+    }
+    let (width, height) = (width as u32, height as u32);
+
+    let max_colors = 1usize << bit_depth;
+    let count = match colors_used as usize {
+        0 => max_colors,
+        n if n <= max_colors => n,
+        _ => return Err("BMP palette is larger than its bit depth allows".into()),
+    };
+    let entry_len = if core { 3 } else { 4 };
+    let palette_start = FILE_HEADER_LEN + dib_len;
+    let palette_bytes = bytes
+        .get(palette_start..palette_start + count * entry_len)
+        .ok_or_else(|| "BMP palette is truncated".to_string())?;
+    let palette: Vec<[u8; 3]> = palette_bytes.chunks_exact(entry_len).map(|c| [c[2], c[1], c[0]]).collect();
+
+    let pixels = bytes.get(data_offset..).ok_or_else(|| "BMP pixel data is missing".to_string())?;
+    let (w, h) = (width as usize, height as usize);
+    let mut indices = vec![0u8; w * h];
+    match compression {
+        BmpCompression::None => {
+            let stride = row_stride(width, bit_depth);
+            if pixels.len() < stride * h {
+                return Err("BMP pixel data is truncated".into());
+            }
+            let per_byte = 8 / bit_depth as usize;
+            for (stored, row) in pixels.chunks_exact(stride).take(h).enumerate() {
+                let y = if top_down { stored } else { h - 1 - stored };
+                for x in 0..w {
+                    let shift = 8 - bit_depth as usize * (x % per_byte + 1);
+                    indices[y * w + x] = (row[x / per_byte] >> shift) & (max_colors - 1) as u8;
+                }
+            }
+        }
+        BmpCompression::Rle8 | BmpCompression::Rle4 => {
+            decode_rle(pixels, compression == BmpCompression::Rle4, w, h, &mut indices)?;
+        }
+    }
+    if let Some(bad) = indices.iter().find(|&&i| i as usize >= palette.len()) {
+        return Err(format!("BMP pixel uses index {} outside its {}-entry palette", bad, palette.len()));
+    }
+    Ok(IndexedBmp {
+        info: IndexedBmpInfo {
+            width,
+            height,
+            bit_depth,
+            compression,
+            palette,
+        },
+        indices,
+    })
+}
+
+/// RLE4/RLE8 streams are bottom-up. Pixels skipped by a delta or an early
+/// end of line stay at index 0; runs past the right edge are clipped.
+fn decode_rle(data: &[u8], nibbles: bool, w: usize, h: usize, out: &mut [u8]) -> Result<(), String> {
+    let truncated = || "BMP RLE data is truncated".to_string();
+    let (mut x, mut y, mut pos) = (0usize, 0usize, 0usize);
+    let mut put = |x: usize, y: usize, v: u8| {
+        if x < w && y < h {
+            out[(h - 1 - y) * w + x] = v;
+        }
+    };
+    loop {
+        let (count, value) = match data.get(pos..pos + 2) {
+            Some(pair) => (pair[0] as usize, pair[1]),
+            None => return Err(truncated()),
+        };
+        pos += 2;
+        if count > 0 {
+            for k in 0..count {
+                let v = match (nibbles, k % 2) {
+                    (false, _) => value,
+                    (true, 0) => value >> 4,
+                    (true, _) => value & 0x0f,
+                };
+                put(x + k, y, v);
+            }
+            x += count;
+            continue;
+        }
+        match value {
+            0 => {
+                x = 0;
+                y += 1;
+            }
+            1 => return Ok(()),
+            2 => {
+                let delta = data.get(pos..pos + 2).ok_or_else(truncated)?;
+                x += delta[0] as usize;
+                y += delta[1] as usize;
+                pos += 2;
+            }
+            n => {
+                let n = n as usize;
+                let len = if nibbles { n.div_ceil(2) } else { n };
+                let run = data.get(pos..pos + len).ok_or_else(truncated)?;
+                for k in 0..n {
+                    let v = match (nibbles, k % 2) {
+                        (false, _) => run[k],
+                        (true, 0) => run[k / 2] >> 4,
+                        (true, _) => run[k / 2] & 0x0f,
+                    };
+                    put(x + k, y, v);
+                }
+                x += n;
+                // Absolute runs are padded to a 16-bit boundary.
+                pos += len + len % 2;
+            }
+        }
+        if y >= h {
+            return Ok(());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexedBmpOptions {
+    pub width: u32,
+    pub height: u32,
+    pub palette: Vec<[u8; 3]>,
+    /// 4 or 8. Defaults to 4 when the palette has at most 16 entries.
+    #[serde(default)]
+    pub bit_depth: Option<u8>,
+}
+
+/// Encode one index per pixel as an uncompressed bottom-up BMP. Only the
+/// given palette entries are written (`biClrUsed`), so the palette reads
+/// back exactly.
+pub fn encode_indexed(opts: &IndexedBmpOptions, indices: &[u8]) -> Result<Vec<u8>, String> {
+    let (width, height) = (opts.width, opts.height);
+    if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err("image has an invalid width or height".into());
+    }
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err("image dimensions are too large".into());
+    }
+    if indices.len() as u64 != width as u64 * height as u64 {
+        return Err("index buffer does not match the image size".into());
+    }
+    if opts.palette.is_empty() || opts.palette.len() > 256 {
+        return Err("palette must have 1 to 256 entries".into());
+    }
+    let bit_depth = match opts.bit_depth {
+        None if opts.palette.len() <= 16 => 4,
+        None | Some(8) => 8,
+        Some(4) if opts.palette.len() <= 16 => 4,
+        Some(4) => return Err(format!("{} palette entries do not fit in 4 bits", opts.palette.len())),
+        Some(bd) => return Err(format!("BMPs can only be written at 4 or 8 bits per pixel, not {}", bd)),
+    };
+    if let Some(bad) = indices.iter().find(|&&i| i as usize >= opts.palette.len()) {
+        return Err(format!("index {} is outside the {}-entry palette", bad, opts.palette.len()));
+    }
+
+    let (w, h) = (width as usize, height as usize);
+    let stride = row_stride(width, bit_depth);
+    let data_offset = FILE_HEADER_LEN + INFO_HEADER_LEN + opts.palette.len() * 4;
+    let image_len = stride * h;
+    let file_len = u32::try_from(data_offset + image_len).map_err(|_| "BMP would be too large".to_string())?;
+
+    let mut out = Vec::with_capacity(file_len as usize);
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&file_len.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(data_offset as u32).to_le_bytes());
+    out.extend_from_slice(&(INFO_HEADER_LEN as u32).to_le_bytes());
+    out.extend_from_slice(&(width as i32).to_le_bytes());
+    out.extend_from_slice(&(height as i32).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&(bit_depth as u16).to_le_bytes());
+    out.extend_from_slice(&BI_RGB.to_le_bytes());
+    out.extend_from_slice(&(image_len as u32).to_le_bytes());
+    out.extend_from_slice(&PIXELS_PER_METER.to_le_bytes());
+    out.extend_from_slice(&PIXELS_PER_METER.to_le_bytes());
+    out.extend_from_slice(&(opts.palette.len() as u32).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    for [r, g, b] in &opts.palette {
+        out.extend_from_slice(&[*b, *g, *r, 0]);
+    }
+    for row in indices.chunks_exact(w).rev() {
+        let start = out.len();
+        out.resize(start + stride, 0);
+        match bit_depth {
+            8 => out[start..start + w].copy_from_slice(row),
+            _ => {
+                for (x, &index) in row.iter().enumerate() {
+                    out[start + x / 2] |= if x % 2 == 0 { index << 4 } else { index };
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_round_trips_with_exact_palette() {
+        let palette = vec![[255, 0, 255], [0, 0, 0], [255, 0, 255]];
+        let opts = IndexedBmpOptions {
+            width: 3,
+            height: 2,
+            palette: palette.clone(),
+            bit_depth: None,
+        };
+        let indices = [0, 1, 2, 2, 1, 0];
+        let bmp = encode_indexed(&opts, &indices).unwrap();
+        let img = decode_indexed(&bmp).unwrap();
+        assert_eq!(img.info.bit_depth, 4);
+        assert_eq!(img.info.palette, palette);
+        assert_eq!(img.indices, indices);
+
+        let eight = IndexedBmpOptions { bit_depth: Some(8), ..opts.clone() };
+        let img = decode_indexed(&encode_indexed(&eight, &indices).unwrap()).unwrap();
+        assert_eq!((img.info.bit_depth, img.indices.as_slice()), (8, &indices[..]));
+        assert!(encode_indexed(&opts, &[0, 1, 2, 3, 0, 0]).is_err());
+    }
+
+    fn rle_file(bit_depth: u16, compression: u32, w: i32, h: i32, colors: u32, data: &[u8]) -> Vec<u8> {
+        let offset = 14 + 40 + colors as usize * 4;
+        let mut out = b"BM".to_vec();
+        out.extend_from_slice(&((offset + data.len()) as u32).to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(offset as u32).to_le_bytes());
+        out.extend_from_slice(&40u32.to_le_bytes());
+        out.extend_from_slice(&w.to_le_bytes());
+        out.extend_from_slice(&h.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&bit_depth.to_le_bytes());
+        out.extend_from_slice(&compression.to_le_bytes());
+        out.extend_from_slice(&[0; 12]);
+        out.extend_from_slice(&colors.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        for i in 0..colors {
+            out.extend_from_slice(&[i as u8, i as u8, i as u8, 0]);
+        }
+        out.extend_from_slice(data);
+        out
+    }
+
+    #[test]
+    fn rle_streams_decode_bottom_up() {
+        // Bottom row: run of three 2s, then absolute [1, 3, 1]; end of line;
+        // top row: delta (2, 0) then one 3; end of bitmap.
+        let rle8 = [3, 2, 0, 3, 1, 3, 1, 0, 0, 0, 0, 2, 2, 0, 1, 3, 0, 1];
+        let img = decode_indexed(&rle_file(8, BI_RLE8, 6, 2, 4, &rle8)).unwrap();
+        assert_eq!(img.info.compression, BmpCompression::Rle8);
+        assert_eq!(img.indices, [0, 0, 3, 0, 0, 0, 2, 2, 2, 1, 3, 1]);
+
+        // Run of four alternating 1/2, absolute nibbles [3, 0, 3]; end of bitmap.
+        let rle4 = [4, 0x12, 0, 3, 0x30, 0x30, 0, 1];
+        let img = decode_indexed(&rle_file(4, BI_RLE4, 7, 1, 4, &rle4)).unwrap();
+        assert_eq!(img.indices, [1, 2, 1, 2, 3, 0, 3]);
+
+        assert!(decode_indexed(&rle_file(8, BI_RLE8, 6, -2, 4, &rle8)).is_err());
+    }
+}
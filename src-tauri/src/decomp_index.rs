@@ -0,0 +1,192 @@
+//! Species index for a pokeemerald-style decomp.
+//!
+//! `include/constants/species.h` gives the species constants and numbers;
+//! the `INCBIN` lines in `src/data/graphics/pokemon.h` (and, for the
+//! animated front pics, `src/anim_mon_front_pics.c`) give each species'
+//! graphics, named `gMon<Kind>_<Species>`. A symbol belongs to the constant
+//! whose name matches once case and underscores are dropped
+//! (`gMonFrontPic_MrMime` is `SPECIES_MR_MIME`). Build outputs map back to
+//! their sources: `front.4bpp.lz` is `front.png`, `normal.gbapal.lz` is
+//! `normal.pal`.
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const SPECIES_HEADER: &str = "include/constants/species.h";
+pub const GRAPHICS_HEADER: &str = "src/data/graphics/pokemon.h";
+/// Holds the `gMonFrontPic_*` symbols; optional, since some forks move them
+/// into `GRAPHICS_HEADER`.
+pub const FRONT_PICS_SOURCE: &str = "src/anim_mon_front_pics.c";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SpeciesAssets {
+    /// `SPECIES_BULBASAUR`; `None` for graphics no constant matches, like
+    /// the question-mark placeholders.
+    pub constant: Option<String>,
+    /// Value of a numeric `#define`.
+    pub id: Option<u32>,
+    /// Symbol suffix, e.g. `Bulbasaur`.
+    pub name: String,
+    pub front: Option<String>,
+    pub still_front: Option<String>,
+    pub back: Option<String>,
+    pub icon: Option<String>,
+    pub footprint: Option<String>,
+    pub normal_palette: Option<String>,
+    pub shiny_palette: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DecompIndex {
+    pub root: String,
+    pub species: Vec<SpeciesAssets>,
+}
+
+/// The decomp root for a hooked folder: the folder itself, or its parent
+/// when the hooked folder is `graphics/`.
+pub fn find_root(hooked: &Path) -> Option<PathBuf> {
+    [Some(hooked), hooked.parent()]
+        .into_iter()
+        .flatten()
+        .find(|dir| dir.join(SPECIES_HEADER).is_file() && dir.join(GRAPHICS_HEADER).is_file())
+        .map(Path::to_path_buf)
+}
+
+fn fold(name: &str) -> String {
+    name.chars().filter(|c| c.is_ascii_alphanumeric()).map(|c| c.to_ascii_lowercase()).collect()
+}
+
+/// `#define SPECIES_X <value>` lines, in file order; the value is kept only
+/// when it is a plain number.
+pub fn parse_species(header: &str) -> Vec<(String, Option<u32>)> {
+    header
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.trim().strip_prefix("#define")?.split_whitespace();
+            let name = words.next()?;
+            if !name.starts_with("SPECIES_") {
+                return None;
+            }
+            let value = words.next().and_then(|v| match v.strip_prefix("0x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => v.parse().ok(),
+            });
+            Some((name.to_string(), value))
+        })
+        .collect()
+}
+
+/// `(kind, species suffix, path)` for every `gMon<Kind>_<Species>[] =
+/// INCBIN_Uxx("path")` line.
+pub fn parse_incbins(source: &str) -> Vec<(String, String, String)> {
+    source
+        .lines()
+        .filter_map(|line| {
+            let symbol_at = line.find("gMon")?;
+            let symbol: String = line[symbol_at + 4..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+                .collect();
+            let (kind, name) = symbol.split_once('_')?;
+            let rest = &line[line.find("INCBIN_U")?..];
+            let path = rest.split('"').nth(1)?;
+            Some((kind.to_string(), name.to_string(), path.to_string()))
+        })
+        .collect()
+}
+
+/// Editable source for a built file: `graphics/pokemon/a/front.4bpp.lz`
+/// becomes `graphics/pokemon/a/front.png`, a `.gbapal` becomes `.pal`.
+fn source_path(built: &str) -> String {
+    let (dir, file) = built.rsplit_once('/').unwrap_or(("", built));
+    let (stem, ext) = file.split_once('.').unwrap_or((file, ""));
+    let ext = if ext.starts_with("gbapal") { "pal" } else { "png" };
+    if dir.is_empty() {
+        format!("{}.{}", stem, ext)
+    } else {
+        format!("{}/{}.{}", dir, stem, ext)
+    }
+}
+
+/// `graphics_sources` are the texts of `GRAPHICS_HEADER` and, when present,
+/// `FRONT_PICS_SOURCE`.
+pub fn build(root: &Path, species_header: &str, graphics_sources: &[&str]) -> DecompIndex {
+    let constants = parse_species(species_header);
+    let by_fold: BTreeMap<String, (String, Option<u32>)> = constants
+        .iter()
+        .map(|(name, id)| (fold(&name["SPECIES_".len()..]), (name.clone(), *id)))
+        .collect();
+
+    let mut species: BTreeMap<String, SpeciesAssets> = BTreeMap::new();
+    for (kind, name, built) in graphics_sources.iter().flat_map(|source| parse_incbins(source)) {
+        let key = fold(&name);
+        let entry = species.entry(key.clone()).or_insert_with(|| {
+            let (constant, id) = by_fold.get(&key).cloned().map_or((None, None), |(c, id)| (Some(c), id));
+            SpeciesAssets {
+                constant,
+                id,
+                name: name.clone(),
+                ..Default::default()
+            }
+        });
+        let slot = match kind.as_str() {
+            "FrontPic" => &mut entry.front,
+            "StillFrontPic" => &mut entry.still_front,
+            "BackPic" => &mut entry.back,
+            "Icon" => &mut entry.icon,
+            "Footprint" => &mut entry.footprint,
+            "Palette" => &mut entry.normal_palette,
+            "ShinyPalette" => &mut entry.shiny_palette,
+            _ => continue,
+        };
+        let path = root.join(source_path(&built));
+        *slot = Some(path.to_string_lossy().to_string());
+    }
+
+    let mut species: Vec<SpeciesAssets> = species.into_values().collect();
+    // Numbered species first in dex order, then the rest by name.
+    species.sort_by(|a, b| {
+        (a.id.is_none(), a.id, a.name.to_ascii_lowercase()).cmp(&(b.id.is_none(), b.id, b.name.to_ascii_lowercase()))
+    });
+    DecompIndex {
+        root: root.to_string_lossy().to_string(),
+        species,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_incbins_to_species_sources() {
+        let species_h = "#define SPECIES_NONE 0\n#define SPECIES_BULBASAUR 1\n#define SPECIES_MR_MIME 122\n#define SPECIES_UNOWN_B (NUM_SPECIES + 0)\n";
+        let pokemon_h = r#"
+const u32 gMonStillFrontPic_Bulbasaur[] = INCBIN_U32("graphics/pokemon/bulbasaur/front.4bpp.lz");
+const u32 gMonPalette_Bulbasaur[] = INCBIN_U32("graphics/pokemon/bulbasaur/normal.gbapal.lz");
+const u32 gMonShinyPalette_Bulbasaur[] = INCBIN_U32("graphics/pokemon/bulbasaur/shiny.gbapal.lz");
+const u8 gMonFootprint_Bulbasaur[] = INCBIN_U8("graphics/pokemon/bulbasaur/footprint.1bpp");
+const u32 gMonStillFrontPic_MrMime[] = INCBIN_U32("graphics/pokemon/mr_mime/front.4bpp.lz");
+const u8 gMonIcon_UnownB[] = INCBIN_U8("graphics/pokemon/unown/b/icon.4bpp");
+"#;
+        let anim_mon_front_pics_c = r#"
+const u32 gMonFrontPic_MrMime[] = INCBIN_U32("graphics/pokemon/mr_mime/anim_front.4bpp.lz");
+const u32 gMonFrontPic_CircledQuestionMark[] = INCBIN_U32("graphics/pokemon/circled_question_mark/anim_front.4bpp.lz");
+"#;
+        let index = build(Path::new("/emerald"), species_h, &[pokemon_h, anim_mon_front_pics_c]);
+        let names: Vec<_> = index.species.iter().map(|s| (s.name.as_str(), s.id)).collect();
+        assert_eq!(names, [("Bulbasaur", Some(1)), ("MrMime", Some(122)), ("CircledQuestionMark", None), ("UnownB", None)]);
+
+        let bulbasaur = &index.species[0];
+        assert_eq!(bulbasaur.constant.as_deref(), Some("SPECIES_BULBASAUR"));
+        let p = |s: &str| Path::new("/emerald").join(s).to_string_lossy().to_string();
+        assert_eq!(bulbasaur.still_front, Some(p("graphics/pokemon/bulbasaur/front.png")));
+        assert_eq!(bulbasaur.shiny_palette, Some(p("graphics/pokemon/bulbasaur/shiny.pal")));
+        assert_eq!(bulbasaur.footprint, Some(p("graphics/pokemon/bulbasaur/footprint.png")));
+        let mr_mime = &index.species[1];
+        assert_eq!(mr_mime.front, Some(p("graphics/pokemon/mr_mime/anim_front.png")));
+        assert_eq!(mr_mime.still_front, Some(p("graphics/pokemon/mr_mime/front.png")));
+        assert_eq!(index.species[3].constant.as_deref(), Some("SPECIES_UNOWN_B"));
+        assert_eq!(index.species[2].constant, None);
+    }
+}
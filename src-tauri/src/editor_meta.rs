@@ -0,0 +1,175 @@
+//! CDPaint editing state stored inside saved PNGs.
+//!
+//! The bit-depth lock, palette lock, tile size and export asset type
+//! are written as a compressed iTXt chunk with the keyword `CDPaint` whose
+//! text is versioned JSON. To other editors it is just one more text chunk,
+//! so the file stays an ordinary PNG; `read_image_metadata` hands it back
+//! when the file is opened again.
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+use crate::png;
+
+pub const KEYWORD: &[u8] = b"CDPaint";
+pub const VERSION: u32 = 1;
+
+/// Decompressed metadata larger than this is ignored.
+const MAX_TEXT_BYTES: u64 = 1 << 20;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EditorMetadata {
+    #[serde(default)]
+    pub version: u32,
+    /// Color mode the document was locked to: 1, 4 or 8 (indexed), 15
+    /// (RGB555), 16 (RGB565) or 24 (full color).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bit_depth: Option<u8>,
+    /// The palette may not be changed by painting.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub palette_locked: bool,
+    /// Tile grid size in pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tile_size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export_asset_type: Option<String>,
+    /// Fields written by newer versions, kept as they are.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl EditorMetadata {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(bd) = self.bit_depth {
+            if !matches!(bd, 1 | 2 | 4 | 8 | 15 | 16 | 24) {
+                return Err(format!("invalid bit depth {} in editor metadata", bd));
+            }
+        }
+        if self.tile_size == Some(0) {
+            return Err("tile size must be at least 1".into());
+        }
+        Ok(())
+    }
+}
+
+fn keyword(data: &[u8]) -> &[u8] {
+    data.split(|&b| b == 0).next().unwrap_or_default()
+}
+
+pub(crate) fn is_ours(chunk: &png::Chunk<'_>) -> bool {
+    matches!(&chunk.kind, b"tEXt" | b"zTXt" | b"iTXt") && keyword(chunk.data) == KEYWORD
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    flate2::read::ZlibDecoder::new(data)
+        .take(MAX_TEXT_BYTES + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| format!("inflate failed: {}", e))?;
+    if out.len() as u64 > MAX_TEXT_BYTES {
+        return Err("editor metadata is too large".into());
+    }
+    Ok(out)
+}
+
+/// The text of a tEXt/zTXt/iTXt chunk.
+fn chunk_text(chunk: &png::Chunk<'_>) -> Result<Vec<u8>, String> {
+    let rest = &chunk.data[keyword(chunk.data).len()..];
+    let rest = rest.strip_prefix(&[0]).ok_or("text chunk has no keyword terminator")?;
+    match &chunk.kind {
+        b"tEXt" => Ok(rest.to_vec()),
+        b"zTXt" => match rest.split_first() {
+            Some((0, compressed)) => inflate(compressed),
+            _ => Err("zTXt uses an unknown compression method".into()),
+        },
+        _ => {
+            let [flag, method, tail @ ..] = rest else {
+                return Err("iTXt chunk is truncated".into());
+            };
+            // Skip the language tag and the translated keyword.
+            let mut fields = tail.splitn(3, |&b| b == 0);
+            let text = match (fields.next(), fields.next(), fields.next()) {
+                (Some(_), Some(_), Some(text)) => text,
+                _ => return Err("iTXt chunk is truncated".into()),
+            };
+            match (flag, method) {
+                (0, _) => Ok(text.to_vec()),
+                (1, 0) => inflate(text),
+                _ => Err("iTXt uses an unknown compression method".into()),
+            }
+        }
+    }
+}
+
+/// The metadata stored in a PNG, if it has any.
+pub fn extract(bytes: &[u8]) -> Result<Option<EditorMetadata>, String> {
+    let chunks = png::parse_chunks(bytes)?;
+    let Some(chunk) = chunks.iter().find(|c| is_ours(c)) else {
+        return Ok(None);
+    };
+    let text = chunk_text(chunk)?;
+    serde_json::from_slice(&text)
+        .map(Some)
+        .map_err(|e| format!("invalid editor metadata: {}", e))
+}
+
+/// `bytes` with `meta` stored as the (only) CDPaint iTXt chunk, ahead of the
+/// image data.
+pub fn embed(bytes: &[u8], meta: &EditorMetadata) -> Result<Vec<u8>, String> {
+    meta.validate()?;
+    let meta = EditorMetadata {
+        version: VERSION,
+        ..meta.clone()
+    };
+    let json = serde_json::to_vec(&meta).map_err(|e| format!("serialize editor metadata failed: {}", e))?;
+    let mut z = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+    z.write_all(&json).map_err(|e| format!("deflate failed: {}", e))?;
+    let compressed = z.finish().map_err(|e| format!("deflate failed: {}", e))?;
+    let mut itxt = KEYWORD.to_vec();
+    // Terminator, compressed, zlib, empty language tag and translated keyword.
+    itxt.extend_from_slice(&[0, 1, 0, 0, 0]);
+    itxt.extend_from_slice(&compressed);
+
+    let chunks = png::parse_chunks(bytes)?;
+    let mut out = Vec::with_capacity(bytes.len() + itxt.len() + 12);
+    out.extend_from_slice(&png::SIGNATURE);
+    let mut placed = false;
+    for chunk in chunks.iter().filter(|c| !is_ours(c)) {
+        if !placed && (chunk.is(b"IDAT") || chunk.is(b"IEND")) {
+            png::write_chunk(&mut out, b"iTXt", &itxt);
+            placed = true;
+        }
+        png::write_chunk(&mut out, &chunk.kind, chunk.data);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::chunk;
+
+    #[test]
+    fn metadata_round_trips_and_replaces_older_copies() {
+        let mut png_bytes = png::SIGNATURE.to_vec();
+        for c in [
+            chunk(b"IHDR", &[0; 13]),
+            chunk(b"tEXt", b"CDPaint\0{\"version\":1,\"tile_size\":8}"),
+            chunk(b"IDAT", b"px"),
+            chunk(b"IEND", &[]),
+        ] {
+            png_bytes.extend_from_slice(&c);
+        }
+        assert_eq!(extract(&png_bytes).unwrap().unwrap().tile_size, Some(8));
+
+        let mut meta: EditorMetadata =
+            serde_json::from_str(r#"{"bit_depth":15,"palette_locked":true,"tile_size":16,"future":true}"#).unwrap();
+        let saved = embed(&png_bytes, &meta).unwrap();
+        let kinds: Vec<[u8; 4]> = png::parse_chunks(&saved).unwrap().iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, [*b"IHDR", *b"iTXt", *b"IDAT", *b"IEND"]);
+
+        meta.version = VERSION;
+        assert_eq!(extract(&saved).unwrap(), Some(meta.clone()));
+        meta.bit_depth = Some(12);
+        assert!(embed(&png_bytes, &meta).is_err());
+    }
+}
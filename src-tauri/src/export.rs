@@ -0,0 +1,236 @@
+//! Multi-file export planning: what writing a bundle into a folder would do
+//! to the files already there, and the policy for acting on it.
+//!
+//! Export bundles land next to hand-tuned assets (`front.png`, `normal.pal`
+//! in a decomp sprite folder), so callers can ask for a dry run first
+//! (`preview_export_files`) and pass an explicit `policy` header to the
+//! write commands instead of always overwriting. The write itself is
+//! all-or-nothing (`atomic_save::save_all_with_backups`) and answers with an
+//! `ExportReport`.
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportPolicy {
+    /// Replace existing files (backups still rotate as configured).
+    #[default]
+    Overwrite,
+    /// Leave every existing file alone and only create new ones.
+    SkipExisting,
+    /// Refuse the whole export if any existing file would change.
+    FailOnDifference,
+}
+
+impl ExportPolicy {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(str::trim) {
+            None | Some("") => Ok(Self::default()),
+            Some(v) => serde_json::from_value(serde_json::Value::String(v.to_string()))
+                .map_err(|_| format!("unknown export policy: {}", v)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportAction {
+    Create,
+    OverwriteIdentical,
+    OverwriteDifferent,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportPlanEntry {
+    pub name: String,
+    pub path: String,
+    pub action: ExportAction,
+    pub size: u64,
+    pub existing_size: Option<u64>,
+    /// Milliseconds since the Unix epoch.
+    pub existing_modified_ms: Option<u64>,
+}
+
+impl ExportPlanEntry {
+    /// Whether `policy` lets this file be written. Identical files are never
+    /// rewritten, so re-exporting unchanged assets doesn't churn backups.
+    pub fn should_write(&self, policy: ExportPolicy) -> bool {
+        match self.action {
+            ExportAction::Create => true,
+            ExportAction::OverwriteIdentical => false,
+            ExportAction::OverwriteDifferent => policy == ExportPolicy::Overwrite,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedFile {
+    pub name: String,
+    pub path: String,
+    pub action: ExportAction,
+    /// False when the policy (or identical contents) left the file alone.
+    pub written: bool,
+    pub size: u64,
+    /// Lowercase hex SHA-256 of the exported bytes.
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportReport {
+    pub directory: String,
+    pub files: Vec<ExportedFile>,
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+pub fn build_report(dir: &Path, plan: Vec<ExportPlanEntry>, data: &[&[u8]], policy: ExportPolicy) -> ExportReport {
+    let files = plan
+        .into_iter()
+        .zip(data)
+        .map(|(entry, bytes)| ExportedFile {
+            written: entry.should_write(policy),
+            sha256: sha256_hex(bytes),
+            name: entry.name,
+            path: entry.path,
+            action: entry.action,
+            size: entry.size,
+        })
+        .collect();
+    ExportReport {
+        directory: dir.to_string_lossy().to_string(),
+        files,
+    }
+}
+
+/// Where `name` lands inside `dir`. Names are bare file names with an
+/// extension the write commands accept, or an OAM layout
+/// (`oam::LAYOUT_SUFFIX`).
+pub fn target_path(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("file name is empty".into());
+    }
+    if name.contains('\\') || name.contains('/') || name.contains(':') {
+        return Err("file name must not contain path separators".into());
+    }
+    let out = dir.join(name);
+    let is_layout = name.to_ascii_lowercase().ends_with(crate::oam::LAYOUT_SUFFIX);
+    if !is_layout && !crate::is_allowed_write_extension(out.extension().and_then(|e| e.to_str())) {
+        return Err(format!("file extension not allowed: {}", name));
+    }
+    Ok(out)
+}
+
+fn modified_ms(meta: &std::fs::Metadata) -> Option<u64> {
+    let modified = meta.modified().ok()?;
+    let since = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(since.as_millis() as u64)
+}
+
+fn plan_file(dir: &Path, name: &str, data: &[u8]) -> Result<ExportPlanEntry, String> {
+    let path = target_path(dir, name)?;
+    let size = data.len() as u64;
+    let (action, existing_size, existing_modified_ms) = match std::fs::metadata(&path) {
+        Ok(meta) if meta.is_file() => {
+            let identical = meta.len() == size
+                && std::fs::read(&path).map_err(|e| format!("read failed ({}): {}", name, e))? == data;
+            let action = if identical {
+                ExportAction::OverwriteIdentical
+            } else {
+                ExportAction::OverwriteDifferent
+            };
+            (action, Some(meta.len()), modified_ms(&meta))
+        }
+        Ok(_) => return Err(format!("target is not a file: {}", name)),
+        Err(_) => (ExportAction::Create, None, None),
+    };
+    Ok(ExportPlanEntry {
+        name: name.trim().to_string(),
+        path: path.to_string_lossy().to_string(),
+        action,
+        size,
+        existing_size,
+        existing_modified_ms,
+    })
+}
+
+/// Dry run: what writing `files` (name, bytes) into `dir` would do, in order.
+pub fn plan_export<'a, I>(dir: &Path, files: I) -> Result<Vec<ExportPlanEntry>, String>
+where
+    I: IntoIterator<Item = (&'a str, &'a [u8])>,
+{
+    if !dir.is_dir() {
+        return Err("target directory does not exist".into());
+    }
+    let mut plan: Vec<ExportPlanEntry> = Vec::new();
+    for (name, data) in files {
+        let entry = plan_file(dir, name, data)?;
+        if plan.iter().any(|e| e.name.eq_ignore_ascii_case(&entry.name)) {
+            return Err(format!("duplicate file name in export: {}", entry.name));
+        }
+        plan.push(entry);
+    }
+    Ok(plan)
+}
+
+/// Apply `policy` to a plan before anything is written.
+pub fn enforce_policy(plan: &[ExportPlanEntry], policy: ExportPolicy) -> Result<(), String> {
+    if policy != ExportPolicy::FailOnDifference {
+        return Ok(());
+    }
+    let changed: Vec<&str> = plan
+        .iter()
+        .filter(|e| e.action == ExportAction::OverwriteDifferent)
+        .map(|e| e.name.as_str())
+        .collect();
+    if changed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("export would overwrite changed files: {}", changed.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_classifies_existing_files() {
+        let dir = std::env::temp_dir().join(format!("cdpaint-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("front.png"), b"same").unwrap();
+        std::fs::write(dir.join("normal.pal"), b"old").unwrap();
+
+        let files: [(&str, &[u8]); 3] = [("front.png", b"same"), ("normal.pal", b"new"), ("back.png", b"b")];
+        let plan = plan_export(&dir, files).unwrap();
+        let actions: Vec<ExportAction> = plan.iter().map(|e| e.action).collect();
+        assert_eq!(
+            actions,
+            [ExportAction::OverwriteIdentical, ExportAction::OverwriteDifferent, ExportAction::Create]
+        );
+        assert_eq!(plan[1].existing_size, Some(3));
+        assert!(plan[1].existing_modified_ms.is_some());
+
+        assert!(enforce_policy(&plan, ExportPolicy::FailOnDifference).is_err());
+        let written = |policy| plan.iter().filter(|e| e.should_write(policy)).count();
+        assert_eq!(written(ExportPolicy::Overwrite), 2);
+        assert_eq!(written(ExportPolicy::SkipExisting), 1);
+
+        let dupes: [(&str, &[u8]); 2] = [("a.png", b""), ("A.PNG", b"")];
+        assert!(plan_export(&dir, dupes).is_err());
+        assert_eq!(ExportPolicy::parse(Some("skip-existing")).unwrap(), ExportPolicy::SkipExisting);
+        assert!(ExportPolicy::parse(Some("clobber")).is_err());
+
+        let report = build_report(&dir, plan, &[b"same", b"new", b"b"], ExportPolicy::SkipExisting);
+        let written: Vec<bool> = report.files.iter().map(|f| f.written).collect();
+        assert_eq!(written, [false, false, true]);
+        assert_eq!(
+            report.files[2].sha256,
+            "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
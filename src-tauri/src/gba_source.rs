@@ -0,0 +1,223 @@
+//! Graphics as source: an indexed image or palette written out as C arrays
+//! (a `.c` / `.h` pair) or GAS data (`.s` / `.h`) for engines that build
+//! graphics in rather than INCBIN them.
+//!
+//! Symbols come from the image's file name, camel-cased: `front_pic.png`
+//! gives `frontPicPal`, `frontPicTiles` and `frontPicTilemap`. Colors are
+//! RGB555 halfwords, tiles the gbagfx layout as little-endian words.
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+use crate::gba_tiles;
+use crate::palette::{self, PaletteFormat};
+use crate::tilemap::{self, TilemapOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceFormat {
+    C,
+    Asm,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SourceOptions {
+    pub format: SourceFormat,
+    /// 0 x 0 writes the palette only.
+    #[serde(default)]
+    pub width: u32,
+    #[serde(default)]
+    pub height: u32,
+    /// 4 or 8.
+    pub bit_depth: u8,
+    pub palette: Vec<[u8; 3]>,
+    /// Deduplicate the tiles and add the text-BG tilemap.
+    #[serde(default)]
+    pub tilemap: bool,
+}
+
+/// C identifier from a file name: the part before the first dot, split on
+/// anything that isn't a letter or digit and camel-cased.
+pub fn symbol_name(file_name: &str) -> String {
+    let stem = file_name.split('.').next().unwrap_or("");
+    let mut out = String::new();
+    for word in stem.split(|c: char| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            out.push(if out.is_empty() { first } else { first.to_ascii_uppercase() });
+            out.extend(chars);
+        }
+    }
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+enum Values {
+    Halfwords(Vec<u16>),
+    Words(Vec<u32>),
+}
+
+impl Values {
+    fn len(&self) -> usize {
+        match self {
+            Values::Halfwords(v) => v.len(),
+            Values::Words(v) => v.len(),
+        }
+    }
+
+    fn c_type(&self) -> &'static str {
+        match self {
+            Values::Halfwords(_) => "u16",
+            Values::Words(_) => "u32",
+        }
+    }
+
+    fn hex(&self) -> Vec<String> {
+        match self {
+            Values::Halfwords(v) => v.iter().map(|x| format!("0x{:04X}", x)).collect(),
+            Values::Words(v) => v.iter().map(|x| format!("0x{:08X}", x)).collect(),
+        }
+    }
+}
+
+/// Source files for `file_name`'s image, named after its stem: the `.c` or
+/// `.s` first, then the `.h`.
+pub fn generate(file_name: &str, opts: &SourceOptions, indices: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let stem = file_name.split('.').next().filter(|s| !s.is_empty()).unwrap_or("graphics");
+    let symbol = symbol_name(file_name);
+    if !matches!(opts.bit_depth, 4 | 8) {
+        return Err(format!("GBA tiles are 4 or 8 bits per pixel, not {}", opts.bit_depth));
+    }
+    if opts.palette.len() > 1 << opts.bit_depth {
+        return Err(format!("a {}bpp palette holds at most {} colors", opts.bit_depth, 1 << opts.bit_depth));
+    }
+
+    let mut arrays = Vec::new();
+    if !opts.palette.is_empty() {
+        let colors: Vec<[u8; 4]> = opts.palette.iter().map(|c| [c[0], c[1], c[2], 255]).collect();
+        let bytes = palette::write(PaletteFormat::GbaPal, &colors, None)?;
+        let pal = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        arrays.push((format!("{}Pal", symbol), Values::Halfwords(pal)));
+    }
+    if opts.width > 0 && opts.height > 0 {
+        let (tiles, map) = if opts.tilemap {
+            let tilemap_opts = TilemapOptions {
+                width: opts.width,
+                height: opts.height,
+                bit_depth: opts.bit_depth,
+                palette_bank: 0,
+                allow_flips: true,
+            };
+            let out = tilemap::generate(&tilemap_opts, indices)?;
+            if out.info.over_limit {
+                return Err(format!("{} unique tiles is over the {}-tile limit", out.info.unique_tiles, out.info.tile_limit));
+            }
+            (out.tileset, Some(out.map))
+        } else {
+            let tiles = gba_tiles::encode_tiles(opts.width as usize, opts.height as usize, opts.bit_depth, indices)?;
+            (tiles, None)
+        };
+        let words = tiles.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect();
+        arrays.push((format!("{}Tiles", symbol), Values::Words(words)));
+        if let Some(map) = map {
+            let entries = map.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            arrays.push((format!("{}Tilemap", symbol), Values::Halfwords(entries)));
+        }
+    }
+    if arrays.is_empty() {
+        return Err("nothing to export: no palette and no image".into());
+    }
+
+    let header_name = format!("{}.h", stem);
+    let body = match opts.format {
+        SourceFormat::C => (format!("{}.c", stem), c_source(file_name, &header_name, &arrays)),
+        SourceFormat::Asm => (format!("{}.s", stem), asm_source(file_name, &arrays)),
+    };
+    let header = (header_name, c_header(file_name, stem, &arrays));
+    Ok(vec![(body.0, body.1.into_bytes()), (header.0, header.1.into_bytes())])
+}
+
+fn c_source(file_name: &str, header_name: &str, arrays: &[(String, Values)]) -> String {
+    let mut out = format!("// Generated by CDPaint from {}\n\n#include \"{}\"\n", file_name, header_name);
+    for (name, values) in arrays {
+        let _ = write!(out, "\nconst {} {}[{}] = {{\n", values.c_type(), name, values.len());
+        for line in values.hex().chunks(8) {
+            let _ = writeln!(out, "    {},", line.join(", "));
+        }
+        out.push_str("};\n");
+    }
+    out
+}
+
+fn asm_source(file_name: &str, arrays: &[(String, Values)]) -> String {
+    let mut out = format!("@ Generated by CDPaint from {}\n\n\t.section .rodata\n", file_name);
+    for (name, values) in arrays {
+        let directive = match values {
+            Values::Halfwords(_) => ".hword",
+            Values::Words(_) => ".word",
+        };
+        let _ = write!(out, "\n\t.align 2\n\t.global {}\n{}:\n", name, name);
+        for line in values.hex().chunks(8) {
+            let _ = writeln!(out, "\t{} {}", directive, line.join(", "));
+        }
+        let _ = writeln!(out, "\t.size {}, .-{}", name, name);
+    }
+    out
+}
+
+fn c_header(file_name: &str, stem: &str, arrays: &[(String, Values)]) -> String {
+    let guard: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    let mut out = format!(
+        "// Generated by CDPaint from {}\n\n#ifndef GUARD_{}_H\n#define GUARD_{}_H\n\n#include \"gba/types.h\"\n\n",
+        file_name, guard, guard
+    );
+    for (name, values) in arrays {
+        let _ = writeln!(out, "extern const {} {}[{}];", values.c_type(), name, values.len());
+    }
+    let _ = write!(out, "\n#endif // GUARD_{}_H\n", guard);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_c_and_asm_arrays() {
+        assert_eq!(symbol_name("front_pic.png"), "frontPic");
+        assert_eq!(symbol_name("2 Bulbasaur-back.4bpp.png"), "_2BulbasaurBack");
+
+        let mut opts = SourceOptions {
+            format: SourceFormat::C,
+            width: 8,
+            height: 8,
+            bit_depth: 4,
+            palette: vec![[0, 0, 0], [255, 255, 255]],
+            tilemap: false,
+        };
+        let files = generate("front_pic.png", &opts, &[1; 64]).unwrap();
+        assert_eq!(files[0].0, "front_pic.c");
+        let c = String::from_utf8(files[0].1.clone()).unwrap();
+        assert!(c.contains("const u16 frontPicPal[2] = {\n    0x0000, 0x7FFF,\n};"));
+        assert!(c.contains("const u32 frontPicTiles[8] = {\n    0x11111111, 0x11111111,"));
+        let h = String::from_utf8(files[1].1.clone()).unwrap();
+        assert!(h.contains("#ifndef GUARD_FRONT_PIC_H"));
+        assert!(h.contains("extern const u32 frontPicTiles[8];"));
+
+        opts.format = SourceFormat::Asm;
+        opts.tilemap = true;
+        let files = generate("front_pic.png", &opts, &[1; 64]).unwrap();
+        assert_eq!(files[0].0, "front_pic.s");
+        let s = String::from_utf8(files[0].1.clone()).unwrap();
+        assert!(s.contains("frontPicTilemap:\n\t.hword 0x0000\n\t.size frontPicTilemap, .-frontPicTilemap"));
+
+        // A palette on its own.
+        let pal_only = SourceOptions { width: 0, height: 0, ..opts };
+        let files = generate("cursor.png", &pal_only, &[]).unwrap();
+        assert!(!String::from_utf8(files[1].1.clone()).unwrap().contains("Tiles"));
+    }
+}
@@ -0,0 +1,212 @@
+//! GBA tile data, byte for byte as gbagfx writes it.
+//!
+//! The build turns indexed PNGs into `.4bpp` / `.8bpp` tiles and a
+//! `.gbapal` with gbagfx; this produces the same bytes from the editor's
+//! indices so the conversion can be checked (or done) without the tool.
+//! Tiles are 8x8, taken row-major across the image. In 4bpp each byte holds
+//! two pixels, the left one in the low nibble.
+use serde::{Deserialize, Serialize};
+
+use crate::lz77;
+use crate::palette::{self, PaletteFormat};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GbaTilesOptions {
+    pub width: u32,
+    pub height: u32,
+    /// 4 or 8.
+    pub bit_depth: u8,
+    /// Written as the `.gbapal`, one entry per color.
+    pub palette: Vec<[u8; 3]>,
+    /// LZ77-compress the tiles, for a `.4bpp.lz` / `.8bpp.lz`.
+    #[serde(default)]
+    pub compress: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GbaTilesInfo {
+    pub tile_count: usize,
+    pub tiles_len: usize,
+    pub palette_len: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct GbaTiles {
+    pub info: GbaTilesInfo,
+    pub tiles: Vec<u8>,
+    pub palette: Vec<u8>,
+}
+
+/// Tile data for `width` x `height` indices, one byte each, row-major.
+pub fn encode_tiles(width: usize, height: usize, bit_depth: u8, indices: &[u8]) -> Result<Vec<u8>, String> {
+    if !matches!(bit_depth, 4 | 8) {
+        return Err(format!("GBA tiles are 4 or 8 bits per pixel, not {}", bit_depth));
+    }
+    if !width.is_multiple_of(8) || !height.is_multiple_of(8) {
+        return Err(format!("image size {}x{} is not a multiple of 8", width, height));
+    }
+    if indices.len() != width * height {
+        return Err("index buffer does not match the image size".into());
+    }
+    if bit_depth == 4 {
+        if let Some(bad) = indices.iter().find(|&&i| i > 15) {
+            return Err(format!("index {} does not fit in 4bpp", bad));
+        }
+    }
+    let mut out = Vec::with_capacity(indices.len() * bit_depth as usize / 8);
+    for tile_y in (0..height).step_by(8) {
+        for tile_x in (0..width).step_by(8) {
+            for y in tile_y..tile_y + 8 {
+                let row = &indices[y * width + tile_x..y * width + tile_x + 8];
+                if bit_depth == 8 {
+                    out.extend_from_slice(row);
+                } else {
+                    out.extend(row.chunks_exact(2).map(|pair| pair[0] | pair[1] << 4));
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Tiles and `.gbapal` for one indexed image.
+pub fn encode(opts: &GbaTilesOptions, indices: &[u8]) -> Result<GbaTiles, String> {
+    let mut tiles = encode_tiles(opts.width as usize, opts.height as usize, opts.bit_depth, indices)?;
+    let tile_count = tiles.len() / (8 * opts.bit_depth as usize);
+    if opts.compress && !tiles.is_empty() {
+        tiles = lz77::compress(&tiles)?;
+    }
+    if opts.palette.len() > 1 << opts.bit_depth {
+        return Err(format!("a {}bpp palette holds at most {} colors", opts.bit_depth, 1 << opts.bit_depth));
+    }
+    let colors: Vec<[u8; 4]> = opts.palette.iter().map(|c| [c[0], c[1], c[2], 255]).collect();
+    let palette = palette::write(PaletteFormat::GbaPal, &colors, None)?;
+    Ok(GbaTiles {
+        info: GbaTilesInfo {
+            tile_count,
+            tiles_len: tiles.len(),
+            palette_len: palette.len(),
+        },
+        tiles,
+        palette,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TileImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    /// The file was LZ77-compressed.
+    pub compressed: bool,
+    pub palette: Vec<[u8; 3]>,
+    /// Where the palette came from; `None` when none was found and a gray
+    /// ramp stands in.
+    pub palette_path: Option<String>,
+}
+
+/// Indices for tile data laid out `width` pixels wide, as gbagfx converts
+/// tiles back to a PNG: a last partial row of tiles is filled with index 0.
+/// Returns the height and the indices, row-major.
+pub fn decode_tiles(tiles: &[u8], width: usize, bit_depth: u8) -> Result<(usize, Vec<u8>), String> {
+    if !matches!(bit_depth, 4 | 8) {
+        return Err(format!("GBA tiles are 4 or 8 bits per pixel, not {}", bit_depth));
+    }
+    if width == 0 || !width.is_multiple_of(8) {
+        return Err(format!("width {} is not a positive multiple of 8", width));
+    }
+    let tile_bytes = 8 * bit_depth as usize;
+    if tiles.is_empty() || !tiles.len().is_multiple_of(tile_bytes) {
+        return Err(format!("tile data is not a whole number of {}-byte tiles", tile_bytes));
+    }
+    let tiles_wide = width / 8;
+    let tile_count = tiles.len() / tile_bytes;
+    if tiles_wide > tile_count {
+        return Err(format!("width {} is wider than the {} tiles laid side by side", width, tile_count));
+    }
+    let height = tile_count.div_ceil(tiles_wide) * 8;
+    let mut indices = vec![0u8; width * height];
+    for (n, tile) in tiles.chunks_exact(tile_bytes).enumerate() {
+        let (tx, ty) = (n % tiles_wide * 8, n / tiles_wide * 8);
+        for (y, row) in tile.chunks_exact(bit_depth as usize).enumerate() {
+            let at = (ty + y) * width + tx;
+            if bit_depth == 8 {
+                indices[at..at + 8].copy_from_slice(row);
+            } else {
+                for (x, byte) in row.iter().enumerate() {
+                    indices[at + x * 2] = byte & 0xF;
+                    indices[at + x * 2 + 1] = byte >> 4;
+                }
+            }
+        }
+    }
+    Ok((height, indices))
+}
+
+/// Stand-in palette for tiles opened without one.
+pub fn gray_ramp(bit_depth: u8) -> Vec<[u8; 3]> {
+    let count = 1usize << bit_depth;
+    (0..count).map(|i| [(i * 255 / (count - 1)) as u8; 3]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_match_gbagfx_layout() {
+        // 16x8: tile 0 counts 0..7 along each row, tile 1 is all 7s.
+        let mut indices = Vec::new();
+        for _ in 0..8 {
+            indices.extend(0..8u8);
+            indices.extend([7; 8]);
+        }
+        let tiles = encode_tiles(16, 8, 4, &indices).unwrap();
+        assert_eq!(tiles.len(), 64);
+        assert_eq!(tiles[..4], [0x10, 0x32, 0x54, 0x76]);
+        assert_eq!(tiles[32..36], [0x77; 4]);
+        assert_eq!(encode_tiles(16, 8, 8, &indices).unwrap()[..9], [0, 1, 2, 3, 4, 5, 6, 7, 0]);
+
+        assert!(encode_tiles(12, 8, 4, &[0; 96]).is_err());
+        assert!(encode_tiles(8, 8, 4, &[16; 64]).is_err());
+
+        let opts = GbaTilesOptions {
+            width: 16,
+            height: 8,
+            bit_depth: 4,
+            palette: vec![[0, 0, 0], [255, 255, 255], [248, 0, 8]],
+            compress: false,
+        };
+        let out = encode(&opts, &indices).unwrap();
+        assert_eq!(out.info.tile_count, 2);
+        assert_eq!(out.palette, [0x00, 0x00, 0xFF, 0x7F, 0x1F, 0x04]);
+    }
+
+    #[test]
+    fn decoded_tiles_round_trip_through_lz() {
+        let indices: Vec<u8> = (0..16 * 16).map(|i| (i % 13) as u8).collect();
+        let opts = GbaTilesOptions {
+            width: 16,
+            height: 16,
+            bit_depth: 4,
+            palette: vec![[0, 0, 0]],
+            compress: true,
+        };
+        let out = encode(&opts, &indices).unwrap();
+        assert!(lz77::is_compressed(&out.tiles));
+        let raw = lz77::decompress(&out.tiles).unwrap();
+        assert_eq!(decode_tiles(&raw, 16, 4).unwrap(), (16, indices.clone()));
+        // Laid out one tile wide instead, the four tiles stack vertically.
+        let (height, tall) = decode_tiles(&raw, 8, 4).unwrap();
+        assert_eq!(height, 32);
+        assert_eq!(tall[8 * 8..8 * 8 + 8], indices[8..16]);
+        // Three tiles at two per row leave a blank last tile.
+        let (height, padded) = decode_tiles(&raw[..96], 16, 4).unwrap();
+        assert_eq!(height, 16);
+        assert!(padded[16 * 8 + 8..].chunks(16).all(|row| row[..8] == [0; 8]));
+        // No wider than all four tiles in one row.
+        assert_eq!(decode_tiles(&raw, 32, 4).unwrap().0, 8);
+        assert!(decode_tiles(&raw, 40, 4).is_err());
+        assert!(decode_tiles(&raw, usize::MAX & !7, 4).is_err());
+    }
+}
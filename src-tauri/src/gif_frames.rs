@@ -0,0 +1,308 @@
+//! Animated GIFs as frame lists.
+//!
+//! The webview only ever shows the first frame of a GIF, and canvas-based
+//! exports requantize colors. Decoding here yields every frame composited
+//! the way a viewer would show it (disposal methods applied), with its delay
+//! and the global/local palettes; encoding writes a multi-frame indexed GIF
+//! whose frames all share one exact palette.
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::num::NonZeroU64;
+
+/// Per-frame decoder buffer limit.
+const MAX_FRAME_BYTES: u64 = 1 << 26;
+/// Limit on all composited RGBA frames together.
+const MAX_DECODED_BYTES: usize = 1 << 29;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Disposal {
+    None,
+    Keep,
+    Background,
+    Previous,
+}
+
+impl From<gif::DisposalMethod> for Disposal {
+    fn from(d: gif::DisposalMethod) -> Self {
+        match d {
+            gif::DisposalMethod::Any => Self::None,
+            gif::DisposalMethod::Keep => Self::Keep,
+            gif::DisposalMethod::Background => Self::Background,
+            gif::DisposalMethod::Previous => Self::Previous,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GifFrameInfo {
+    pub delay_ms: u32,
+    /// Where the frame's own rectangle sits on the canvas.
+    pub left: u16,
+    pub top: u16,
+    pub width: u16,
+    pub height: u16,
+    pub disposal: Disposal,
+    pub transparent_index: Option<u8>,
+    /// Local color table, when the frame has one.
+    pub palette: Option<Vec<[u8; 3]>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GifInfo {
+    pub width: u16,
+    pub height: u16,
+    /// Extra plays after the first; `None` loops forever.
+    pub loop_count: Option<u16>,
+    pub global_palette: Option<Vec<[u8; 3]>>,
+    pub frames: Vec<GifFrameInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DecodedGif {
+    pub info: GifInfo,
+    /// One full-canvas RGBA image per frame, as displayed.
+    pub frames: Vec<Vec<u8>>,
+}
+
+fn to_palette(raw: &[u8]) -> Vec<[u8; 3]> {
+    raw.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect()
+}
+
+/// Decode every frame of a GIF, compositing each onto the canvas left by
+/// the frames before it. The background starts (and is restored to)
+/// transparent, as browsers do.
+pub fn decode(bytes: &[u8]) -> Result<DecodedGif, String> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    if let Some(limit) = NonZeroU64::new(MAX_FRAME_BYTES) {
+        options.set_memory_limit(gif::MemoryLimit::Bytes(limit));
+    }
+    let mut decoder = options.read_info(bytes).map_err(|e| format!("GIF decode failed: {}", e))?;
+    let (width, height) = (decoder.width(), decoder.height());
+    let (w, h) = (width as usize, height as usize);
+    if w == 0 || h == 0 {
+        return Err("GIF has zero width or height".into());
+    }
+    let global = decoder.global_palette().map(to_palette);
+
+    let mut canvas = vec![0u8; w * h * 4];
+    let mut frames = Vec::new();
+    let mut infos = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| format!("GIF decode failed: {}", e))? {
+        if (frames.len() + 1) * canvas.len() > MAX_DECODED_BYTES {
+            return Err("GIF has too many frames to decode".into());
+        }
+        let local = frame.palette.as_deref().map(to_palette);
+        let palette = local
+            .as_ref()
+            .or(global.as_ref())
+            .ok_or_else(|| "GIF frame has no color table".to_string())?;
+        let disposal = Disposal::from(frame.dispose);
+        let saved = (disposal == Disposal::Previous).then(|| canvas.clone());
+
+        let (fx, fy, fw, fh) = (frame.left as usize, frame.top as usize, frame.width as usize, frame.height as usize);
+        for (row, line) in frame.buffer.chunks_exact(fw.max(1)).take(fh).enumerate() {
+            let y = fy + row;
+            if y >= h {
+                break;
+            }
+            for (col, &index) in line.iter().enumerate() {
+                let x = fx + col;
+                if x >= w || Some(index) == frame.transparent {
+                    continue;
+                }
+                let color = palette.get(index as usize).copied().unwrap_or([0, 0, 0]);
+                let at = (y * w + x) * 4;
+                canvas[at..at + 4].copy_from_slice(&[color[0], color[1], color[2], 255]);
+            }
+        }
+        frames.push(canvas.clone());
+        infos.push(GifFrameInfo {
+            delay_ms: frame.delay as u32 * 10,
+            left: frame.left,
+            top: frame.top,
+            width: frame.width,
+            height: frame.height,
+            disposal,
+            transparent_index: frame.transparent,
+            palette: local,
+        });
+
+        match (disposal, saved) {
+            (Disposal::Previous, Some(saved)) => canvas = saved,
+            (Disposal::Background, _) => {
+                for y in fy..(fy + fh).min(h) {
+                    let start = (y * w + fx.min(w)) * 4;
+                    let end = (y * w + (fx + fw).min(w)) * 4;
+                    canvas[start..end].fill(0);
+                }
+            }
+            _ => {}
+        }
+    }
+    if frames.is_empty() {
+        return Err("GIF has no frames".into());
+    }
+    let loop_count = match decoder.repeat() {
+        gif::Repeat::Infinite => None,
+        gif::Repeat::Finite(n) => Some(n),
+    };
+    Ok(DecodedGif {
+        info: GifInfo {
+            width,
+            height,
+            loop_count,
+            global_palette: global,
+            frames: infos,
+        },
+        frames,
+    })
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GifEncodeFrame {
+    #[serde(default)]
+    pub delay_ms: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GifEncodeOptions {
+    pub width: u16,
+    pub height: u16,
+    /// Shared by every frame; written as the global color table.
+    pub palette: Vec<[u8; 3]>,
+    #[serde(default)]
+    pub transparent_index: Option<u8>,
+    /// Extra plays after the first; `None` loops forever.
+    #[serde(default)]
+    pub loop_count: Option<u16>,
+    pub frames: Vec<GifEncodeFrame>,
+}
+
+/// Encode full-canvas frames of one index per pixel (`indices` holds them
+/// back to back). GIF color tables hold a power of two entries, so the
+/// palette is padded with black up to the next one.
+pub fn encode(opts: &GifEncodeOptions, indices: &[u8]) -> Result<Vec<u8>, String> {
+    let (w, h) = (opts.width as usize, opts.height as usize);
+    if w == 0 || h == 0 {
+        return Err("image has zero width or height".into());
+    }
+    if opts.frames.is_empty() {
+        return Err("GIF needs at least one frame".into());
+    }
+    if indices.len() != w * h * opts.frames.len() {
+        return Err("index buffer does not match the frame size and count".into());
+    }
+    if opts.palette.is_empty() || opts.palette.len() > 256 {
+        return Err("palette must have 1 to 256 entries".into());
+    }
+    if let Some(bad) = indices.iter().find(|&&i| i as usize >= opts.palette.len()) {
+        return Err(format!("index {} is outside the {}-entry palette", bad, opts.palette.len()));
+    }
+    if opts.transparent_index.is_some_and(|t| t as usize >= opts.palette.len()) {
+        return Err("transparent index is outside the palette".into());
+    }
+
+    let palette: Vec<u8> = opts.palette.iter().flatten().copied().collect();
+    let mut out = Vec::new();
+    {
+        let fail = |e: gif::EncodingError| format!("GIF encode failed: {}", e);
+        let mut encoder = gif::Encoder::new(&mut out, opts.width, opts.height, &palette).map_err(fail)?;
+        let repeat = match opts.loop_count {
+            None => gif::Repeat::Infinite,
+            Some(n) => gif::Repeat::Finite(n),
+        };
+        encoder.set_repeat(repeat).map_err(fail)?;
+        // Full-canvas frames with holes must clear what the previous frame
+        // left, or it would show through the transparent pixels.
+        let dispose = if opts.transparent_index.is_some() {
+            gif::DisposalMethod::Background
+        } else {
+            gif::DisposalMethod::Keep
+        };
+        for (meta, frame_indices) in opts.frames.iter().zip(indices.chunks_exact(w * h)) {
+            let frame = gif::Frame {
+                delay: (meta.delay_ms.div_ceil(10)).min(u16::MAX as u32) as u16,
+                dispose,
+                transparent: opts.transparent_index,
+                width: opts.width,
+                height: opts.height,
+                buffer: Cow::Borrowed(frame_indices),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame).map_err(fail)?;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip_with_shared_palette() {
+        let opts = GifEncodeOptions {
+            width: 2,
+            height: 2,
+            palette: vec![[0, 0, 0], [255, 0, 0], [0, 0, 255]],
+            transparent_index: Some(0),
+            loop_count: Some(2),
+            frames: vec![GifEncodeFrame { delay_ms: 100 }, GifEncodeFrame { delay_ms: 55 }],
+        };
+        let gif = encode(&opts, &[1, 1, 2, 0, 0, 2, 2, 1]).unwrap();
+        let decoded = decode(&gif).unwrap();
+        assert_eq!(decoded.info.loop_count, Some(2));
+        // Padded up to a power of two.
+        assert_eq!(decoded.info.global_palette.as_ref().unwrap()[..3], opts.palette[..]);
+        let delays: Vec<u32> = decoded.info.frames.iter().map(|f| f.delay_ms).collect();
+        assert_eq!(delays, [100, 60]);
+        // Background disposal clears frame 1 before frame 2 is drawn.
+        assert_eq!(decoded.frames[1][..4], [0, 0, 0, 0]);
+        assert_eq!(decoded.frames[1][12..16], [255, 0, 0, 255]);
+        assert_eq!(decoded.frames[0][12..16], [0, 0, 0, 0]);
+        assert!(encode(&opts, &[3; 8]).is_err());
+    }
+
+    #[test]
+    fn partial_frames_composite_with_disposal() {
+        // 2x1 canvas: frame 1 paints both pixels and is kept, frame 2 covers
+        // only the right pixel and is restored to previous.
+        let mut out = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut out, 2, 1, &[0, 0, 0, 255, 255, 255]).unwrap();
+            let first = gif::Frame {
+                width: 2,
+                height: 1,
+                dispose: gif::DisposalMethod::Keep,
+                buffer: Cow::Borrowed(&[1, 1]),
+                ..gif::Frame::default()
+            };
+            let second = gif::Frame {
+                left: 1,
+                width: 1,
+                height: 1,
+                dispose: gif::DisposalMethod::Previous,
+                buffer: Cow::Borrowed(&[0]),
+                ..gif::Frame::default()
+            };
+            let third = gif::Frame {
+                width: 1,
+                height: 1,
+                transparent: Some(1),
+                buffer: Cow::Borrowed(&[1]),
+                ..gif::Frame::default()
+            };
+            for frame in [first, second, third] {
+                encoder.write_frame(&frame).unwrap();
+            }
+        }
+        let decoded = decode(&out).unwrap();
+        assert_eq!(decoded.info.loop_count, Some(0));
+        assert_eq!(decoded.frames[1], [255, 255, 255, 255, 0, 0, 0, 255]);
+        // Frame 2 was undone, and frame 3's only pixel is transparent.
+        assert_eq!(decoded.frames[2], [255, 255, 255, 255, 255, 255, 255, 255]);
+        assert_eq!(decoded.info.frames[1].disposal, Disposal::Previous);
+    }
+}
@@ -0,0 +1,337 @@
+//! Where on disk the frontend may read and write.
+//!
+//! File commands take paths from the webview, so checking the extension
+//! alone would let a compromised page read or overwrite images anywhere.
+//! Instead every path must fall inside a grant, and grants are only ever
+//! created on the Rust side:
+//! - files and folders the user picks in a native dialog (`show_file_dialog`,
+//!   `pick_export_folder` and the export dialog commands);
+//! - images passed on the command line (`collect_image_paths`);
+//! - the hooked project root (`hook_project_folder`).
+//!
+//! Grants are persisted in `file-grants.json` next to the window state, so
+//! recent files and the hooked project keep working after a restart, and can
+//! be dropped again with `revoke_file_grant`.
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Manager;
+use tauri_plugin_dialog::DialogExt;
+
+/// Oldest grants are forgotten past this many.
+const MAX_GRANTS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrantScope {
+    /// Exactly this file.
+    File,
+    /// This folder and everything below it.
+    Directory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrantSource {
+    Dialog,
+    Launch,
+    Project,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileGrant {
+    pub path: String,
+    pub scope: GrantScope,
+    pub source: GrantSource,
+    pub granted_ms: u64,
+}
+
+#[derive(Default)]
+pub struct FileGrants(Mutex<Vec<FileGrant>>);
+
+/// Resolve symlinks and platform prefixes where possible, so a path compares
+/// equal however the frontend or the OS spells it. Paths that don't exist
+/// yet (a file about to be saved, a file just removed) resolve through their
+/// parent.
+pub fn canonical_path(path: &Path) -> PathBuf {
+    if let Ok(p) = path.canonicalize() {
+        return PathBuf::from(crate::normalize_device_path(&p.to_string_lossy()));
+    }
+    match (path.parent().and_then(|p| p.canonicalize().ok()), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            PathBuf::from(crate::normalize_device_path(&parent.to_string_lossy())).join(name)
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Windows paths compare case-insensitively.
+fn fold_case(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(path.to_string_lossy().to_lowercase())
+    } else {
+        path.to_path_buf()
+    }
+}
+
+fn covers(grant: &FileGrant, path: &Path) -> bool {
+    let root = fold_case(Path::new(&grant.path));
+    let path = fold_case(path);
+    match grant.scope {
+        GrantScope::File => path == root,
+        GrantScope::Directory => path.starts_with(&root),
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Add a grant. Grants inside a granted folder are kept on their own, so
+/// revoking the folder (unhooking a project) leaves files the user opened
+/// there individually. Returns false if the same grant was already there.
+fn insert_grant(grants: &mut Vec<FileGrant>, path: &Path, scope: GrantScope, source: GrantSource) -> bool {
+    let folded = fold_case(path);
+    if grants.iter().any(|g| g.scope == scope && fold_case(Path::new(&g.path)) == folded) {
+        return false;
+    }
+    grants.push(FileGrant {
+        path: path.to_string_lossy().to_string(),
+        scope,
+        source,
+        granted_ms: now_ms(),
+    });
+    // Past the limit the oldest file grants go first, then folders.
+    while grants.len() > MAX_GRANTS {
+        let oldest = grants.iter().position(|g| g.scope == GrantScope::File).unwrap_or(0);
+        grants.remove(oldest);
+    }
+    true
+}
+
+impl FileGrants {
+    pub fn check(&self, path: &Path) -> Result<(), String> {
+        let p = canonical_path(path);
+        let allowed = self
+            .0
+            .lock()
+            .map(|g| g.iter().any(|grant| covers(grant, &p)))
+            .unwrap_or(false);
+        if allowed {
+            Ok(())
+        } else {
+            Err(format!("access denied: {} was not opened in CDPaint", path.display()))
+        }
+    }
+}
+
+fn grants_path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    let dir = app.path().app_config_dir().ok()?;
+    Some(dir.join("file-grants.json"))
+}
+
+/// Load persisted grants. Called once from `setup()`.
+pub fn load(app: &tauri::AppHandle) {
+    let Some(path) = grants_path(app) else { return };
+    let Ok(raw) = std::fs::read(path) else { return };
+    let Ok(saved) = serde_json::from_slice::<Vec<FileGrant>>(&raw) else { return };
+    if let Ok(mut guard) = app.state::<FileGrants>().0.lock() {
+        *guard = saved;
+    }
+}
+
+fn persist(app: &tauri::AppHandle, grants: &[FileGrant]) -> Result<(), String> {
+    let path = grants_path(app).ok_or_else(|| "no config path".to_string())?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("create config dir failed: {}", e))?;
+    }
+    let bytes = serde_json::to_vec_pretty(grants).map_err(|e| format!("serialize file grants failed: {}", e))?;
+    crate::atomic_save::write_atomic(&path, &bytes)
+}
+
+pub fn grant(app: &tauri::AppHandle, path: &Path, scope: GrantScope, source: GrantSource) {
+    let p = canonical_path(path);
+    let state = app.state::<FileGrants>();
+    let Ok(mut guard) = state.0.lock() else { return };
+    if insert_grant(&mut guard, &p, scope, source) {
+        // The grant still holds for this session if it can't be saved.
+        let _ = persist(app, &guard);
+    }
+}
+
+#[tauri::command]
+pub fn list_file_grants(grants: tauri::State<'_, FileGrants>) -> Vec<FileGrant> {
+    grants.0.lock().map(|g| g.clone()).unwrap_or_default()
+}
+
+/// Drop the grant recorded for exactly `path`. Returns whether one existed.
+#[tauri::command]
+pub fn revoke_file_grant(app: tauri::AppHandle, grants: tauri::State<'_, FileGrants>, path: String) -> Result<bool, String> {
+    let p = fold_case(&canonical_path(&crate::normalize_to_absolute_path(&path)?));
+    let mut guard = grants.0.lock().map_err(|_| "file grants poisoned".to_string())?;
+    let before = guard.len();
+    guard.retain(|g| fold_case(Path::new(&g.path)) != p);
+    if guard.len() == before {
+        return Ok(false);
+    }
+    persist(&app, &guard)?;
+    Ok(true)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DialogFilter {
+    name: String,
+    extensions: Vec<String>,
+}
+
+/// The subset of the dialog plugin's `open` / `save` options the frontend uses.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FileDialogOptions {
+    /// `"open"` (default) or `"save"`.
+    mode: Option<String>,
+    title: Option<String>,
+    directory: bool,
+    multiple: bool,
+    default_path: Option<String>,
+    filters: Vec<DialogFilter>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum DialogSelection {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Native open/save dialog that grants access to whatever the user picks.
+/// Returns a path (or a list with `multiple`), or null when cancelled.
+#[tauri::command]
+pub async fn show_file_dialog(app: tauri::AppHandle, options: FileDialogOptions) -> Result<Option<DialogSelection>, String> {
+    let mut dialog = app.dialog().file();
+    if let Some(title) = options.title.as_deref() {
+        dialog = dialog.set_title(title);
+    }
+    for filter in &options.filters {
+        let exts: Vec<&str> = filter.extensions.iter().map(String::as_str).collect();
+        dialog = dialog.add_filter(&filter.name, &exts);
+    }
+    if let Some(default_path) = options.default_path.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        // A bare file name, or a full path whose folder becomes the start.
+        match crate::normalize_to_absolute_path(default_path) {
+            Ok(p) if p.is_dir() => dialog = dialog.set_directory(&p),
+            Ok(p) => {
+                if let Some(parent) = p.parent().filter(|d| d.is_dir()) {
+                    dialog = dialog.set_directory(parent);
+                }
+                if let Some(name) = p.file_name() {
+                    dialog = dialog.set_file_name(name.to_string_lossy());
+                }
+            }
+            Err(_) => dialog = dialog.set_file_name(default_path),
+        }
+    }
+
+    let saving = options.mode.as_deref() == Some("save");
+    let directory = options.directory;
+    let multiple = options.multiple;
+    let picked = tauri::async_runtime::spawn_blocking(move || {
+        let one = |p: Option<tauri_plugin_dialog::FilePath>| p.map(|p| vec![p]);
+        match (saving, directory, multiple) {
+            (true, _, _) => one(dialog.blocking_save_file()),
+            (false, true, true) => dialog.blocking_pick_folders(),
+            (false, true, false) => one(dialog.blocking_pick_folder()),
+            (false, false, true) => dialog.blocking_pick_files(),
+            (false, false, false) => one(dialog.blocking_pick_file()),
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+
+    let scope = if directory && !saving {
+        GrantScope::Directory
+    } else {
+        GrantScope::File
+    };
+    let mut paths = Vec::with_capacity(picked.len());
+    for file_path in picked {
+        let p = crate::resolve_dialog_file_path(file_path)?;
+        grant(&app, &p, scope, GrantSource::Dialog);
+        paths.push(crate::normalize_device_path(&p.to_string_lossy()));
+    }
+    Ok(Some(if multiple && !saving {
+        DialogSelection::Many(paths)
+    } else {
+        DialogSelection::One(paths.remove(0))
+    }))
+}
+
+/// Folder picker for the project browser; the picked root becomes a
+/// project grant.
+#[tauri::command]
+pub async fn hook_project_folder(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    let dialog = app.dialog().file().set_title("Hook Project Folder");
+    let folder = tauri::async_runtime::spawn_blocking(move || dialog.blocking_pick_folder())
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(folder) = folder else {
+        return Ok(None);
+    };
+    let path = crate::resolve_dialog_file_path(folder)?;
+    grant(&app, &path, GrantScope::Directory, GrantSource::Project);
+    Ok(Some(crate::normalize_device_path(&path.to_string_lossy())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directory_grants_cover_descendants_only() {
+        let mut grants = Vec::new();
+        let root = std::env::temp_dir().join("cdpaint-proj");
+        assert!(insert_grant(&mut grants, &root.join("a.png"), GrantScope::File, GrantSource::Launch));
+        assert!(insert_grant(&mut grants, &root, GrantScope::Directory, GrantSource::Project));
+        assert!(!insert_grant(&mut grants, &root, GrantScope::Directory, GrantSource::Dialog));
+        assert_eq!(grants.len(), 2);
+
+        let folder = &grants[1];
+        assert!(covers(folder, &root.join("graphics/front.png")));
+        assert!(!covers(folder, &std::env::temp_dir().join("cdpaint-proj-other/x.png")));
+        assert!(!covers(folder, &std::env::temp_dir().join("x.png")));
+
+        // Unhooking the folder keeps the file opened on its own.
+        grants.retain(|g| g.scope != GrantScope::Directory);
+        assert!(grants.iter().any(|g| covers(g, &root.join("a.png"))));
+        assert!(!grants.iter().any(|g| covers(g, &root.join("b.png"))));
+    }
+
+    #[test]
+    fn folders_outlive_file_grants_past_the_limit() {
+        let mut grants = Vec::new();
+        let root = std::env::temp_dir().join("cdpaint-many");
+        insert_grant(&mut grants, &root, GrantScope::Directory, GrantSource::Project);
+        for i in 0..MAX_GRANTS {
+            insert_grant(&mut grants, &root.join(format!("{}.png", i)), GrantScope::File, GrantSource::Dialog);
+        }
+        assert_eq!(grants.len(), MAX_GRANTS);
+        assert_eq!(grants[0].scope, GrantScope::Directory);
+        assert_eq!(grants[1].path, root.join("1.png").to_string_lossy());
+    }
+
+    #[test]
+    fn file_grants_match_exactly() {
+        let mut grants = Vec::new();
+        let file = std::env::temp_dir().join("cdpaint-grant.png");
+        insert_grant(&mut grants, &file, GrantScope::File, GrantSource::Dialog);
+        assert!(covers(&grants[0], &file));
+        assert!(!covers(&grants[0], &std::env::temp_dir().join("cdpaint-grant.png.1.bak")));
+        assert!(!covers(&grants[0], &std::env::temp_dir()));
+    }
+}
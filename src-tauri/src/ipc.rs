@@ -0,0 +1,50 @@
+//! Helpers for commands that move file bytes as raw IPC bodies.
+//!
+//! A JSON `Vec<u8>` costs a number per byte in both directions, which is what
+//! made opening and saving large canvases slow. Byte-carrying commands instead
+//! take the bytes as the raw request body and their other arguments as
+//! headers, percent-encoded by the frontend (`encodeURIComponent`) so paths
+//! with non-ASCII characters survive, and answer with `tauri::ipc::Response`,
+//! which reaches JavaScript as an `ArrayBuffer`.
+use serde::Serialize;
+use tauri::ipc::{InvokeBody, Request, Response};
+
+pub fn raw_body<'a>(request: &'a Request<'_>) -> Result<&'a [u8], String> {
+    match request.body() {
+        InvokeBody::Raw(bytes) => Ok(bytes),
+        InvokeBody::Json(_) => Err("expected a binary request body".into()),
+    }
+}
+
+pub fn header(request: &Request<'_>, name: &str) -> Result<Option<String>, String> {
+    let Some(value) = request.headers().get(name) else {
+        return Ok(None);
+    };
+    let raw = value
+        .to_str()
+        .map_err(|_| format!("header {} is not percent-encoded", name))?;
+    percent_encoding::percent_decode_str(raw)
+        .decode_utf8()
+        .map(|s| Some(s.into_owned()))
+        .map_err(|_| format!("header {} is not valid UTF-8", name))
+}
+
+pub fn required_header(request: &Request<'_>, name: &str) -> Result<String, String> {
+    header(request, name)?.ok_or_else(|| format!("missing {} header", name))
+}
+
+/// Structured result plus byte blobs in one binary response:
+/// `[u32 LE json length][json][blob 0][blob 1]...`. The JSON refers to each
+/// blob by the offset/length the caller recorded relative to the blob area.
+pub fn framed_response<T: Serialize>(meta: &T, blobs: Vec<Vec<u8>>) -> Result<Response, String> {
+    let json = serde_json::to_vec(meta).map_err(|e| format!("serialize response failed: {}", e))?;
+    let json_len = u32::try_from(json.len()).map_err(|_| "response metadata too large".to_string())?;
+    let total = 4 + json.len() + blobs.iter().map(Vec::len).sum::<usize>();
+    let mut out = Vec::with_capacity(total);
+    out.extend_from_slice(&json_len.to_le_bytes());
+    out.extend_from_slice(&json);
+    for blob in blobs {
+        out.extend_from_slice(&blob);
+    }
+    Ok(Response::new(out))
+}
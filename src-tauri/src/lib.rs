@@ -8,6 +8,33 @@ use tauri_plugin_updater::UpdaterExt;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
+mod aseprite;
+mod atomic_save;
+mod autosave;
+mod bmp;
+mod decomp_index;
+mod editor_meta;
+mod export;
+mod gba_source;
+mod gba_tiles;
+mod gif_frames;
+mod grants;
+mod ipc;
+mod lz77;
+mod oam;
+mod ora;
+mod palette;
+mod palette_check;
+mod palette_reorder;
+mod png;
+mod png_chunks;
+mod tilemap;
+mod watcher;
+mod zip_archive;
+
+use atomic_save::{BackupSettings, FileBackupEntry};
+use grants::{FileGrants, GrantScope, GrantSource};
+
 const MIN_WINDOW_WIDTH: u32 = 400;
 const MIN_WINDOW_HEIGHT: u32 = 400;
 
@@ -403,7 +430,7 @@ fn is_image_path(path: &str) -> bool {
 fn is_image_extension(ext: Option<&str>) -> bool {
     matches!(
         ext.unwrap_or("").to_ascii_lowercase().as_str(),
-        "png" | "jpg" | "jpeg" | "bmp" | "gif" | "webp" | "ora"
+        "png" | "jpg" | "jpeg" | "bmp" | "gif" | "webp" | "ora" | "ase" | "aseprite"
     )
 }
 
@@ -458,27 +485,112 @@ fn resolve_path_dots(path: &Path) -> PathBuf {
 }
 
 fn is_allowed_write_extension(ext: Option<&str>) -> bool {
+    let ext = ext.unwrap_or("");
     matches!(
-        ext.unwrap_or("").to_ascii_lowercase().as_str(),
-        "png" | "jpg" | "jpeg" | "bmp" | "gif" | "webp" | "pal"
-    )
+        ext.to_ascii_lowercase().as_str(),
+        "png" | "jpg" | "jpeg" | "bmp" | "gif" | "webp" | "ora" | "4bpp" | "8bpp" | "lz" | "bin" | "c" | "h" | "s"
+    ) || palette::is_palette_extension(ext)
+}
+
+/// Format-specific checks on bytes about to be written to `path`, so a
+/// malformed container never replaces a good file.
+fn validate_write_payload(path: &Path, data: &[u8]) -> Result<(), String> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if ext.eq_ignore_ascii_case("ora") {
+        ora::validate_ora(data)?;
+    }
+    Ok(())
 }
 
 #[tauri::command]
-fn read_image_file(path: String) -> Result<Vec<u8>, String> {
+fn read_image_file(grants: tauri::State<'_, FileGrants>, path: String) -> Result<tauri::ipc::Response, String> {
     let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
     if !p.is_file() {
         return Err("path is not an existing file".into());
     }
     if !is_image_extension(p.extension().and_then(|e| e.to_str())) {
         return Err("only image files can be read".into());
     }
-    std::fs::read(&p).map_err(|e| format!("read failed: {}", e))
+    let bytes = std::fs::read(&p).map_err(|e| format!("read failed: {}", e))?;
+    Ok(tauri::ipc::Response::new(bytes))
 }
 
+/// Remember the chunks of a PNG the window has opened as its document, so
+/// saving it puts them back (see `png_chunks`). Only called for documents:
+/// thumbnail reads would push them out of the per-window list.
 #[tauri::command]
-fn write_allowed_file(path: String, data: Vec<u8>) -> Result<(), String> {
+fn keep_png_chunks(
+    window: tauri::Window,
+    grants: tauri::State<'_, FileGrants>,
+    kept_chunks: tauri::State<'_, png_chunks::KeptPngChunks>,
+    path: String,
+) -> Result<(), String> {
     let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !ext.eq_ignore_ascii_case("png") || !p.is_file() {
+        return Ok(());
+    }
+    let bytes = std::fs::read(&p).map_err(|e| format!("read failed: {}", e))?;
+    if bytes.starts_with(&png::SIGNATURE) {
+        kept_chunks.remember(window.label(), &grants::canonical_path(&p), &bytes);
+    }
+    Ok(())
+}
+
+struct BackupConfig(Mutex<BackupSettings>);
+
+impl BackupConfig {
+    fn generations(&self) -> u32 {
+        self.0.lock().map(|s| s.generations).unwrap_or(0)
+    }
+}
+
+fn backup_settings_path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    let dir = app.path().app_config_dir().ok()?;
+    Some(dir.join("backup-settings.json"))
+}
+
+fn read_backup_settings(app: &tauri::AppHandle) -> Option<BackupSettings> {
+    let path = backup_settings_path(app)?;
+    let raw = std::fs::read(path).ok()?;
+    serde_json::from_slice::<BackupSettings>(&raw).ok()
+}
+
+fn write_backup_settings(app: &tauri::AppHandle, settings: &BackupSettings) -> Result<(), String> {
+    let path = backup_settings_path(app).ok_or_else(|| "no config path".to_string())?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("create config dir failed: {}", e))?;
+    }
+    let bytes = serde_json::to_vec_pretty(settings).map_err(|e| format!("serialize backup settings failed: {}", e))?;
+    atomic_save::write_atomic(&path, &bytes)
+}
+
+#[tauri::command]
+fn get_backup_settings(state: tauri::State<'_, BackupConfig>) -> BackupSettings {
+    state.0.lock().map(|s| s.clone()).unwrap_or_default()
+}
+
+#[tauri::command]
+fn set_backup_settings(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, BackupConfig>,
+    settings: BackupSettings,
+) -> Result<BackupSettings, String> {
+    let settings = BackupSettings {
+        generations: settings.generations.min(atomic_save::MAX_BACKUP_GENERATIONS),
+    };
+    write_backup_settings(&app, &settings)?;
+    if let Ok(mut guard) = state.0.lock() {
+        *guard = settings.clone();
+    }
+    Ok(settings)
+}
+
+fn writable_target_path(grants: &FileGrants, path: &str) -> Result<PathBuf, String> {
+    let p = normalize_to_absolute_path(path)?;
+    grants.check(&p)?;
     if !is_allowed_write_extension(p.extension().and_then(|e| e.to_str())) {
         return Err("file extension not allowed".into());
     }
@@ -489,7 +601,358 @@ fn write_allowed_file(path: String, data: Vec<u8>) -> Result<(), String> {
     } else {
         return Err("invalid target path".into());
     }
-    std::fs::write(&p, data).map_err(|e| format!("write failed: {}", e))
+    Ok(p)
+}
+
+/// Body: the file bytes. Headers: `path`, and for PNGs optionally
+/// `cdpaint-metadata` (`editor_meta::EditorMetadata` as JSON) to embed. PNG
+/// chunks kept from when this window read the same path are put back (see
+/// `png_chunks`).
+#[tauri::command]
+fn write_allowed_file(
+    app: tauri::AppHandle,
+    window: tauri::Window,
+    request: tauri::ipc::Request<'_>,
+    backups: tauri::State<'_, BackupConfig>,
+    grants: tauri::State<'_, FileGrants>,
+    kept_chunks: tauri::State<'_, png_chunks::KeptPngChunks>,
+) -> Result<(), String> {
+    let p = writable_target_path(&grants, &ipc::required_header(&request, "path")?)?;
+    let data = ipc::raw_body(&request)?;
+    validate_write_payload(&p, data)?;
+    let mut merged = if data.starts_with(&png::SIGNATURE) {
+        kept_chunks.restore(window.label(), &grants::canonical_path(&p), data)
+    } else {
+        None
+    };
+    if let Some(meta) = ipc::header(&request, "cdpaint-metadata")? {
+        if !data.starts_with(&png::SIGNATURE) {
+            return Err("editor metadata can only be embedded in PNG files".into());
+        }
+        let meta: editor_meta::EditorMetadata =
+            serde_json::from_str(&meta).map_err(|e| format!("invalid editor metadata: {}", e))?;
+        merged = Some(editor_meta::embed(merged.as_deref().unwrap_or(data), &meta)?);
+    }
+    watcher::note_own_write(&app, &p);
+    atomic_save::save_with_backups(&p, merged.as_deref().unwrap_or(data), backups.generations())
+}
+
+#[tauri::command]
+fn list_file_backups(grants: tauri::State<'_, FileGrants>, path: String) -> Result<Vec<FileBackupEntry>, String> {
+    let p = writable_target_path(&grants, &path)?;
+    Ok(atomic_save::list_backups(&p))
+}
+
+#[tauri::command]
+fn restore_file_backup(
+    app: tauri::AppHandle,
+    path: String,
+    generation: u32,
+    backups: tauri::State<'_, BackupConfig>,
+    grants: tauri::State<'_, FileGrants>,
+) -> Result<(), String> {
+    let p = writable_target_path(&grants, &path)?;
+    watcher::note_own_write(&app, &p);
+    atomic_save::restore_backup(&p, generation, backups.generations())
+}
+
+/// Parse an ORA on the Rust side and return its layer stack. Nested stacks
+/// come back as groups unless `flatten_groups` is set. The response is framed
+/// (see `ipc::framed_response`): the document JSON first, then every layer's
+/// PNG, located by each layer's `png_offset` / `png_len`.
+#[tauri::command]
+fn read_ora_document(
+    grants: tauri::State<'_, FileGrants>,
+    path: String,
+    flatten_groups: Option<bool>,
+) -> Result<tauri::ipc::Response, String> {
+    let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    if !p.is_file() {
+        return Err("path is not an existing file".into());
+    }
+    let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !ext.eq_ignore_ascii_case("ora") {
+        return Err("only .ora files can be read as layered documents".into());
+    }
+    let bytes = std::fs::read(&p).map_err(|e| format!("read failed: {}", e))?;
+    let mut doc = ora::read_ora_document(&bytes, flatten_groups.unwrap_or(false))?;
+    let blobs = ora::take_layer_pngs(&mut doc);
+    ipc::framed_response(&doc, blobs)
+}
+
+/// `read_ora_document` for an archive already in memory (a file dropped or
+/// picked in the webview, or a recovery snapshot). Body: the ORA bytes.
+/// Headers: `flatten-groups` (optional, `true`).
+#[tauri::command]
+fn parse_ora_document(request: tauri::ipc::Request<'_>) -> Result<tauri::ipc::Response, String> {
+    let bytes = ipc::raw_body(&request)?;
+    let flatten = ipc::header(&request, "flatten-groups")?.is_some_and(|v| v == "true");
+    let mut doc = ora::read_ora_document(bytes, flatten)?;
+    let blobs = ora::take_layer_pngs(&mut doc);
+    ipc::framed_response(&doc, blobs)
+}
+
+/// Decode an indexed PNG keeping its exact palette and indices. The response
+/// is framed (see `ipc::framed_response`): `png::IndexedPngInfo` as JSON,
+/// then one index byte per pixel, row-major.
+#[tauri::command]
+fn decode_indexed_png(grants: tauri::State<'_, FileGrants>, path: String) -> Result<tauri::ipc::Response, String> {
+    let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    if !p.is_file() {
+        return Err("path is not an existing file".into());
+    }
+    let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !ext.eq_ignore_ascii_case("png") {
+        return Err("only .png files can be decoded as indexed images".into());
+    }
+    let bytes = std::fs::read(&p).map_err(|e| format!("read failed: {}", e))?;
+    let image = png::decode_indexed(&bytes)?;
+    ipc::framed_response(&image.info, vec![image.indices])
+}
+
+/// Decode a paletted (1/4/8-bit, optionally RLE) BMP keeping its exact
+/// palette. Framed like `decode_indexed_png`: `bmp::IndexedBmpInfo` as JSON,
+/// then one index byte per pixel, row-major from the top.
+#[tauri::command]
+fn decode_indexed_bmp(grants: tauri::State<'_, FileGrants>, path: String) -> Result<tauri::ipc::Response, String> {
+    let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    if !p.is_file() {
+        return Err("path is not an existing file".into());
+    }
+    let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !ext.eq_ignore_ascii_case("bmp") {
+        return Err("only .bmp files can be decoded as indexed bitmaps".into());
+    }
+    let bytes = std::fs::read(&p).map_err(|e| format!("read failed: {}", e))?;
+    let image = bmp::decode_indexed(&bytes)?;
+    ipc::framed_response(&image.info, vec![image.indices])
+}
+
+/// Encode a 4/8-bit paletted BMP from one index byte per pixel (the raw
+/// body) and `bmp::IndexedBmpOptions` as JSON in the `options` header.
+#[tauri::command]
+fn encode_indexed_bmp(request: tauri::ipc::Request<'_>) -> Result<tauri::ipc::Response, String> {
+    let indices = ipc::raw_body(&request)?;
+    let options = ipc::required_header(&request, "options")?;
+    let options: bmp::IndexedBmpOptions =
+        serde_json::from_str(&options).map_err(|e| format!("invalid BMP options: {}", e))?;
+    Ok(tauri::ipc::Response::new(bmp::encode_indexed(&options, indices)?))
+}
+
+/// Decode every frame of a GIF. Framed: `gif_frames::GifInfo` as JSON, then
+/// one full-canvas RGBA image per frame, in order.
+#[tauri::command]
+fn decode_gif_frames(grants: tauri::State<'_, FileGrants>, path: String) -> Result<tauri::ipc::Response, String> {
+    let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    if !p.is_file() {
+        return Err("path is not an existing file".into());
+    }
+    let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !ext.eq_ignore_ascii_case("gif") {
+        return Err("only .gif files can be decoded as frames".into());
+    }
+    let bytes = std::fs::read(&p).map_err(|e| format!("read failed: {}", e))?;
+    let gif = gif_frames::decode(&bytes)?;
+    ipc::framed_response(&gif.info, gif.frames)
+}
+
+/// Encode an indexed GIF from full-canvas frames of one index byte per pixel,
+/// back to back in the raw body, and `gif_frames::GifEncodeOptions` as JSON
+/// in the `options` header.
+#[tauri::command]
+fn encode_indexed_gif(request: tauri::ipc::Request<'_>) -> Result<tauri::ipc::Response, String> {
+    let indices = ipc::raw_body(&request)?;
+    let options = ipc::required_header(&request, "options")?;
+    let options: gif_frames::GifEncodeOptions =
+        serde_json::from_str(&options).map_err(|e| format!("invalid GIF options: {}", e))?;
+    Ok(tauri::ipc::Response::new(gif_frames::encode(&options, indices)?))
+}
+
+/// Parse an Aseprite document. The response is framed: the
+/// `aseprite::AsepriteDocument` as JSON, then every cel's pixels in the
+/// file's color depth at the offsets recorded on each cel.
+#[tauri::command]
+fn read_aseprite_document(grants: tauri::State<'_, FileGrants>, path: String) -> Result<tauri::ipc::Response, String> {
+    let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    if !p.is_file() {
+        return Err("path is not an existing file".into());
+    }
+    let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !ext.eq_ignore_ascii_case("ase") && !ext.eq_ignore_ascii_case("aseprite") {
+        return Err("only .ase and .aseprite files can be read as Aseprite documents".into());
+    }
+    let bytes = std::fs::read(&p).map_err(|e| format!("read failed: {}", e))?;
+    let mut doc = aseprite::parse(&bytes)?;
+    let blobs = aseprite::take_cel_pixels(&mut doc);
+    ipc::framed_response(&doc, blobs)
+}
+
+/// The CDPaint editing metadata embedded in a PNG by `write_allowed_file`,
+/// or null when the file has none.
+#[tauri::command]
+fn read_image_metadata(
+    grants: tauri::State<'_, FileGrants>,
+    path: String,
+) -> Result<Option<editor_meta::EditorMetadata>, String> {
+    let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    if !p.is_file() {
+        return Err("path is not an existing file".into());
+    }
+    let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !ext.eq_ignore_ascii_case("png") {
+        return Ok(None);
+    }
+    let bytes = std::fs::read(&p).map_err(|e| format!("read failed: {}", e))?;
+    editor_meta::extract(&bytes)
+}
+
+/// Encode an indexed PNG from one index byte per pixel (the raw body) and
+/// `png::IndexedPngOptions` as JSON in the `options` header. Answers with the
+/// PNG file bytes.
+#[tauri::command]
+fn encode_indexed_png(request: tauri::ipc::Request<'_>) -> Result<tauri::ipc::Response, String> {
+    let indices = ipc::raw_body(&request)?;
+    let options = ipc::required_header(&request, "options")?;
+    let options: png::IndexedPngOptions =
+        serde_json::from_str(&options).map_err(|e| format!("invalid PNG options: {}", e))?;
+    Ok(tauri::ipc::Response::new(png::encode_indexed(&options, indices)?))
+}
+
+/// GBA tile data for an indexed image, as gbagfx would convert it. Body: one
+/// index byte per pixel; `options` header: `gba_tiles::GbaTilesOptions` as
+/// JSON. Framed: `gba_tiles::GbaTilesInfo` as JSON, then the `.4bpp` /
+/// `.8bpp` bytes followed by the `.gbapal` bytes.
+#[tauri::command]
+fn export_gba_tiles(request: tauri::ipc::Request<'_>) -> Result<tauri::ipc::Response, String> {
+    let indices = ipc::raw_body(&request)?;
+    let options = ipc::required_header(&request, "options")?;
+    let options: gba_tiles::GbaTilesOptions =
+        serde_json::from_str(&options).map_err(|e| format!("invalid tile options: {}", e))?;
+    let out = gba_tiles::encode(&options, indices)?;
+    ipc::framed_response(&out.info, vec![out.tiles, out.palette])
+}
+
+/// Cut an indexed image into deduplicated 8x8 tiles and a text-BG tilemap.
+/// Body: one index byte per pixel; `options` header:
+/// `tilemap::TilemapOptions` as JSON. Framed: `tilemap::TilemapInfo` as
+/// JSON, then the tileset followed by the `.bin` screen entries.
+#[tauri::command]
+fn generate_tilemap(request: tauri::ipc::Request<'_>) -> Result<tauri::ipc::Response, String> {
+    let indices = ipc::raw_body(&request)?;
+    let options = ipc::required_header(&request, "options")?;
+    let options: tilemap::TilemapOptions =
+        serde_json::from_str(&options).map_err(|e| format!("invalid tilemap options: {}", e))?;
+    let out = tilemap::generate(&options, indices)?;
+    ipc::framed_response(&out.info, vec![out.tileset, out.map])
+}
+
+/// Cut sprite art into legal OBJ pieces. Body: one index byte per pixel;
+/// `options` header: `oam::OamOptions` as JSON. Framed: `oam::OamInfo` as
+/// JSON, then every piece's tiles in turn.
+#[tauri::command]
+fn slice_oam_sprite(request: tauri::ipc::Request<'_>) -> Result<tauri::ipc::Response, String> {
+    let indices = ipc::raw_body(&request)?;
+    let options = ipc::required_header(&request, "options")?;
+    let options: oam::OamOptions =
+        serde_json::from_str(&options).map_err(|e| format!("invalid OAM options: {}", e))?;
+    let out = oam::slice(&options, indices)?;
+    ipc::framed_response(&out.info, vec![out.tiles])
+}
+
+/// `path`'s file name split into the part before `.4bpp` / `.8bpp`, the bit
+/// depth, and whether `.lz` follows.
+fn split_tile_file_name(path: &Path) -> Option<(String, u8, bool)> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    let (name, compressed) = match name.strip_suffix(".lz") {
+        Some(rest) => (rest.to_string(), true),
+        None => (name, false),
+    };
+    let (stem, bit_depth) = match (name.strip_suffix(".4bpp"), name.strip_suffix(".8bpp")) {
+        (Some(stem), _) => (stem, 4),
+        (_, Some(stem)) => (stem, 8),
+        _ => return None,
+    };
+    Some((stem.to_string(), bit_depth, compressed))
+}
+
+/// Open `.4bpp` / `.8bpp` tile data, LZ77-compressed (`.lz`) or not, as an
+/// indexed image. `width` is in pixels; without it the width of a PNG of
+/// the same name beside the file is used. The palette is `palette_path`, or
+/// else `<name>.gbapal` or `normal.gbapal` beside the file when readable.
+/// Framed like `decode_indexed_png`: `gba_tiles::TileImageInfo` as JSON,
+/// then one index byte per pixel.
+#[tauri::command]
+fn read_gba_tiles(
+    grants: tauri::State<'_, FileGrants>,
+    path: String,
+    width: Option<u32>,
+    palette_path: Option<String>,
+) -> Result<tauri::ipc::Response, String> {
+    let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    if !p.is_file() {
+        return Err("path is not an existing file".into());
+    }
+    let (stem, bit_depth, compressed) =
+        split_tile_file_name(&p).ok_or("only .4bpp and .8bpp files (optionally .lz) can be read as tiles")?;
+    let dir = p.parent().ok_or("invalid tile file path")?;
+    // Siblings are only consulted when they are covered by a grant too.
+    let sibling = |name: &str| {
+        std::fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .map(|e| e.path())
+            .find(|s| s.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.eq_ignore_ascii_case(name)))
+            .filter(|s| s.is_file() && grants.check(s).is_ok())
+    };
+
+    let width = match width {
+        Some(w) => w,
+        None => {
+            let png_path = sibling(&format!("{}.png", stem)).ok_or("a width is needed to lay out the tiles")?;
+            let bytes = std::fs::read(&png_path).map_err(|e| format!("read failed: {}", e))?;
+            png::dimensions(&bytes)?.0
+        }
+    };
+    let bytes = std::fs::read(&p).map_err(|e| format!("read failed: {}", e))?;
+    let tiles = if compressed { lz77::decompress(&bytes)? } else { bytes };
+    let (height, indices) = gba_tiles::decode_tiles(&tiles, width as usize, bit_depth)?;
+
+    let palette_file = match palette_path {
+        Some(pp) => {
+            let pp = normalize_to_absolute_path(&pp)?;
+            grants.check(&pp)?;
+            Some(pp)
+        }
+        None => sibling(&format!("{}.gbapal", stem)).or_else(|| sibling("normal.gbapal")),
+    };
+    let mut palette: Vec<[u8; 3]> = match &palette_file {
+        Some(pp) => {
+            let bytes = std::fs::read(pp).map_err(|e| format!("read failed: {}", e))?;
+            palette::read(&bytes)?.colors.iter().map(|c| [c[0], c[1], c[2]]).collect()
+        }
+        None => gba_tiles::gray_ramp(bit_depth),
+    };
+    // Indices past the end of a short palette show as black.
+    let used = indices.iter().max().map_or(0, |&m| m as usize + 1);
+    if palette.len() < used {
+        palette.resize(used, [0, 0, 0]);
+    }
+    let info = gba_tiles::TileImageInfo {
+        width,
+        height: height as u32,
+        bit_depth,
+        compressed,
+        palette,
+        palette_path: palette_file.map(|pp| normalize_device_path(&pp.to_string_lossy())),
+    };
+    ipc::framed_response(&info, vec![indices])
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -508,8 +971,7 @@ const SCAN_DIR_DENYLIST: [&str; 6] = [".git", "node_modules", "target", ".vscode
 fn scan_dir(dir: &Path, depth: usize, out: &mut ProjectNode) -> std::io::Result<()> {
     let mut dir_entries: Vec<(String, PathBuf)> = Vec::new();
     let mut file_entries: Vec<(String, PathBuf, u64)> = Vec::new();
-    let mut entries = std::fs::read_dir(dir)?;
-    while let Some(entry) = entries.next() {
+    for entry in std::fs::read_dir(dir)? {
         let entry = match entry {
             Ok(e) => e,
             Err(_) => continue,
@@ -536,7 +998,7 @@ fn scan_dir(dir: &Path, depth: usize, out: &mut ProjectNode) -> std::io::Result<
                 .extension()
                 .and_then(|e| e.to_str())
                 .map(|e| e.to_ascii_lowercase());
-            if matches!(ext.as_deref(), Some("png") | Some("pal")) {
+            if ext.as_deref() == Some("png") || palette::is_palette_file(&p) {
                 file_entries.push((fname, p, meta.len()));
             }
         }
@@ -550,10 +1012,8 @@ fn scan_dir(dir: &Path, depth: usize, out: &mut ProjectNode) -> std::io::Result<
             size: 0,
             children: Vec::new(),
         };
-        if scan_dir(&p, depth + 1, &mut node).is_ok() {
-            if !node.children.is_empty() {
-                out.children.push(node);
-            }
+        if scan_dir(&p, depth + 1, &mut node).is_ok() && !node.children.is_empty() {
+            out.children.push(node);
         }
     }
     for (fname, p, size) in file_entries {
@@ -579,8 +1039,9 @@ fn scan_dir(dir: &Path, depth: usize, out: &mut ProjectNode) -> std::io::Result<
 }
 
 #[tauri::command]
-fn scan_project(path: String) -> Result<ProjectNode, String> {
+fn scan_project(grants: tauri::State<'_, FileGrants>, path: String) -> Result<ProjectNode, String> {
     let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
     if !p.is_dir() {
         return Err("path is not an existing directory".into());
     }
@@ -599,9 +1060,94 @@ fn scan_project(path: String) -> Result<ProjectNode, String> {
     Ok(root)
 }
 
+/// Check each folder of a scanned tree with `palette_check`, one folder's
+/// files in memory at a time. Files that can't be read are left out; the
+/// scan already saw them.
+fn check_palette_folders(node: &ProjectNode, out: &mut Vec<palette_check::Diagnostic>) {
+    let mut folder = palette_check::Folder::default();
+    for child in &node.children {
+        if child.kind == "dir" {
+            check_palette_folders(child, out);
+            continue;
+        }
+        let lower = child.name.to_ascii_lowercase();
+        let slot = match lower.as_str() {
+            "normal.pal" => &mut folder.normal,
+            "shiny.pal" => &mut folder.shiny,
+            _ if lower.ends_with(".png") => {
+                if child.size > palette_check::MAX_PNG_FILE_BYTES {
+                    out.push(palette_check::too_large(&child.path, child.size));
+                } else if let Ok(bytes) = std::fs::read(&child.path) {
+                    folder.pngs.push((child.path.clone(), bytes));
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if child.size <= palette::MAX_PALETTE_FILE_BYTES {
+            *slot = std::fs::read(&child.path).ok().map(|bytes| (child.path.clone(), bytes));
+        }
+    }
+    if !folder.pngs.is_empty() || folder.normal.is_some() {
+        out.extend(palette_check::check_folder(&folder));
+    }
+}
+
+/// Check every PNG under a project folder against the palette rules of
+/// `palette_check`. Walks the same tree as `scan_project`.
+#[tauri::command]
+fn validate_project_palettes(
+    grants: tauri::State<'_, FileGrants>,
+    path: String,
+) -> Result<Vec<palette_check::Diagnostic>, String> {
+    let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    if !p.is_dir() {
+        return Err("path is not an existing directory".into());
+    }
+    let mut root = ProjectNode {
+        name: String::new(),
+        path: p.to_string_lossy().to_string(),
+        kind: "dir".to_string(),
+        ext: None,
+        size: 0,
+        children: Vec::new(),
+    };
+    scan_dir(&p, 0, &mut root).map_err(|e| format!("scan failed: {}", e))?;
+    let mut diagnostics = Vec::new();
+    check_palette_folders(&root, &mut diagnostics);
+    Ok(diagnostics)
+}
+
+/// Species graphics of the decomp a hooked folder belongs to (see
+/// `decomp_index`). `path` is the hooked folder: the decomp root or its
+/// `graphics/`. The headers (and the front pic source, when the decomp has
+/// one) must be readable under the grants.
 #[tauri::command]
-fn read_text_file(path: String) -> Result<String, String> {
+fn index_decomp_project(grants: tauri::State<'_, FileGrants>, path: String) -> Result<decomp_index::DecompIndex, String> {
     let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    let root = decomp_index::find_root(&p).ok_or("not a pokeemerald-style decomp (no species headers found)")?;
+    let species_path = root.join(decomp_index::SPECIES_HEADER);
+    let graphics_path = root.join(decomp_index::GRAPHICS_HEADER);
+    grants.check(&species_path)?;
+    grants.check(&graphics_path)?;
+    let species = std::fs::read_to_string(&species_path).map_err(|e| format!("read failed: {}", e))?;
+    let graphics = std::fs::read_to_string(&graphics_path).map_err(|e| format!("read failed: {}", e))?;
+    let front_pics_path = root.join(decomp_index::FRONT_PICS_SOURCE);
+    let front_pics = if front_pics_path.is_file() {
+        grants.check(&front_pics_path)?;
+        std::fs::read_to_string(&front_pics_path).map_err(|e| format!("read failed: {}", e))?
+    } else {
+        String::new()
+    };
+    Ok(decomp_index::build(&root, &species, &[&graphics, &front_pics]))
+}
+
+#[tauri::command]
+fn read_text_file(grants: tauri::State<'_, FileGrants>, path: String) -> Result<String, String> {
+    let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
     if !p.is_file() {
         return Err("path is not an existing file".into());
     }
@@ -615,12 +1161,166 @@ fn read_text_file(path: String) -> Result<String, String> {
     std::fs::read_to_string(&p).map_err(|e| format!("read failed: {}", e))
 }
 
-#[derive(Debug, Deserialize)]
+/// Read a palette in any format `palette` knows; the format is told by the
+/// file's content, not its extension.
+#[tauri::command]
+fn read_palette_file(grants: tauri::State<'_, FileGrants>, path: String) -> Result<palette::PaletteFile, String> {
+    let p = normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    if !p.is_file() {
+        return Err("path is not an existing file".into());
+    }
+    let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !palette::is_palette_extension(ext) {
+        return Err("only palette files can be read as palettes".into());
+    }
+    let len = std::fs::metadata(&p).map_err(|e| format!("read failed: {}", e))?.len();
+    if len > palette::MAX_PALETTE_FILE_BYTES {
+        return Err("palette file is too large".into());
+    }
+    let bytes = std::fs::read(&p).map_err(|e| format!("read failed: {}", e))?;
+    palette::read(&bytes)
+}
+
+/// Write `colors` (RGBA) as a palette file. The format follows the path's
+/// extension (`.pal` is JASC-PAL, `.txt` Paint.NET) unless `format` is given.
+#[tauri::command]
+fn write_palette_file(
+    app: tauri::AppHandle,
+    backups: tauri::State<'_, BackupConfig>,
+    grants: tauri::State<'_, FileGrants>,
+    path: String,
+    colors: Vec<[u8; 4]>,
+    format: Option<palette::PaletteFormat>,
+    name: Option<String>,
+) -> Result<(), String> {
+    let p = writable_target_path(&grants, &path)?;
+    let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let format = format
+        .or_else(|| palette::PaletteFormat::from_extension(ext))
+        .ok_or_else(|| "only palette files can be written as palettes".to_string())?;
+    let bytes = palette::write(format, &colors, name.as_deref())?;
+    watcher::note_own_write(&app, &p);
+    atomic_save::save_with_backups(&p, &bytes, backups.generations())
+}
+
+/// `colors` (RGBA) encoded as a palette file in `format`, for exports that
+/// bundle the palette with other files.
+#[tauri::command]
+fn encode_palette(
+    colors: Vec<[u8; 4]>,
+    format: palette::PaletteFormat,
+    name: Option<String>,
+) -> Result<tauri::ipc::Response, String> {
+    Ok(tauri::ipc::Response::new(palette::write(format, &colors, name.as_deref())?))
+}
+
+/// Rewrites for reordering the palette at `path` along with the PNGs and
+/// palettes beside it (see `palette_reorder`). The whole folder must be
+/// granted, since any file in it may be rewritten.
+fn plan_palette_reorder(grants: &FileGrants, path: &str, order: &[usize]) -> Result<Vec<palette_reorder::Rewrite>, String> {
+    let p = normalize_to_absolute_path(path)?;
+    grants.check(&p)?;
+    if !p.is_file() {
+        return Err("path is not an existing file".into());
+    }
+    let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !palette::is_palette_extension(ext) {
+        return Err("only palette files can be reordered".into());
+    }
+    let dir = p.parent().ok_or("palette has no folder")?;
+    grants.check(dir)?;
+    let source = std::fs::read(&p).map_err(|e| format!("read failed: {}", e))?;
+    let mut siblings = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(|e| format!("read folder failed: {}", e))?.flatten() {
+        let sibling = entry.path();
+        let is_file = std::fs::symlink_metadata(&sibling).is_ok_and(|m| m.is_file());
+        let ext = sibling.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        if !is_file || sibling == p || !(ext == "png" || palette::is_palette_extension(&ext)) {
+            continue;
+        }
+        if let Ok(bytes) = std::fs::read(&sibling) {
+            siblings.push((sibling.to_string_lossy().to_string(), bytes));
+        }
+    }
+    siblings.sort_by_key(|(path, _)| path.to_ascii_lowercase());
+    palette_reorder::plan((&p.to_string_lossy(), &source), &siblings, order)
+}
+
+/// What reordering the palette at `path` by `order` (`order[new] = old`)
+/// would rewrite, with each file's current hash, without writing anything.
+#[tauri::command]
+fn preview_palette_reorder(
+    grants: tauri::State<'_, FileGrants>,
+    path: String,
+    order: Vec<usize>,
+) -> Result<Vec<palette_reorder::ReorderedFile>, String> {
+    Ok(plan_palette_reorder(&grants, &path, &order)?.into_iter().map(|r| r.info).collect())
+}
+
+/// Reorder the palette at `path` and the previewed `files` (paths and the
+/// hashes the preview returned) the user kept, all replaced as one unit.
+/// Nothing is written if any of them changed since the preview.
+#[tauri::command]
+fn apply_palette_reorder(
+    app: tauri::AppHandle,
+    backups: tauri::State<'_, BackupConfig>,
+    grants: tauri::State<'_, FileGrants>,
+    path: String,
+    order: Vec<usize>,
+    files: Vec<palette_reorder::PreviewedFile>,
+) -> Result<Vec<palette_reorder::ReorderedFile>, String> {
+    let rewrites = palette_reorder::select(plan_palette_reorder(&grants, &path, &order)?, &files)?;
+    let writes: Vec<(&Path, &[u8])> = rewrites
+        .iter()
+        .map(|r| (Path::new(&r.info.path), r.data.as_slice()))
+        .collect();
+    for (path, _) in &writes {
+        watcher::note_own_write(&app, path);
+    }
+    atomic_save::save_all_with_backups(&writes, backups.generations())?;
+    Ok(rewrites.into_iter().map(|r| r.info).collect())
+}
+
+#[derive(Debug)]
 struct ExportFilePayload {
     name: String,
     data: Vec<u8>,
 }
 
+#[derive(Debug, Deserialize)]
+struct ExportFileManifestEntry {
+    name: String,
+    size: usize,
+}
+
+/// Split a binary export request into files. The body is every file's bytes
+/// back to back; the `files` header is a JSON list of `{ name, size }` in the
+/// same order.
+fn export_files_from_request(request: &tauri::ipc::Request<'_>) -> Result<Vec<ExportFilePayload>, String> {
+    let manifest = ipc::required_header(request, "files")?;
+    let entries: Vec<ExportFileManifestEntry> =
+        serde_json::from_str(&manifest).map_err(|e| format!("invalid files header: {}", e))?;
+    let body = ipc::raw_body(request)?;
+    let total = entries
+        .iter()
+        .try_fold(0usize, |acc, e| acc.checked_add(e.size))
+        .ok_or_else(|| "export file sizes overflow".to_string())?;
+    if total != body.len() {
+        return Err("export file sizes do not match the request body".into());
+    }
+    let mut offset = 0;
+    let mut files = Vec::with_capacity(entries.len());
+    for entry in entries {
+        files.push(ExportFilePayload {
+            name: entry.name,
+            data: body[offset..offset + entry.size].to_vec(),
+        });
+        offset += entry.size;
+    }
+    Ok(files)
+}
+
 fn resolve_dialog_file_path(file_path: tauri_plugin_dialog::FilePath) -> Result<PathBuf, String> {
     if let Ok(path) = file_path.clone().into_path() {
         if path.is_absolute() {
@@ -633,37 +1333,114 @@ fn resolve_dialog_file_path(file_path: tauri_plugin_dialog::FilePath) -> Result<
     normalize_to_absolute_path(&raw)
 }
 
-fn write_export_files_to_directory(dir: &Path, files: Vec<ExportFilePayload>) -> Result<(), String> {
-    if !dir.is_dir() {
-        return Err("target directory does not exist".into());
+fn export_plan(dir: &Path, files: &[ExportFilePayload]) -> Result<Vec<export::ExportPlanEntry>, String> {
+    export::plan_export(dir, files.iter().map(|f| (f.name.as_str(), f.data.as_slice())))
+}
+
+/// Write a bundle into `dir` under `policy`, all or nothing: names,
+/// payloads and the policy are checked up front, and the files the policy
+/// lets through are replaced as one unit (see
+/// `atomic_save::save_all_with_backups`).
+fn write_export_files_to_directory(
+    app: &tauri::AppHandle,
+    dir: &Path,
+    files: Vec<ExportFilePayload>,
+    policy: export::ExportPolicy,
+    backup_generations: u32,
+) -> Result<export::ExportReport, String> {
+    let plan = export_plan(dir, &files)?;
+    export::enforce_policy(&plan, policy)?;
+    for (file, entry) in files.iter().zip(&plan) {
+        validate_write_payload(Path::new(&entry.path), &file.data).map_err(|e| format!("{} ({})", e, entry.name))?;
     }
-    for file in files {
-        let name = file.name.trim();
-        if name.is_empty() {
-            return Err("file name is empty".into());
-        }
-        if name.contains('\\') || name.contains('/') || name.contains(':') {
-            return Err("file name must not contain path separators".into());
-        }
-        let out = dir.join(name);
-        if !is_allowed_write_extension(out.extension().and_then(|e| e.to_str())) {
-            return Err(format!("file extension not allowed: {}", name));
-        }
-        std::fs::write(&out, file.data).map_err(|e| format!("write failed ({}): {}", name, e))?;
+    let writes: Vec<(&Path, &[u8])> = files
+        .iter()
+        .zip(&plan)
+        .filter(|(_, entry)| entry.should_write(policy))
+        .map(|(file, entry)| (Path::new(&entry.path), file.data.as_slice()))
+        .collect();
+    for (path, _) in &writes {
+        watcher::note_own_write(app, path);
     }
-    Ok(())
+    atomic_save::save_all_with_backups(&writes, backup_generations)?;
+    let data: Vec<&[u8]> = files.iter().map(|f| f.data.as_slice()).collect();
+    Ok(export::build_report(dir, plan, &data, policy))
+}
+
+/// Dry run of `write_export_files`: what each file would do to the folder.
+/// Body and `files` header: see `export_files_from_request`. Headers:
+/// `directory`.
+#[tauri::command]
+fn preview_export_files(
+    request: tauri::ipc::Request<'_>,
+    grants: tauri::State<'_, FileGrants>,
+) -> Result<Vec<export::ExportPlanEntry>, String> {
+    let dir = normalize_to_absolute_path(&ipc::required_header(&request, "directory")?)?;
+    grants.check(&dir)?;
+    let files = export_files_from_request(&request)?;
+    export_plan(&dir, &files)
+}
+
+/// Body and `files` header: see `export_files_from_request`. Headers:
+/// `directory` and `policy` (optional, see `export::ExportPolicy`).
+#[tauri::command]
+fn write_export_files(
+    app: tauri::AppHandle,
+    request: tauri::ipc::Request<'_>,
+    backups: tauri::State<'_, BackupConfig>,
+    grants: tauri::State<'_, FileGrants>,
+) -> Result<export::ExportReport, String> {
+    let dir = normalize_to_absolute_path(&ipc::required_header(&request, "directory")?)?;
+    grants.check(&dir)?;
+    let files = export_files_from_request(&request)?;
+    let policy = export::ExportPolicy::parse(ipc::header(&request, "policy")?.as_deref())?;
+    write_export_files_to_directory(&app, &dir, files, policy, backups.generations())
 }
 
+/// Write an indexed image or palette as C or assembly source next to the
+/// image. Body: one index byte per pixel (empty for a palette); headers:
+/// `path` (the image; the files are named after it and go in its folder),
+/// `options` (`gba_source::SourceOptions` as JSON) and `policy` (optional;
+/// unlike the other exports it defaults to `fail-on-difference`, since the
+/// files land next to hand-edited sources).
 #[tauri::command]
-fn write_export_files(directory: String, files: Vec<ExportFilePayload>) -> Result<(), String> {
-    let dir = normalize_to_absolute_path(&directory)?;
-    write_export_files_to_directory(&dir, files)
+fn export_gba_source(
+    app: tauri::AppHandle,
+    request: tauri::ipc::Request<'_>,
+    backups: tauri::State<'_, BackupConfig>,
+    grants: tauri::State<'_, FileGrants>,
+) -> Result<export::ExportReport, String> {
+    let path = normalize_to_absolute_path(&ipc::required_header(&request, "path")?)?;
+    let dir = path.parent().ok_or("image path has no folder")?.to_path_buf();
+    grants.check(&dir)?;
+    let file_name = path.file_name().and_then(|n| n.to_str()).ok_or("image path has no file name")?;
+    let options = ipc::required_header(&request, "options")?;
+    let options: gba_source::SourceOptions =
+        serde_json::from_str(&options).map_err(|e| format!("invalid source options: {}", e))?;
+    let policy = match ipc::header(&request, "policy")? {
+        Some(value) => export::ExportPolicy::parse(Some(&value))?,
+        None => export::ExportPolicy::FailOnDifference,
+    };
+    let files = gba_source::generate(file_name, &options, ipc::raw_body(&request)?)?
+        .into_iter()
+        .map(|(name, data)| ExportFilePayload { name, data })
+        .collect();
+    write_export_files_to_directory(&app, &dir, files, policy, backups.generations())
 }
 
+/// Body and `files` header: see `export_files_from_request`. Headers:
+/// `policy` (optional).
 #[tauri::command]
-async fn write_export_files_with_dialog(app: tauri::AppHandle, files: Vec<ExportFilePayload>) -> Result<bool, String> {
+async fn write_export_files_with_dialog(
+    app: tauri::AppHandle,
+    request: tauri::ipc::Request<'_>,
+) -> Result<bool, String> {
+    let files = export_files_from_request(&request)?;
+    let policy = export::ExportPolicy::parse(ipc::header(&request, "policy")?.as_deref())?;
+    let generations = app.state::<BackupConfig>().generations();
+    let dialog_app = app.clone();
     let folder = tauri::async_runtime::spawn_blocking(move || {
-        app.dialog()
+        dialog_app.dialog()
             .file()
             .set_title("Choose Export Folder")
             .blocking_pick_folder()
@@ -674,16 +1451,22 @@ async fn write_export_files_with_dialog(app: tauri::AppHandle, files: Vec<Export
         return Ok(false);
     };
     let dir = resolve_dialog_file_path(folder)?;
-    write_export_files_to_directory(&dir, files)?;
+    grants::grant(&app, &dir, GrantScope::Directory, GrantSource::Dialog);
+    write_export_files_to_directory(&app, &dir, files, policy, generations)?;
     Ok(true)
 }
 
+/// Body and `files` header: see `export_files_from_request`. Headers:
+/// `suggested-name` and `policy` (both optional).
 #[tauri::command]
 async fn write_export_files_with_save_dialog(
     app: tauri::AppHandle,
-    files: Vec<ExportFilePayload>,
-    suggested_name: Option<String>,
+    request: tauri::ipc::Request<'_>,
 ) -> Result<bool, String> {
+    let files = export_files_from_request(&request)?;
+    let suggested_name = ipc::header(&request, "suggested-name")?;
+    let policy = export::ExportPolicy::parse(ipc::header(&request, "policy")?.as_deref())?;
+    let generations = app.state::<BackupConfig>().generations();
     let mut dialog = app.dialog().file().set_title("Save Export Location");
     if let Some(name) = suggested_name {
         let trimmed = name.trim();
@@ -707,17 +1490,29 @@ async fn write_export_files_with_save_dialog(
             .map(Path::to_path_buf)
             .ok_or_else(|| "selected path has no parent directory".to_string())?
     };
-    write_export_files_to_directory(&dir, files)?;
+    // The dialog named one file, so only the files written beside it are
+    // granted, not the whole folder.
+    let report = write_export_files_to_directory(&app, &dir, files, policy, generations)?;
+    for file in &report.files {
+        grants::grant(&app, Path::new(&file.path), GrantScope::File, GrantSource::Dialog);
+    }
     Ok(true)
 }
 
+/// Body: the file bytes. Headers: `name`, `suggested-name` and
+/// `default-directory` (both optional).
 #[tauri::command]
 async fn write_export_file_with_save_dialog(
     app: tauri::AppHandle,
-    file: ExportFilePayload,
-    suggested_name: Option<String>,
-    default_directory: Option<String>,
+    request: tauri::ipc::Request<'_>,
 ) -> Result<Option<String>, String> {
+    let file = ExportFilePayload {
+        name: ipc::required_header(&request, "name")?,
+        data: ipc::raw_body(&request)?.to_vec(),
+    };
+    let suggested_name = ipc::header(&request, "suggested-name")?;
+    let default_directory = ipc::header(&request, "default-directory")?;
+    let generations = app.state::<BackupConfig>().generations();
     let mut dialog = app.dialog().file().set_title("Save Export File");
 
     if let Some(dir) = default_directory {
@@ -770,7 +1565,12 @@ async fn write_export_file_with_save_dialog(
         return Err("file extension not allowed".into());
     }
 
-    std::fs::write(&save_path, file.data).map_err(|e| format!("write failed: {}", e))?;
+    validate_write_payload(&save_path, &file.data)?;
+    // Only the saved file is granted; the returned folder is just where the
+    // next export dialog opens.
+    grants::grant(&app, &save_path, GrantScope::File, GrantSource::Dialog);
+    watcher::note_own_write(&app, &save_path);
+    atomic_save::save_with_backups(&save_path, &file.data, generations)?;
     Ok(Some(normalize_device_path(&parent.to_string_lossy())))
 }
 
@@ -820,8 +1620,9 @@ fn toggle_current_window_fullscreen(window: tauri::Window) -> Result<bool, Strin
 
 #[tauri::command]
 async fn pick_export_folder(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    let dialog_app = app.clone();
     let folder = tauri::async_runtime::spawn_blocking(move || {
-        app.dialog()
+        dialog_app.dialog()
             .file()
             .set_title("Choose Export Folder")
             .blocking_pick_folder()
@@ -832,6 +1633,7 @@ async fn pick_export_folder(app: tauri::AppHandle) -> Result<Option<String>, Str
         return Ok(None);
     };
     let path = resolve_dialog_file_path(folder)?;
+    grants::grant(&app, &path, GrantScope::Directory, GrantSource::Dialog);
     Ok(Some(normalize_device_path(&path.to_string_lossy())))
 }
 
@@ -902,11 +1704,19 @@ fn spawn_additional_window(
 pub fn run() {
     tauri::Builder::default()
         .manage(PendingFiles(Mutex::new(HashMap::new())))
+        .manage(BackupConfig(Mutex::new(BackupSettings::default())))
+        .manage(autosave::AutosaveStore(Mutex::new(None)))
+        .manage(watcher::FileWatcher::default())
+        .manage(FileGrants::default())
+        .manage(png_chunks::KeptPngChunks::default())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
-            let paths = collect_image_paths(argv.into_iter());
+            let paths = collect_image_paths(argv);
+            for path in &paths {
+                grants::grant(app, Path::new(path), GrantScope::File, GrantSource::Launch);
+            }
             let app_handle = app.clone();
             tauri::async_runtime::spawn(async move {
                 let saved_window_state = read_saved_window_state(&app_handle);
@@ -933,7 +1743,37 @@ pub fn run() {
             updater_download_and_install,
             get_pending_file,
             read_image_file,
+            read_ora_document,
+            parse_ora_document,
+            decode_indexed_png,
+            encode_indexed_png,
+            read_image_metadata,
+            keep_png_chunks,
+            decode_indexed_bmp,
+            encode_indexed_bmp,
+            decode_gif_frames,
+            encode_indexed_gif,
+            read_aseprite_document,
+            export_gba_tiles,
+            read_gba_tiles,
+            generate_tilemap,
+            slice_oam_sprite,
+            export_gba_source,
             write_allowed_file,
+            get_backup_settings,
+            set_backup_settings,
+            list_file_backups,
+            restore_file_backup,
+            autosave::write_autosave,
+            autosave::clear_autosave,
+            autosave::list_recoverable_documents,
+            autosave::restore_document,
+            autosave::discard_recoverable_document,
+            watcher::watch_document,
+            watcher::unwatch_document,
+            watcher::watch_project,
+            watcher::unwatch_project,
+            preview_export_files,
             write_export_files,
             write_export_files_with_dialog,
             write_export_files_with_save_dialog,
@@ -941,16 +1781,54 @@ pub fn run() {
             show_current_window,
             toggle_current_window_fullscreen,
             pick_export_folder,
+            grants::show_file_dialog,
+            grants::hook_project_folder,
+            grants::list_file_grants,
+            grants::revoke_file_grant,
             scan_project,
-            read_text_file
+            index_decomp_project,
+            validate_project_palettes,
+            read_text_file,
+            read_palette_file,
+            write_palette_file,
+            encode_palette,
+            preview_palette_reorder,
+            apply_palette_reorder
         ])
+        .on_window_event(|window, event| {
+            // A window that goes away cleanly leaves nothing to recover.
+            if let tauri::WindowEvent::Destroyed = event {
+                if let Some(root) = autosave::store_root(window.app_handle()) {
+                    let _ = autosave::clear_slot(&root, window.label());
+                }
+                watcher::forget_window(window.app_handle(), window.label());
+                window.state::<png_chunks::KeptPngChunks>().forget_window(window.label());
+            }
+        })
         .setup(|app| {
             if let Some(main_window) = app.get_webview_window("main") {
                 enforce_min_window_size(&main_window);
                 install_window_state_persistence(&app.handle().clone(), &main_window);
             }
+            grants::load(app.handle());
+            if let Some(settings) = read_backup_settings(app.handle()) {
+                if let Ok(mut guard) = app.state::<BackupConfig>().0.lock() {
+                    *guard = settings;
+                }
+            }
+            if let Some(root) = autosave::store_root(app.handle()) {
+                // Runs before any window can write a snapshot, so every slot
+                // still on disk was left behind by a crashed session.
+                let _ = autosave::adopt_orphaned_slots(&root);
+                if let Ok(mut guard) = app.state::<autosave::AutosaveStore>().0.lock() {
+                    *guard = Some(root);
+                }
+            }
+            // Without a watcher the app still works; watch_* commands report it.
+            let _ = watcher::init(app.handle());
             let args: Vec<String> = std::env::args().collect();
             if let Some(path) = first_file_path(args.into_iter().skip(1)) {
+                grants::grant(app.handle(), Path::new(&path), GrantScope::File, GrantSource::Launch);
                 // Queue the startup file immediately so the frontend can pull it
                 // on first boot without waiting for an artificial delay.
                 if let Ok(mut guard) = app.state::<PendingFiles>().0.lock() {
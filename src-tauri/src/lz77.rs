@@ -0,0 +1,137 @@
+//! GBA BIOS LZ77 (type 0x10), the `.lz` in pokeemerald's `.4bpp.lz`.
+//!
+//! A 4-byte header (0x10, then the decompressed size as 24-bit little
+//! endian) is followed by groups of a flag byte and eight items; a set flag
+//! bit (most significant first) marks a 2-byte back-reference of 3 to 18
+//! bytes up to 4096 back, a clear one a literal byte. The encoder follows
+//! gbagfx's greedy search so its output is byte-identical.
+pub const TYPE_LZ77: u8 = 0x10;
+
+/// Closest back-reference the encoder uses. gbagfx keeps 2 by default so
+/// the data also decompresses with `LZ77UnCompVram`, which writes 16 bits
+/// at a time.
+const MIN_DISTANCE: usize = 2;
+const MAX_DISTANCE: usize = 0x1000;
+const MAX_BLOCK: usize = 18;
+const MAX_SIZE: usize = 0xFF_FFFF;
+
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    bytes.len() >= 4 && bytes[0] == TYPE_LZ77
+}
+
+pub fn compress(src: &[u8]) -> Result<Vec<u8>, String> {
+    if src.is_empty() {
+        return Err("nothing to compress".into());
+    }
+    if src.len() > MAX_SIZE {
+        return Err("data is too large for LZ77 (16 MiB limit)".into());
+    }
+    let mut out = Vec::with_capacity(4 + src.len() + src.len().div_ceil(8));
+    out.push(TYPE_LZ77);
+    out.extend_from_slice(&(src.len() as u32).to_le_bytes()[..3]);
+    let mut pos = 0;
+    loop {
+        let flags_at = out.len();
+        out.push(0);
+        for bit in 0..8 {
+            let (mut best_distance, mut best_size) = (0, 0);
+            let mut distance = MIN_DISTANCE;
+            while distance <= pos && distance <= MAX_DISTANCE {
+                let start = pos - distance;
+                let mut size = 0;
+                while size < MAX_BLOCK && pos + size < src.len() && src[start + size] == src[pos + size] {
+                    size += 1;
+                }
+                if size > best_size {
+                    (best_distance, best_size) = (distance, size);
+                    if size == MAX_BLOCK {
+                        break;
+                    }
+                }
+                distance += 1;
+            }
+            if best_size >= 3 {
+                out[flags_at] |= 0x80 >> bit;
+                pos += best_size;
+                let (size, distance) = (best_size - 3, best_distance - 1);
+                out.push((size << 4) as u8 | (distance >> 8) as u8);
+                out.push(distance as u8);
+            } else {
+                out.push(src[pos]);
+                pos += 1;
+            }
+            if pos == src.len() {
+                out.resize(out.len().next_multiple_of(4), 0);
+                return Ok(out);
+            }
+        }
+    }
+}
+
+pub fn decompress(src: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_compressed(src) {
+        return Err("not LZ77-compressed data".into());
+    }
+    let size = u32::from_le_bytes([src[1], src[2], src[3], 0]) as usize;
+    let mut out = Vec::with_capacity(size);
+    let mut pos = 4;
+    while out.len() < size {
+        let flags = *src.get(pos).ok_or("LZ77 data is truncated")?;
+        pos += 1;
+        for bit in 0..8 {
+            if flags & (0x80 >> bit) != 0 {
+                let Some(&[hi, lo]) = src.get(pos..pos + 2) else {
+                    return Err("LZ77 data is truncated".into());
+                };
+                pos += 2;
+                let block = (hi >> 4) as usize + 3;
+                let distance = ((hi as usize & 0xF) << 8 | lo as usize) + 1;
+                let start = out
+                    .len()
+                    .checked_sub(distance)
+                    .ok_or("LZ77 back-reference points before the start")?;
+                // Some shipped data overruns its stated size; like gbagfx,
+                // stop at the size instead of failing.
+                for i in 0..block.min(size - out.len()) {
+                    out.push(out[start + i]);
+                }
+            } else {
+                out.push(*src.get(pos).ok_or("LZ77 data is truncated")?);
+                pos += 1;
+            }
+            if out.len() == size {
+                break;
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compresses_like_gbagfx_and_round_trips() {
+        // Literals, then a run copied from distance 2 (distance 1 is never
+        // used), padded to a multiple of four.
+        let out = compress(&[1, 2, 1, 2, 1, 2, 1, 2, 3]).unwrap();
+        assert_eq!(out, [0x10, 9, 0, 0, 0x20, 1, 2, 0x30, 0x01, 3, 0, 0]);
+        assert_eq!(decompress(&out).unwrap(), [1, 2, 1, 2, 1, 2, 1, 2, 3]);
+
+        let data: Vec<u8> = (0..5000u32).map(|i| (i * 7 % 13) as u8 ^ (i / 300) as u8).collect();
+        let packed = compress(&data).unwrap();
+        assert_eq!(packed.len() % 4, 0);
+        assert!(packed.len() < data.len());
+        assert_eq!(decompress(&packed).unwrap(), data);
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert!(decompress(&[0x10, 4, 0, 0, 0x80, 0x00, 0x05]).is_err());
+        assert!(decompress(&[0x10, 4, 0, 0, 0x00, 1, 2]).is_err());
+        assert!(decompress(&[0x11, 4, 0, 0]).is_err());
+        // Overlong copies stop at the stated size.
+        assert_eq!(decompress(&[0x10, 4, 0, 0, 0x40, 9, 0xF0, 0x00]).unwrap(), [9; 4]);
+    }
+}
@@ -0,0 +1,203 @@
+//! Sprite art cut into GBA OBJ pieces.
+//!
+//! The opaque 8x8 tiles of an indexed image are covered with the twelve
+//! legal OBJ sizes, one piece at a time: the first uncovered opaque tile in
+//! reading order anchors the next piece, and of the shapes (and left shifts)
+//! that contain it the one covering the most opaque tiles for the fewest
+//! transparent ones wins. Each opaque tile belongs to exactly one piece, so
+//! overlapping pieces never draw a tile twice. Index 0 is transparent.
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+use crate::gba_tiles;
+
+/// Suffix of the text file listing the pieces' offsets and attributes,
+/// written beside their tiles.
+pub const LAYOUT_SUFFIX: &str = "_oam.txt";
+
+/// Width and height in pixels for each (shape, size) pair, as the
+/// shape bits of attribute 0 and the size bits of attribute 1 select them.
+const OBJ_SIZES: [[(usize, usize); 4]; 3] = [
+    [(8, 8), (16, 16), (32, 32), (64, 64)],
+    [(16, 8), (32, 8), (32, 16), (64, 32)],
+    [(8, 16), (8, 32), (16, 32), (32, 64)],
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OamOptions {
+    pub width: u32,
+    pub height: u32,
+    /// 4 or 8.
+    pub bit_depth: u8,
+    /// Point piece offsets are measured from, e.g. the sprite's center.
+    #[serde(default)]
+    pub origin_x: i32,
+    #[serde(default)]
+    pub origin_y: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OamPiece {
+    /// Top-left corner relative to the origin.
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// 0 square, 1 wide, 2 tall.
+    pub shape: u8,
+    pub size: u8,
+    /// `shape << 14`, to OR into attribute 0.
+    pub attr0: u16,
+    /// `size << 14`, to OR into attribute 1.
+    pub attr1: u16,
+    /// Where this piece's tiles start in the framed tile data.
+    pub tiles_offset: usize,
+    pub tiles_len: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OamInfo {
+    pub pieces: Vec<OamPiece>,
+    /// Tiles across all pieces, as they take up VRAM.
+    pub tile_count: usize,
+    pub opaque_tiles: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct OamSlices {
+    pub info: OamInfo,
+    /// Each piece's tiles in turn, laid out for 1D OBJ mapping.
+    pub tiles: Vec<u8>,
+}
+
+/// How well a candidate piece fits; larger is better.
+type Fit = (i64, usize, Reverse<usize>);
+
+pub fn slice(opts: &OamOptions, indices: &[u8]) -> Result<OamSlices, String> {
+    let (w, h) = (opts.width as usize, opts.height as usize);
+    if !matches!(opts.bit_depth, 4 | 8) {
+        return Err(format!("GBA tiles are 4 or 8 bits per pixel, not {}", opts.bit_depth));
+    }
+    if w == 0 || h == 0 {
+        return Err("image is empty".into());
+    }
+    if indices.len() != w * h {
+        return Err("index buffer does not match the image size".into());
+    }
+    // Partial tiles at the right and bottom edges count as padded with 0.
+    let (cols, rows) = (w.div_ceil(8), h.div_ceil(8));
+    let mut open = vec![false; cols * rows];
+    for (i, &v) in indices.iter().enumerate() {
+        if v != 0 {
+            open[i / w / 8 * cols + i % w / 8] = true;
+        }
+    }
+    let opaque_tiles = open.iter().filter(|&&o| o).count();
+
+    let mut pieces = Vec::new();
+    let mut tiles = Vec::new();
+    while let Some(first) = open.iter().position(|&o| o) {
+        let (tx, ty) = (first % cols, first / cols);
+        // Rows above are all covered, so pieces start on this row; shifting
+        // left can take in opaque tiles further down.
+        let mut best: Option<(Fit, usize, usize, usize)> = None;
+        for (shape, sizes) in OBJ_SIZES.iter().enumerate() {
+            for (size, &(pw, ph)) in sizes.iter().enumerate() {
+                let (tw, th) = (pw / 8, ph / 8);
+                for left in tx.saturating_sub(tw - 1)..=tx {
+                    let covered = (ty..ty + th)
+                        .flat_map(|y| (left..left + tw).map(move |x| (x, y)))
+                        .filter(|&(x, y)| x < cols && y < rows && open[y * cols + x])
+                        .count();
+                    // Opaque tiles gained against transparent ones drawn,
+                    // then fewer pieces, then less VRAM.
+                    let key = (2 * covered as i64 - (tw * th) as i64, covered, Reverse(tw * th));
+                    if best.as_ref().is_none_or(|(k, ..)| key > *k) {
+                        best = Some((key, shape, size, left));
+                    }
+                }
+            }
+        }
+        let (_, shape, size, left) = best.expect("8x8 always fits");
+        let (pw, ph) = OBJ_SIZES[shape][size];
+
+        let mut piece = vec![0u8; pw * ph];
+        for y in ty..(ty + ph / 8).min(rows) {
+            for x in left..(left + pw / 8).min(cols) {
+                if !std::mem::take(&mut open[y * cols + x]) {
+                    continue;
+                }
+                for py in y * 8..(y * 8 + 8).min(h) {
+                    for px in x * 8..(x * 8 + 8).min(w) {
+                        piece[(py - ty * 8) * pw + px - left * 8] = indices[py * w + px];
+                    }
+                }
+            }
+        }
+        let data = gba_tiles::encode_tiles(pw, ph, opts.bit_depth, &piece)?;
+        pieces.push(OamPiece {
+            x: (left * 8) as i32 - opts.origin_x,
+            y: (ty * 8) as i32 - opts.origin_y,
+            width: pw as u32,
+            height: ph as u32,
+            shape: shape as u8,
+            size: size as u8,
+            attr0: (shape as u16) << 14,
+            attr1: (size as u16) << 14,
+            tiles_offset: tiles.len(),
+            tiles_len: data.len(),
+        });
+        tiles.extend(data);
+    }
+
+    let tile_count = tiles.len() / (8 * opts.bit_depth as usize);
+    Ok(OamSlices {
+        info: OamInfo {
+            pieces,
+            tile_count,
+            opaque_tiles,
+        },
+        tiles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(width: u32, height: u32) -> OamOptions {
+        OamOptions {
+            width,
+            height,
+            bit_depth: 4,
+            origin_x: 0,
+            origin_y: 0,
+        }
+    }
+
+    #[test]
+    fn covers_opaque_tiles_with_legal_shapes() {
+        // A solid 16x24 block fits one 16x32 (tall, size 2) piece.
+        let out = slice(&opts(16, 24), &[1; 16 * 24]).unwrap();
+        assert_eq!(out.info.pieces.len(), 1);
+        let p = &out.info.pieces[0];
+        assert_eq!((p.width, p.height, p.shape, p.size), (16, 32, 2, 2));
+        assert_eq!((p.attr0, p.attr1), (0x8000, 0x8000));
+        assert_eq!(out.tiles.len(), 8 * 32);
+        assert!(out.tiles[6 * 32..].iter().all(|&b| b == 0));
+
+        // Two specks far apart get an 8x8 each instead of one big piece;
+        // the empty space between them costs nothing.
+        let mut indices = vec![0u8; 64 * 64];
+        indices[0] = 3;
+        indices[60 * 64 + 60] = 5;
+        let mut o = opts(64, 64);
+        (o.origin_x, o.origin_y) = (32, 32);
+        let out = slice(&o, &indices).unwrap();
+        let placed: Vec<_> = out.info.pieces.iter().map(|p| (p.x, p.y, p.width, p.height)).collect();
+        assert_eq!(placed, [(-32, -32, 8, 8), (24, 24, 8, 8)]);
+        assert_eq!(out.info.tile_count, 2);
+        assert_eq!(out.tiles[0], 3);
+        assert_eq!(out.tiles[32 + 4 * 4 + 2], 0x05);
+    }
+}
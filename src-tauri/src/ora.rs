@@ -0,0 +1,577 @@
+//! OpenRaster (.ora) archive reading and checks.
+//!
+//! Krita and GIMP identify an ORA by a `mimetype` entry that is the first,
+//! uncompressed member of the zip, and refuse files whose `stack.xml` is
+//! broken or points at missing layers. `validate_ora` rejects those before
+//! anything is written to disk; `read_ora_document` parses the layer stack
+//! for the frontend so it never has to unzip in JavaScript.
+use crate::zip_archive::{ZipArchive, ZipLimits, METHOD_STORED};
+use quick_xml::events::{BytesStart, Event};
+use serde::Serialize;
+
+pub const ORA_MIMETYPE: &[u8] = b"image/openraster";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// stack.xml is a short manifest; anything bigger is not a real ORA.
+const MAX_STACK_XML_SIZE: u64 = 16 * 1024 * 1024;
+/// Largest single layer PNG accepted from an archive.
+const MAX_LAYER_SIZE: u64 = 512 * 1024 * 1024;
+/// Nested `<stack>` depth accepted before the file is considered hostile.
+const MAX_STACK_DEPTH: usize = 64;
+
+const ORA_LIMITS: ZipLimits = ZipLimits {
+    max_entries: 4096,
+    max_total_size: 2 * 1024 * 1024 * 1024,
+    // Layer PNGs are already deflated, so a real ORA compresses barely at all.
+    max_ratio: 200,
+};
+
+/// A paint layer, with its PNG bytes pulled out of the archive.
+#[derive(Debug, Clone, Serialize)]
+pub struct OraLayer {
+    pub name: String,
+    pub src: String,
+    pub x: i32,
+    pub y: i32,
+    pub opacity: f32,
+    pub visible: bool,
+    pub composite_op: String,
+    /// CDPaint's own `paint:*` attributes; other writers leave them unset.
+    pub locked: bool,
+    pub alpha_lock: bool,
+    pub is_base: bool,
+    /// Location of the PNG in a framed IPC response (see `take_layer_pngs`).
+    pub png_offset: usize,
+    pub png_len: usize,
+    #[serde(skip)]
+    pub png: Vec<u8>,
+}
+
+/// A nested `<stack>`. Children are ordered bottom to top.
+#[derive(Debug, Clone, Serialize)]
+pub struct OraGroup {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub opacity: f32,
+    pub visible: bool,
+    pub composite_op: String,
+    pub isolated: bool,
+    pub children: Vec<OraNode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum OraNode {
+    Layer(OraLayer),
+    Group(OraGroup),
+}
+
+/// Parsed ORA. `layers` is ordered bottom to top, the order the layer
+/// manager keeps (stack.xml lists them top first).
+#[derive(Debug, Clone, Serialize)]
+pub struct OraDocument {
+    pub width: u32,
+    pub height: u32,
+    pub active_layer: Option<usize>,
+    pub layers: Vec<OraNode>,
+}
+
+#[derive(Debug, Default)]
+struct StackXml {
+    width: u32,
+    height: u32,
+    active_layer: Option<usize>,
+    /// Children of the root `<stack>`, in XML (top to bottom) order.
+    root: Vec<NodeDef>,
+    saw_stack: bool,
+}
+
+#[derive(Debug)]
+enum NodeDef {
+    Layer(OraLayer),
+    Group(OraGroup, Vec<NodeDef>),
+}
+
+/// An element still open while parsing: its name, plus the group being
+/// filled when it is a `<stack>`.
+type OpenElement = (Vec<u8>, Option<(OraGroup, Vec<NodeDef>)>);
+
+fn check_mimetype(zip: &ZipArchive<'_>) -> Result<(), String> {
+    let first = zip
+        .first_local_entry()
+        .ok_or_else(|| "ORA archive is empty".to_string())?;
+    if first.name != "mimetype" {
+        return Err("ORA mimetype entry must be the first file in the archive".into());
+    }
+    if first.method != METHOD_STORED {
+        return Err("ORA mimetype entry must be stored uncompressed".into());
+    }
+    if first.local_extra_len != 0 {
+        // Readers sniff the mimetype at a fixed offset (byte 38).
+        return Err("ORA mimetype entry must not have an extra field".into());
+    }
+    if zip.raw_data(first) != ORA_MIMETYPE {
+        return Err("ORA mimetype must be \"image/openraster\"".into());
+    }
+    Ok(())
+}
+
+fn xml_error(e: impl std::fmt::Display) -> String {
+    format!("stack.xml is not well-formed: {}", e)
+}
+
+/// Attributes of one element, keyed by qualified name (`paint:locked` stays
+/// prefixed so it can't collide with a standard attribute).
+fn element_attrs(e: &BytesStart<'_>) -> Result<Vec<(String, String)>, String> {
+    let mut out = Vec::new();
+    for attr in e.attributes() {
+        let attr = attr.map_err(xml_error)?;
+        let key = String::from_utf8_lossy(attr.key.as_ref()).into_owned();
+        let value = attr
+            .normalized_value(quick_xml::XmlVersion::Implicit1_0)
+            .map_err(xml_error)?
+            .into_owned();
+        out.push((key, value));
+    }
+    Ok(out)
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+fn attr_i32(attrs: &[(String, String)], key: &str) -> i32 {
+    attr(attrs, key)
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|v| v.is_finite())
+        .map(|v| v.round().clamp(i32::MIN as f64, i32::MAX as f64) as i32)
+        .unwrap_or(0)
+}
+
+fn attr_opacity(attrs: &[(String, String)]) -> f32 {
+    attr(attrs, "opacity")
+        .and_then(|v| v.trim().parse::<f32>().ok())
+        .filter(|v| v.is_finite())
+        .map(|v| v.clamp(0.0, 1.0))
+        .unwrap_or(1.0)
+}
+
+fn attr_flag(attrs: &[(String, String)], key: &str) -> bool {
+    attr(attrs, key) == Some("true")
+}
+
+fn layer_from_attrs(attrs: &[(String, String)]) -> Result<OraLayer, String> {
+    let src = attr(attrs, "src")
+        .ok_or_else(|| "stack.xml <layer> is missing src".to_string())?
+        .to_string();
+    Ok(OraLayer {
+        name: attr(attrs, "name").unwrap_or("Layer").to_string(),
+        src,
+        x: attr_i32(attrs, "x"),
+        y: attr_i32(attrs, "y"),
+        opacity: attr_opacity(attrs),
+        visible: attr(attrs, "visibility") != Some("hidden"),
+        composite_op: attr(attrs, "composite-op").unwrap_or("svg:src-over").to_string(),
+        locked: attr_flag(attrs, "paint:locked"),
+        alpha_lock: attr_flag(attrs, "paint:alphaLock"),
+        is_base: attr_flag(attrs, "paint:isBase"),
+        png_offset: 0,
+        png_len: 0,
+        png: Vec::new(),
+    })
+}
+
+fn group_from_attrs(attrs: &[(String, String)]) -> OraGroup {
+    OraGroup {
+        name: attr(attrs, "name").unwrap_or("Group").to_string(),
+        x: attr_i32(attrs, "x"),
+        y: attr_i32(attrs, "y"),
+        opacity: attr_opacity(attrs),
+        visible: attr(attrs, "visibility") != Some("hidden"),
+        composite_op: attr(attrs, "composite-op").unwrap_or("svg:src-over").to_string(),
+        isolated: attr(attrs, "isolation") == Some("isolate"),
+        children: Vec::new(),
+    }
+}
+
+/// Parse stack.xml, requiring it to be well-formed with an `<image>` root
+/// containing a `<stack>`.
+fn parse_stack_xml(xml: &[u8]) -> Result<StackXml, String> {
+    let text = std::str::from_utf8(xml).map_err(|_| "stack.xml is not valid UTF-8".to_string())?;
+    let mut reader = quick_xml::Reader::from_str(text);
+    let mut out = StackXml::default();
+    let mut open: Vec<OpenElement> = Vec::new();
+    let mut saw_root = false;
+    loop {
+        let event = reader.read_event().map_err(xml_error)?;
+        let (e, empty) = match &event {
+            Event::Start(e) => (e, false),
+            Event::Empty(e) => (e, true),
+            Event::End(_) => {
+                let (name, group) = open.pop().ok_or_else(|| xml_error("unbalanced end tag"))?;
+                if name == b"stack" {
+                    let (group, children) = group.ok_or_else(|| xml_error("unbalanced stack"))?;
+                    attach_stack(&mut out, &mut open, group, children);
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        let name = e.local_name().as_ref().to_vec();
+        let attrs = element_attrs(e)?;
+        if open.is_empty() {
+            if saw_root || name != b"image" {
+                return Err("stack.xml root element must be a single <image>".into());
+            }
+            saw_root = true;
+            out.width = attr(&attrs, "w").and_then(|v| v.trim().parse().ok()).unwrap_or(0);
+            out.height = attr(&attrs, "h").and_then(|v| v.trim().parse().ok()).unwrap_or(0);
+            out.active_layer = attr(&attrs, "paint:activeLayer").and_then(|v| v.trim().parse().ok());
+        }
+        let in_stack = open.last().is_some_and(|(_, g)| g.is_some());
+        match name.as_slice() {
+            b"stack" => {
+                if open.len() > MAX_STACK_DEPTH {
+                    return Err("stack.xml nests stacks too deeply".into());
+                }
+                let group = group_from_attrs(&attrs);
+                if empty {
+                    attach_stack(&mut out, &mut open, group, Vec::new());
+                } else {
+                    open.push((name, Some((group, Vec::new()))));
+                }
+                continue;
+            }
+            b"layer" if in_stack => {
+                let layer = layer_from_attrs(&attrs)?;
+                if let Some((_, Some((_, children)))) = open.last_mut() {
+                    children.push(NodeDef::Layer(layer));
+                }
+            }
+            _ => {}
+        }
+        if !empty {
+            open.push((name, None));
+        }
+    }
+    if !open.is_empty() {
+        return Err(xml_error("unclosed element"));
+    }
+    if !saw_root {
+        return Err("stack.xml has no <image> element".into());
+    }
+    if !out.saw_stack {
+        return Err("stack.xml has no <stack> element".into());
+    }
+    Ok(out)
+}
+
+/// A closed `<stack>` becomes a group in its parent stack, or the document
+/// root when its parent is `<image>`.
+fn attach_stack(
+    out: &mut StackXml,
+    open: &mut [OpenElement],
+    group: OraGroup,
+    children: Vec<NodeDef>,
+) {
+    match open.last_mut() {
+        Some((_, Some((_, parent)))) => parent.push(NodeDef::Group(group, children)),
+        _ => {
+            if !out.saw_stack {
+                out.saw_stack = true;
+                out.root = children;
+            }
+        }
+    }
+}
+
+fn collect_sources<'a>(nodes: &'a [NodeDef], out: &mut Vec<&'a str>) {
+    for node in nodes {
+        match node {
+            NodeDef::Layer(l) => out.push(&l.src),
+            NodeDef::Group(_, children) => collect_sources(children, out),
+        }
+    }
+}
+
+fn read_layer_png(zip: &ZipArchive<'_>, src: &str) -> Result<Vec<u8>, String> {
+    let entry = zip
+        .find(src)
+        .ok_or_else(|| format!("ORA layer is missing from the archive: {}", src))?;
+    let data = zip.read(entry, MAX_LAYER_SIZE)?;
+    if !data.starts_with(PNG_SIGNATURE) {
+        return Err(format!("ORA layer is not a PNG: {}", src));
+    }
+    Ok(data)
+}
+
+fn open_archive(bytes: &[u8]) -> Result<(ZipArchive<'_>, StackXml), String> {
+    let zip = ZipArchive::parse(bytes).map_err(|e| format!("invalid ORA: {}", e))?;
+    zip.enforce_limits(&ORA_LIMITS)?;
+    check_mimetype(&zip)?;
+    let stack = zip
+        .find("stack.xml")
+        .ok_or_else(|| "ORA is missing stack.xml".to_string())?;
+    let xml = zip.read(stack, MAX_STACK_XML_SIZE)?;
+    let parsed = parse_stack_xml(&xml)?;
+    Ok((zip, parsed))
+}
+
+/// Check an ORA archive before it is written: mimetype first and stored,
+/// stack.xml well-formed, and every referenced layer present as a PNG.
+pub fn validate_ora(bytes: &[u8]) -> Result<(), String> {
+    let (zip, parsed) = open_archive(bytes)?;
+    let mut sources = Vec::new();
+    collect_sources(&parsed.root, &mut sources);
+    for src in sources {
+        read_layer_png(&zip, src)?;
+    }
+    Ok(())
+}
+
+/// Turn XML-ordered definitions into bottom-to-top nodes, loading each
+/// layer's PNG. With `flatten`, groups dissolve into their layers: offsets
+/// add up, opacity multiplies and a hidden group hides everything in it.
+fn build_nodes(
+    zip: &ZipArchive<'_>,
+    defs: Vec<NodeDef>,
+    flatten: bool,
+    inherited: (i32, i32, f32, bool),
+    out: &mut Vec<OraNode>,
+) -> Result<(), String> {
+    let (dx, dy, opacity, visible) = inherited;
+    for def in defs.into_iter().rev() {
+        match def {
+            NodeDef::Layer(mut layer) => {
+                layer.png = read_layer_png(zip, &layer.src)?;
+                if flatten {
+                    layer.x = layer.x.saturating_add(dx);
+                    layer.y = layer.y.saturating_add(dy);
+                    layer.opacity *= opacity;
+                    layer.visible &= visible;
+                }
+                out.push(OraNode::Layer(layer));
+            }
+            NodeDef::Group(mut group, children) => {
+                if flatten {
+                    let inner = (
+                        dx.saturating_add(group.x),
+                        dy.saturating_add(group.y),
+                        opacity * group.opacity,
+                        visible && group.visible,
+                    );
+                    build_nodes(zip, children, true, inner, out)?;
+                } else {
+                    build_nodes(zip, children, false, (0, 0, 1.0, true), &mut group.children)?;
+                    out.push(OraNode::Group(group));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn read_ora_document(bytes: &[u8], flatten_groups: bool) -> Result<OraDocument, String> {
+    let (zip, parsed) = open_archive(bytes)?;
+    if parsed.width == 0 || parsed.height == 0 {
+        return Err("ORA has invalid canvas dimensions".into());
+    }
+    let mut layers = Vec::new();
+    build_nodes(&zip, parsed.root, flatten_groups, (0, 0, 1.0, true), &mut layers)?;
+    if layers.is_empty() {
+        return Err("ORA contains no layers".into());
+    }
+    Ok(OraDocument {
+        width: parsed.width,
+        height: parsed.height,
+        active_layer: parsed.active_layer,
+        layers,
+    })
+}
+
+fn take_pngs(nodes: &mut [OraNode], offset: &mut usize, out: &mut Vec<Vec<u8>>) {
+    for node in nodes {
+        match node {
+            OraNode::Layer(layer) => {
+                let png = std::mem::take(&mut layer.png);
+                layer.png_offset = *offset;
+                layer.png_len = png.len();
+                *offset += png.len();
+                out.push(png);
+            }
+            OraNode::Group(group) => take_pngs(&mut group.children, offset, out),
+        }
+    }
+}
+
+/// Move every layer's PNG out of the document, in layer order, recording
+/// where each one lands in the concatenated blob area.
+pub fn take_layer_pngs(doc: &mut OraDocument) -> Vec<Vec<u8>> {
+    let mut out = Vec::new();
+    take_pngs(&mut doc.layers, &mut 0, &mut out);
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Build a zip the way layer-system.js does: local records, central
+    /// directory, EOCD. Entries flagged `store` are kept uncompressed.
+    pub(crate) fn build_zip(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for (name, data, store) in entries {
+            let (method, body) = if *store {
+                (0u16, data.to_vec())
+            } else {
+                let mut enc = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                enc.write_all(data).unwrap();
+                (8u16, enc.finish().unwrap())
+            };
+            let crc = crc32fast::hash(data);
+            let offset = out.len() as u32;
+            out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+            out.extend_from_slice(&[20, 0, 0, 0]);
+            out.extend_from_slice(&method.to_le_bytes());
+            out.extend_from_slice(&[0; 4]);
+            out.extend_from_slice(&crc.to_le_bytes());
+            out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&[0, 0]);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&body);
+
+            central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            central.extend_from_slice(&[30, 3, 20, 0, 0, 0]);
+            central.extend_from_slice(&method.to_le_bytes());
+            central.extend_from_slice(&[0; 4]);
+            central.extend_from_slice(&crc.to_le_bytes());
+            central.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 12]);
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let cd_offset = out.len() as u32;
+        out.extend_from_slice(&central);
+        out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        out.extend_from_slice(&cd_offset.to_le_bytes());
+        out.extend_from_slice(&[0, 0]);
+        out
+    }
+
+    pub(crate) const STACK_XML: &[u8] = br#"<?xml version="1.0" encoding="UTF-8"?>
+<image version="0.0.3" w="64" h="64">
+    <stack>
+        <layer name="Top &amp; Co" src="data/layer_1.png" x="2" y="3" opacity="0.5000" visibility="hidden" composite-op="svg:multiply"/>
+        <layer name="Background" src="data/layer_0.png" x="0" y="0" opacity="1.0000" visibility="visible" composite-op="svg:src-over"/>
+    </stack>
+</image>"#;
+
+    pub(crate) fn sample_ora(xml: &[u8]) -> Vec<u8> {
+        let png: &[u8] = b"\x89PNG\r\n\x1a\nfake";
+        build_zip(&[
+            ("mimetype", ORA_MIMETYPE, true),
+            ("stack.xml", xml, false),
+            ("data/layer_0.png", png, false),
+            ("data/layer_1.png", png, false),
+        ])
+    }
+
+    fn layer(node: &OraNode) -> &OraLayer {
+        match node {
+            OraNode::Layer(l) => l,
+            OraNode::Group(_) => panic!("expected a layer"),
+        }
+    }
+
+    #[test]
+    fn validate_ora_accepts_well_formed_archive() {
+        validate_ora(&sample_ora(STACK_XML)).unwrap();
+    }
+
+    #[test]
+    fn validate_ora_rejects_broken_archives() {
+        let png: &[u8] = b"\x89PNG\r\n\x1a\n";
+        let compressed_mimetype = build_zip(&[("mimetype", ORA_MIMETYPE, false), ("stack.xml", STACK_XML, false)]);
+        assert!(validate_ora(&compressed_mimetype).unwrap_err().contains("uncompressed"));
+
+        let mimetype_second = build_zip(&[("stack.xml", STACK_XML, false), ("mimetype", ORA_MIMETYPE, true)]);
+        assert!(validate_ora(&mimetype_second).unwrap_err().contains("first"));
+
+        let unclosed = sample_ora(b"<image w=\"1\" h=\"1\"><stack><layer src=\"data/layer_0.png\"/></image>");
+        assert!(validate_ora(&unclosed).unwrap_err().contains("well-formed"));
+
+        let missing_layer = build_zip(&[
+            ("mimetype", ORA_MIMETYPE, true),
+            ("stack.xml", STACK_XML, false),
+            ("data/layer_0.png", png, false),
+        ]);
+        assert!(validate_ora(&missing_layer).unwrap_err().contains("data/layer_1.png"));
+    }
+
+    #[test]
+    fn read_ora_document_orders_layers_bottom_to_top() {
+        let doc = read_ora_document(&sample_ora(STACK_XML), false).unwrap();
+        assert_eq!((doc.width, doc.height), (64, 64));
+        let names: Vec<&str> = doc.layers.iter().map(|n| layer(n).name.as_str()).collect();
+        assert_eq!(names, ["Background", "Top & Co"]);
+        let top = layer(&doc.layers[1]);
+        assert_eq!((top.x, top.y, top.visible), (2, 3, false));
+        assert_eq!(top.composite_op, "svg:multiply");
+        assert!(top.png.starts_with(PNG_SIGNATURE));
+    }
+
+    #[test]
+    fn read_ora_document_flattens_or_keeps_groups() {
+        let xml = br#"<image w="8" h="8"><stack>
+            <stack name="G" x="4" y="1" opacity="0.5" visibility="hidden">
+                <layer name="Inner" src="data/layer_1.png" x="1" y="1" opacity="0.5"/>
+            </stack>
+            <layer name="Bottom" src="data/layer_0.png"/>
+        </stack></image>"#;
+        let bytes = sample_ora(xml);
+
+        let grouped = read_ora_document(&bytes, false).unwrap();
+        match &grouped.layers[1] {
+            OraNode::Group(g) => {
+                assert_eq!(g.name, "G");
+                assert_eq!(layer(&g.children[0]).x, 1);
+            }
+            OraNode::Layer(_) => panic!("expected a group"),
+        }
+
+        let flat = read_ora_document(&bytes, true).unwrap();
+        let inner = layer(&flat.layers[1]);
+        assert_eq!((inner.x, inner.y, inner.visible), (5, 2, false));
+        assert!((inner.opacity - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn read_ora_document_rejects_escaping_paths_and_bombs() {
+        let png: &[u8] = b"\x89PNG\r\n\x1a\n";
+        let escaping = build_zip(&[
+            ("mimetype", ORA_MIMETYPE, true),
+            ("stack.xml", STACK_XML, false),
+            ("../evil.png", png, false),
+        ]);
+        assert!(read_ora_document(&escaping, true).unwrap_err().contains("escapes"));
+
+        let zeros = vec![0u8; 8 * 1024 * 1024];
+        let bomb = build_zip(&[
+            ("mimetype", ORA_MIMETYPE, true),
+            ("stack.xml", STACK_XML, false),
+            ("data/layer_0.png", &zeros, false),
+        ]);
+        assert!(read_ora_document(&bomb, true).unwrap_err().contains("compression ratio"));
+    }
+}
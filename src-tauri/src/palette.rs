@@ -0,0 +1,587 @@
+//! Palette files in the formats other tools use.
+//!
+//! Reads JASC-PAL, GIMP `.gpl`, Adobe `.act` and `.aco`, Paint.NET `.txt`,
+//! plain hex lists and binary `.gbapal` (little-endian BGR555), telling them
+//! apart by content rather than by extension, and writes any of them back.
+//! `reorder` permutes a file's colors without re-encoding it.
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::Path;
+
+/// Larger files are not palettes.
+pub const MAX_PALETTE_FILE_BYTES: u64 = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaletteFormat {
+    Jasc,
+    Gpl,
+    Act,
+    Aco,
+    PaintNet,
+    Hex,
+    GbaPal,
+}
+
+impl PaletteFormat {
+    /// The format written for a file extension.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "pal" => Some(Self::Jasc),
+            "gpl" => Some(Self::Gpl),
+            "act" => Some(Self::Act),
+            "aco" => Some(Self::Aco),
+            "txt" => Some(Self::PaintNet),
+            "hex" => Some(Self::Hex),
+            "gbapal" => Some(Self::GbaPal),
+            _ => None,
+        }
+    }
+}
+
+pub fn is_palette_extension(ext: &str) -> bool {
+    PaletteFormat::from_extension(ext).is_some()
+}
+
+/// Whether the file at `path` is a palette. `.txt` and `.hex` are common
+/// for other files too, so those only count when their content parses.
+pub fn is_palette_file(path: &Path) -> bool {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    if !is_palette_extension(&ext) {
+        return false;
+    }
+    if !matches!(ext.as_str(), "txt" | "hex") {
+        return true;
+    }
+    std::fs::metadata(path).is_ok_and(|m| m.len() <= MAX_PALETTE_FILE_BYTES)
+        && std::fs::read(path).is_ok_and(|bytes| detect(&bytes).is_ok())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PaletteFile {
+    pub format: PaletteFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// RGBA; alpha is 255 unless the format stores transparency.
+    pub colors: Vec<[u8; 4]>,
+    /// The components were 5-bit (a GBA palette written as JASC, or a
+    /// `.gbapal`) and have been scaled up to 8 bits.
+    pub scaled_from_5bit: bool,
+}
+
+/// gbagfx's conversions between 5- and 8-bit components.
+fn upconvert(v: u16) -> u8 {
+    (v as u32 * 255 / 31) as u8
+}
+
+fn downconvert(v: u8) -> u16 {
+    v as u16 / 8
+}
+
+/// The text of a palette file, when it looks like one.
+fn as_text(bytes: &[u8]) -> Option<&str> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    text.chars()
+        .all(|c| !c.is_control() || matches!(c, '\t' | '\r' | '\n'))
+        .then_some(text)
+}
+
+fn is_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn detect_text(text: &str) -> Option<PaletteFormat> {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    let first = lines.next()?;
+    if first.starts_with("JASC-PAL") {
+        return Some(PaletteFormat::Jasc);
+    }
+    if first.starts_with("GIMP Palette") {
+        return Some(PaletteFormat::Gpl);
+    }
+    let data: Vec<&str> = std::iter::once(first).chain(lines).filter(|l| !l.starts_with(';')).collect();
+    if data.is_empty() {
+        return None;
+    }
+    if data.iter().all(|l| is_hex(l, 8)) {
+        return Some(PaletteFormat::PaintNet);
+    }
+    if data.iter().all(|l| is_hex(l.strip_prefix('#').unwrap_or(l), 6)) {
+        return Some(PaletteFormat::Hex);
+    }
+    None
+}
+
+/// Which format `bytes` are in. Text formats are recognized by their header
+/// or line shape; binary ones by their structure and size.
+pub fn detect(bytes: &[u8]) -> Result<PaletteFormat, String> {
+    // Real `.gbapal`s almost always hold a 0x0000 color, so text that matches
+    // no text format is not a binary palette either.
+    if let Some(text) = as_text(bytes) {
+        return detect_text(text).ok_or_else(|| "unrecognized palette file".to_string());
+    }
+    if matches!(bytes, [0, 1 | 2, ..]) && parse_aco(bytes).is_ok() {
+        return Ok(PaletteFormat::Aco);
+    }
+    if bytes.len() == 768 || bytes.len() == 772 {
+        return Ok(PaletteFormat::Act);
+    }
+    if !bytes.is_empty() && bytes.len().is_multiple_of(2) && bytes.len() <= 512 {
+        return Ok(PaletteFormat::GbaPal);
+    }
+    Err("unrecognized palette file".into())
+}
+
+/// Parse a palette file of any supported format.
+pub fn read(bytes: &[u8]) -> Result<PaletteFile, String> {
+    let format = detect(bytes)?;
+    let text = || as_text(bytes).unwrap_or_default();
+    let mut name = None;
+    let mut scaled_from_5bit = false;
+    let colors = match format {
+        PaletteFormat::Jasc => {
+            let (colors, scaled) = parse_jasc(text())?;
+            scaled_from_5bit = scaled;
+            colors
+        }
+        PaletteFormat::Gpl => {
+            let (colors, gpl_name) = parse_gpl(text())?;
+            name = gpl_name;
+            colors
+        }
+        PaletteFormat::Act => parse_act(bytes),
+        PaletteFormat::Aco => parse_aco(bytes)?,
+        PaletteFormat::PaintNet => data_lines(text(), ';')
+            .map(|l| {
+                let v = u32::from_str_radix(l, 16).map_err(|_| format!("invalid Paint.NET color '{}'", l))?;
+                let [a, r, g, b] = v.to_be_bytes();
+                Ok([r, g, b, a])
+            })
+            .collect::<Result<_, String>>()?,
+        PaletteFormat::Hex => data_lines(text(), ';')
+            .map(|l| {
+                let l = l.strip_prefix('#').unwrap_or(l);
+                let v = u32::from_str_radix(l, 16).map_err(|_| format!("invalid hex color '{}'", l))?;
+                let [_, r, g, b] = v.to_be_bytes();
+                Ok([r, g, b, 255])
+            })
+            .collect::<Result<_, String>>()?,
+        PaletteFormat::GbaPal => {
+            scaled_from_5bit = true;
+            bytes
+                .chunks_exact(2)
+                .map(|c| {
+                    let v = u16::from_le_bytes([c[0], c[1]]);
+                    [upconvert(v & 31), upconvert((v >> 5) & 31), upconvert((v >> 10) & 31), 255]
+                })
+                .collect()
+        }
+    };
+    if colors.is_empty() {
+        return Err("palette file has no colors".into());
+    }
+    Ok(PaletteFile {
+        format,
+        name,
+        colors,
+        scaled_from_5bit,
+    })
+}
+
+fn data_lines(text: &str, comment: char) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(|l| l.trim().trim_start_matches('\u{feff}'))
+        .filter(move |l| !l.is_empty() && !l.starts_with(comment))
+}
+
+/// Like `data_lines`, with the byte range in `text` of each whole line
+/// (indentation included, line ending not).
+fn data_line_spans(text: &str, comment: char) -> Vec<(&str, Range<usize>)> {
+    text.lines()
+        .map(|line| {
+            let at = line.as_ptr() as usize - text.as_ptr() as usize;
+            (line.trim().trim_start_matches('\u{feff}'), at..at + line.len())
+        })
+        .filter(|(l, _)| !l.is_empty() && !l.starts_with(comment))
+        .collect()
+}
+
+fn parse_components<'a>(parts: impl Iterator<Item = &'a str>, line: &str) -> Result<Vec<u8>, String> {
+    parts
+        .map(|p| p.parse::<u8>().map_err(|_| format!("invalid palette line '{}'", line)))
+        .collect()
+}
+
+/// JASC-PAL, optionally with a fourth (alpha) column. Files whose components
+/// all fit in 5 bits are GBA palettes written unscaled (as CDPaint writes
+/// project palettes) and are scaled up.
+fn parse_jasc(text: &str) -> Result<(Vec<[u8; 4]>, bool), String> {
+    let mut lines = data_lines(text, '#');
+    if !lines.next().is_some_and(|l| l.starts_with("JASC-PAL")) || lines.next() != Some("0100") {
+        return Err("invalid JASC-PAL header".into());
+    }
+    let count: usize = lines
+        .next()
+        .and_then(|l| l.parse().ok())
+        .ok_or("invalid JASC-PAL color count")?;
+    let mut colors = Vec::with_capacity(count.min(4096));
+    for line in lines.take(count) {
+        let v = parse_components(line.split_whitespace(), line)?;
+        match v[..] {
+            [r, g, b] => colors.push([r, g, b, 255]),
+            [r, g, b, a] => colors.push([r, g, b, a]),
+            _ => return Err(format!("invalid palette line '{}'", line)),
+        }
+    }
+    if colors.len() != count {
+        return Err("JASC-PAL file has fewer colors than its count".into());
+    }
+    let max = colors.iter().flat_map(|c| &c[..3]).copied().max().unwrap_or(0);
+    let scaled = max > 0 && max <= 31;
+    if scaled {
+        for c in &mut colors {
+            for v in &mut c[..3] {
+                *v = upconvert(*v as u16);
+            }
+        }
+    }
+    Ok((colors, scaled))
+}
+
+fn parse_gpl(text: &str) -> Result<(Vec<[u8; 4]>, Option<String>), String> {
+    let mut name = None;
+    let mut colors = Vec::new();
+    for line in data_lines(text, '#').skip(1) {
+        if let Some(n) = line.strip_prefix("Name:") {
+            name = Some(n.trim().to_string()).filter(|n| !n.is_empty());
+        } else if !line.starts_with("Columns:") {
+            let v = parse_components(line.split_whitespace().take(3), line)?;
+            let [r, g, b] = v[..] else {
+                return Err(format!("invalid palette line '{}'", line));
+            };
+            colors.push([r, g, b, 255]);
+        }
+    }
+    Ok((colors, name))
+}
+
+/// 256 RGB triples, optionally followed by the color count and the
+/// transparent index (both big-endian, 0xFFFF for none).
+fn parse_act(bytes: &[u8]) -> Vec<[u8; 4]> {
+    let mut colors: Vec<[u8; 4]> = bytes[..768].chunks_exact(3).map(|c| [c[0], c[1], c[2], 255]).collect();
+    if let [c0, c1, t0, t1] = bytes[768..] {
+        let count = u16::from_be_bytes([c0, c1]) as usize;
+        if (1..=256).contains(&count) {
+            colors.truncate(count);
+        }
+        if let Some(c) = colors.get_mut(u16::from_be_bytes([t0, t1]) as usize) {
+            c[3] = 0;
+        }
+    }
+    colors
+}
+
+fn be16(bytes: &[u8], at: usize) -> Result<u16, String> {
+    bytes
+        .get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| "ACO file is truncated".to_string())
+}
+
+fn hsb_to_rgb(h: f64, s: f64, v: f64) -> [u8; 3] {
+    let sector = (h * 6.0).floor();
+    let f = h * 6.0 - sector;
+    let (p, q, t) = (v * (1.0 - s), v * (1.0 - s * f), v * (1.0 - s * (1.0 - f)));
+    let (r, g, b) = match sector as i32 % 6 {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    };
+    [r, g, b].map(|c| (c * 255.0).round() as u8)
+}
+
+/// Photoshop swatches: a version 1 section, a version 2 section (the same
+/// colors with names), or both. RGB, HSB and grayscale colors are read.
+fn parse_aco(bytes: &[u8]) -> Result<Vec<[u8; 4]>, String> {
+    let mut at = 0;
+    let mut colors = Vec::new();
+    while at < bytes.len() {
+        let version = be16(bytes, at)?;
+        let count = be16(bytes, at + 2)? as usize;
+        if !matches!(version, 1 | 2) {
+            return Err(format!("unknown ACO version {}", version));
+        }
+        at += 4;
+        let mut section = Vec::with_capacity(count);
+        for _ in 0..count {
+            let space = be16(bytes, at)?;
+            let [w, x, y] = [2, 4, 6].map(|o| be16(bytes, at + o));
+            let (w, x, y) = (w?, x?, y?);
+            be16(bytes, at + 8)?;
+            at += 10;
+            let rgb = match space {
+                0 => [w, x, y].map(|c| (c / 257) as u8),
+                1 => hsb_to_rgb(w as f64 / 65536.0, x as f64 / 65535.0, y as f64 / 65535.0),
+                8 => [(255 - (w.min(10000) as u32 * 255 / 10000)) as u8; 3],
+                _ => return Err(format!("ACO color space {} is not supported", space)),
+            };
+            section.push([rgb[0], rgb[1], rgb[2], 255]);
+            if version == 2 {
+                let units = (be16(bytes, at)? as usize) << 16 | be16(bytes, at + 2)? as usize;
+                at += 4 + units * 2;
+                if at > bytes.len() {
+                    return Err("ACO file is truncated".into());
+                }
+            }
+        }
+        // Both sections describe the same colors; the later one wins.
+        colors = section;
+    }
+    Ok(colors)
+}
+
+/// Where each of the `count` colors of a `format` file is stored: its text
+/// line or binary record. ACO files list
+/// the colors once per section, so an entry can have several ranges.
+fn entry_spans(format: PaletteFormat, bytes: &[u8], count: usize) -> Result<Vec<Vec<Range<usize>>>, String> {
+    let records = |size: usize| (0..count).map(|i| std::iter::once(i * size..(i + 1) * size).collect()).collect();
+    let text_lines = |comment: char, skip: usize| -> Result<Vec<Vec<Range<usize>>>, String> {
+        let text = as_text(bytes).ok_or("palette file is not text")?;
+        let base = bytes.len() - text.len();
+        Ok(data_line_spans(text, comment)
+            .into_iter()
+            .skip(skip)
+            .filter(|(l, _)| format != PaletteFormat::Gpl || !(l.starts_with("Name:") || l.starts_with("Columns:")))
+            .take(count)
+            .map(|(_, r)| std::iter::once(base + r.start..base + r.end).collect())
+            .collect())
+    };
+    let entries = match format {
+        PaletteFormat::Jasc => text_lines('#', 3)?,
+        PaletteFormat::Gpl => text_lines('#', 1)?,
+        PaletteFormat::PaintNet | PaletteFormat::Hex => text_lines(';', 0)?,
+        PaletteFormat::Act => records(3),
+        PaletteFormat::GbaPal => records(2),
+        PaletteFormat::Aco => {
+            let mut entries = vec![Vec::new(); count];
+            let mut at = 0;
+            while at < bytes.len() {
+                let version = be16(bytes, at)?;
+                if be16(bytes, at + 2)? as usize != count {
+                    return Err("ACO sections hold different numbers of colors".into());
+                }
+                at += 4;
+                for entry in &mut entries {
+                    let mut end = at + 10;
+                    if version == 2 {
+                        end += 4 + ((be16(bytes, end)? as usize) << 16 | be16(bytes, end + 2)? as usize) * 2;
+                    }
+                    entry.push(at..end);
+                    at = end;
+                }
+            }
+            entries
+        }
+    };
+    if entries.len() != count {
+        return Err("palette entries could not be located".into());
+    }
+    Ok(entries)
+}
+
+/// `bytes` with the colors reordered (`order[new] = old`) by moving each
+/// color's own line or record, so names, comments, alpha columns, unscaled
+/// 5-bit values and number formatting come through exactly.
+pub fn reorder(bytes: &[u8], order: &[usize]) -> Result<Vec<u8>, String> {
+    let file = read(bytes)?;
+    let count = file.colors.len();
+    if order.len() != count || order.iter().any(|&old| old >= count) {
+        return Err("the order is not a permutation of the palette".into());
+    }
+    let entries = entry_spans(file.format, bytes, count)?;
+    // (range to fill, range whose bytes fill it), in file order.
+    let mut moves: Vec<(Range<usize>, Range<usize>)> = order
+        .iter()
+        .enumerate()
+        .flat_map(|(new, &old)| entries[new].iter().cloned().zip(entries[old].iter().cloned()))
+        .collect();
+    moves.sort_by_key(|(to, _)| to.start);
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut at = 0;
+    for (to, from) in moves {
+        out.extend_from_slice(&bytes[at..to.start]);
+        out.extend_from_slice(&bytes[from]);
+        at = to.end;
+    }
+    out.extend_from_slice(&bytes[at..]);
+    if let (PaletteFormat::Act, [.., t0, t1]) = (file.format, bytes) {
+        // The trailer's transparent index follows its color.
+        let transparent = u16::from_be_bytes([*t0, *t1]) as usize;
+        if let (772, Some(new)) = (bytes.len(), order.iter().position(|&old| old == transparent)) {
+            out[770..].copy_from_slice(&(new as u16).to_be_bytes());
+        }
+    }
+    Ok(out)
+}
+
+/// Serialize `colors` in `format`. Formats limited to 256 entries (ACT,
+/// `.gbapal`) reject longer palettes.
+pub fn write(format: PaletteFormat, colors: &[[u8; 4]], name: Option<&str>) -> Result<Vec<u8>, String> {
+    if colors.is_empty() {
+        return Err("palette has no colors".into());
+    }
+    if matches!(format, PaletteFormat::Act | PaletteFormat::GbaPal) && colors.len() > 256 {
+        return Err("this palette format holds at most 256 colors".into());
+    }
+    let mut out = Vec::new();
+    match format {
+        PaletteFormat::Jasc => {
+            let mut text = format!("JASC-PAL\r\n0100\r\n{}\r\n", colors.len());
+            for c in colors {
+                text.push_str(&format!("{} {} {}\r\n", c[0], c[1], c[2]));
+            }
+            out = text.into_bytes();
+        }
+        PaletteFormat::Gpl => {
+            let name = name.filter(|n| !n.trim().is_empty()).unwrap_or("CDPaint");
+            let mut text = format!("GIMP Palette\nName: {}\nColumns: 16\n#\n", name.trim());
+            for c in colors {
+                text.push_str(&format!("{:3} {:3} {:3}\tUntitled\n", c[0], c[1], c[2]));
+            }
+            out = text.into_bytes();
+        }
+        PaletteFormat::Act => {
+            out.resize(768, 0);
+            for (slot, c) in out.chunks_exact_mut(3).zip(colors) {
+                slot.copy_from_slice(&c[..3]);
+            }
+            let transparent = colors.iter().position(|c| c[3] == 0).map_or(0xFFFF, |i| i as u16);
+            out.extend_from_slice(&(colors.len() as u16).to_be_bytes());
+            out.extend_from_slice(&transparent.to_be_bytes());
+        }
+        PaletteFormat::Aco => {
+            let count = u16::try_from(colors.len()).map_err(|_| "ACO holds at most 65535 colors".to_string())?;
+            for version in [1u16, 2] {
+                out.extend_from_slice(&version.to_be_bytes());
+                out.extend_from_slice(&count.to_be_bytes());
+                for c in colors {
+                    out.extend_from_slice(&[0, 0]);
+                    for v in &c[..3] {
+                        out.extend_from_slice(&(*v as u16 * 257).to_be_bytes());
+                    }
+                    out.extend_from_slice(&[0, 0]);
+                    if version == 2 {
+                        // An empty name: its length (counting the terminator), then the terminator.
+                        out.extend_from_slice(&[0, 0, 0, 1, 0, 0]);
+                    }
+                }
+            }
+        }
+        PaletteFormat::PaintNet => {
+            let mut text = format!("; paint.net Palette File\r\n; Colors: {}\r\n", colors.len());
+            for c in colors {
+                text.push_str(&format!("{:02X}{:02X}{:02X}{:02X}\r\n", c[3], c[0], c[1], c[2]));
+            }
+            out = text.into_bytes();
+        }
+        PaletteFormat::Hex => {
+            let mut text = String::new();
+            for c in colors {
+                text.push_str(&format!("{:02x}{:02x}{:02x}\n", c[0], c[1], c[2]));
+            }
+            out = text.into_bytes();
+        }
+        PaletteFormat::GbaPal => {
+            for c in colors {
+                let v = downconvert(c[0]) | downconvert(c[1]) << 5 | downconvert(c[2]) << 10;
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_format_round_trips_and_is_detected_by_content() {
+        let colors = [[0, 0, 0, 0], [248, 16, 8, 255], [96, 200, 248, 255]];
+        let opaque = colors.map(|c| [c[0], c[1], c[2], 255]);
+        for (format, expected) in [
+            (PaletteFormat::Jasc, &opaque),
+            (PaletteFormat::Gpl, &opaque),
+            (PaletteFormat::Act, &colors),
+            (PaletteFormat::Aco, &opaque),
+            (PaletteFormat::PaintNet, &colors),
+            (PaletteFormat::Hex, &opaque),
+        ] {
+            let bytes = write(format, &colors, Some("Test")).unwrap();
+            let read_back = read(&bytes).unwrap();
+            assert_eq!(read_back.format, format);
+            assert_eq!(read_back.colors, expected.to_vec(), "{:?}", format);
+        }
+        // 5-bit components survive a .gbapal round trip exactly.
+        let gba = read(&write(PaletteFormat::GbaPal, &colors, None).unwrap()).unwrap();
+        assert_eq!(gba.format, PaletteFormat::GbaPal);
+        assert!(gba.scaled_from_5bit);
+        let again = write(PaletteFormat::GbaPal, &gba.colors, None).unwrap();
+        assert_eq!(again, [0x00, 0x00, 0x5F, 0x04, 0x2C, 0x7F]);
+        assert_eq!(read(&write(PaletteFormat::Gpl, &colors, Some("Test")).unwrap()).unwrap().name.as_deref(), Some("Test"));
+    }
+
+    #[test]
+    fn reorder_keeps_each_files_own_encoding() {
+        let colors = [[0, 0, 0, 0], [248, 16, 8, 255], [96, 200, 248, 128]];
+        let order = [2, 0, 1];
+        for format in [
+            PaletteFormat::Jasc,
+            PaletteFormat::Gpl,
+            PaletteFormat::Act,
+            PaletteFormat::Aco,
+            PaletteFormat::PaintNet,
+            PaletteFormat::Hex,
+            PaletteFormat::GbaPal,
+        ] {
+            let bytes = write(format, &colors, Some("Test")).unwrap();
+            let before = read(&bytes).unwrap().colors;
+            let after = read(&reorder(&bytes, &order).unwrap()).unwrap().colors;
+            assert_eq!(after, order.map(|old| before[old]).to_vec(), "{:?}", format);
+        }
+
+        let jasc = b"JASC-PAL\r\n0100\r\n3\r\n0 0 0 0\r\n31 16 1 255\r\n 2 3 4 128\r\n";
+        assert_eq!(
+            reorder(jasc, &order).unwrap(),
+            b"JASC-PAL\r\n0100\r\n3\r\n 2 3 4 128\r\n0 0 0 0\r\n31 16 1 255\r\n"
+        );
+        let gpl = b"GIMP Palette\nName: Skin\n# note\n255 128   0 Orange\n  0   0 255\tBlue\n1 2 3\n";
+        assert_eq!(
+            reorder(gpl, &order).unwrap(),
+            b"GIMP Palette\nName: Skin\n# note\n1 2 3\n255 128   0 Orange\n  0   0 255\tBlue\n"
+        );
+        assert!(reorder(jasc, &[0, 1, 3]).is_err());
+    }
+
+    #[test]
+    fn unscaled_jasc_and_foreign_files_parse() {
+        let gba = read(b"JASC-PAL\r\n0100\r\n2\r\n0 0 0\r\n31 16 1\r\n").unwrap();
+        assert!(gba.scaled_from_5bit);
+        assert_eq!(gba.colors[1], [255, 131, 8, 255]);
+
+        let gpl = read(b"GIMP Palette\nName: Skin\nColumns: 4\n# comment\n255 128   0 Orange\n  0   0 255\n").unwrap();
+        assert_eq!(gpl.name.as_deref(), Some("Skin"));
+        assert_eq!(gpl.colors, [[255, 128, 0, 255], [0, 0, 255, 255]]);
+
+        let pdn = read(b"; paint.net Palette File\r\nFF112233\r\n80FFFFFF\r\n").unwrap();
+        assert_eq!(pdn.colors, [[0x11, 0x22, 0x33, 255], [255, 255, 255, 0x80]]);
+        assert_eq!(read(b"#ff0000\n00ff00\n").unwrap().colors[1], [0, 255, 0, 255]);
+
+        assert!(read(b"JASC-PAL\r\n0100\r\n3\r\n0 0 0\r\n").is_err());
+        assert!(read(b"hello world\n").is_err());
+    }
+}
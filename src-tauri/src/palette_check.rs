@@ -0,0 +1,246 @@
+//! Palette consistency checks for a project's asset folders.
+//!
+//! In a species folder the front and back sprites and `normal.pal` /
+//! `shiny.pal` share one 16-color palette with index 0 transparent; gbagfx
+//! only complains about a broken one at build time. Every PNG is checked on
+//! its own (indexed, at most 16 colors, a size in whole tiles), front/back
+//! PNGs against `normal.pal`, and `shiny.pal` against `normal.pal`. Colors
+//! are compared as the GBA sees them, rounded down to RGB555.
+use serde::Serialize;
+
+use crate::palette;
+use crate::png;
+
+/// Colors in one 4bpp palette bank.
+const BANK_COLORS: usize = 16;
+
+/// PNGs larger than this are reported with `too_large` instead of being read;
+/// no sprite sheet comes close.
+pub const MAX_PNG_FILE_BYTES: u64 = 16 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub path: String,
+    pub severity: Severity,
+    /// Stable id for the check, e.g. `palette-mismatch`.
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// One folder's assets: `(path, bytes)` for each PNG and the shared
+/// palettes when present.
+#[derive(Debug, Default)]
+pub struct Folder {
+    pub pngs: Vec<(String, Vec<u8>)>,
+    pub normal: Option<(String, Vec<u8>)>,
+    pub shiny: Option<(String, Vec<u8>)>,
+}
+
+fn diagnostic(path: &str, severity: Severity, code: &'static str, message: String) -> Diagnostic {
+    Diagnostic {
+        path: path.to_string(),
+        severity,
+        code,
+        message,
+    }
+}
+
+/// A color as the GBA stores it, 5 bits per channel.
+pub fn rgb555(c: [u8; 3]) -> [u8; 3] {
+    c.map(|v| v >> 3)
+}
+
+/// Front and back sprites use the species palette; icons, footprints and
+/// any other PNGs in the folder have their own.
+fn uses_species_palette(path: &str) -> bool {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path).to_ascii_lowercase();
+    matches!(name.as_str(), "front.png" | "anim_front.png" | "back.png")
+}
+
+/// The diagnostic for a PNG skipped for being over `MAX_PNG_FILE_BYTES`.
+pub fn too_large(path: &str, size: u64) -> Diagnostic {
+    diagnostic(
+        path,
+        Severity::Warning,
+        "too-large",
+        format!("is {} bytes; PNGs over {} bytes are not checked", size, MAX_PNG_FILE_BYTES),
+    )
+}
+
+fn read_palette(path: &str, bytes: &[u8], out: &mut Vec<Diagnostic>) -> Option<Vec<[u8; 3]>> {
+    match palette::read(bytes) {
+        Ok(pal) => Some(pal.colors.iter().map(|c| [c[0], c[1], c[2]]).collect()),
+        Err(e) => {
+            out.push(diagnostic(path, Severity::Error, "unreadable", format!("palette could not be read: {}", e)));
+            None
+        }
+    }
+}
+
+pub fn check_folder(folder: &Folder) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    let normal = folder.normal.as_ref().and_then(|(path, bytes)| read_palette(path, bytes, &mut out));
+    if let (Some(normal), Some((path, bytes))) = (&normal, &folder.shiny) {
+        if let Some(shiny) = read_palette(path, bytes, &mut out) {
+            if shiny.len() != normal.len() {
+                out.push(diagnostic(
+                    path,
+                    Severity::Warning,
+                    "shiny-count",
+                    format!("has {} colors but normal.pal has {}", shiny.len(), normal.len()),
+                ));
+            }
+        }
+    }
+
+    for (path, bytes) in &folder.pngs {
+        match png::dimensions(bytes) {
+            Ok((w, h)) if !w.is_multiple_of(8) || !h.is_multiple_of(8) => out.push(diagnostic(
+                path,
+                Severity::Error,
+                "size-not-tiles",
+                format!("{}x{} is not a multiple of 8 in both directions", w, h),
+            )),
+            Ok(_) => {}
+            Err(e) => {
+                out.push(diagnostic(path, Severity::Error, "unreadable", format!("PNG could not be read: {}", e)));
+                continue;
+            }
+        }
+        let image = match png::decode_indexed(bytes) {
+            Ok(image) => image,
+            Err(e) => {
+                let code = if png::is_indexed(bytes) == Ok(false) { "not-indexed" } else { "unreadable" };
+                out.push(diagnostic(path, Severity::Error, code, e));
+                continue;
+            }
+        };
+
+        let mut used = [false; 256];
+        for &i in &image.indices {
+            used[i as usize] = true;
+        }
+        let count = used.iter().filter(|&&u| u).count();
+        let highest = used.iter().rposition(|&u| u).unwrap_or(0);
+        if count > BANK_COLORS {
+            out.push(diagnostic(
+                path,
+                Severity::Error,
+                "too-many-colors",
+                format!("uses {} colors; a 4bpp sprite has {}", count, BANK_COLORS),
+            ));
+        } else if highest >= BANK_COLORS {
+            out.push(diagnostic(
+                path,
+                Severity::Error,
+                "too-many-colors",
+                format!("uses index {}, past the {} colors of a 4bpp sprite", highest, BANK_COLORS),
+            ));
+        }
+
+        let Some(normal) = normal.as_ref().filter(|_| uses_species_palette(path)) else {
+            continue;
+        };
+        let plte_len = image.info.palette.len().min(BANK_COLORS);
+        let normal_len = normal.len().min(BANK_COLORS);
+        if plte_len != normal_len {
+            out.push(diagnostic(
+                path,
+                Severity::Warning,
+                "palette-count",
+                format!("PLTE has {} colors but normal.pal has {}", plte_len, normal_len),
+            ));
+        }
+        let differing: Vec<String> = image
+            .info
+            .palette
+            .iter()
+            .zip(normal)
+            .take(BANK_COLORS)
+            .enumerate()
+            .filter(|(_, (a, b))| rgb555(**a) != rgb555(**b))
+            .map(|(i, _)| i.to_string())
+            .collect();
+        if !differing.is_empty() {
+            out.push(diagnostic(
+                path,
+                Severity::Error,
+                "palette-mismatch",
+                format!("PLTE differs from normal.pal at index {}", differing.join(", ")),
+            ));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::chunk;
+
+    fn indexed_png(w: u32, h: u32, palette: Vec<[u8; 3]>, indices: &[u8]) -> Vec<u8> {
+        let opts = png::IndexedPngOptions {
+            width: w,
+            height: h,
+            palette,
+            alpha: Vec::new(),
+            bit_depth: None,
+            phys: None,
+            srgb: None,
+        };
+        png::encode_indexed(&opts, indices).unwrap()
+    }
+
+    fn header_only_png(bit_depth: u8, color_type: u8) -> Vec<u8> {
+        let mut ihdr = 8u32.to_be_bytes().repeat(2);
+        ihdr.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+        [png::SIGNATURE.to_vec(), chunk(b"IHDR", &ihdr), chunk(b"IEND", &[])].concat()
+    }
+
+    #[test]
+    fn reports_broken_species_folders() {
+        let colors: Vec<[u8; 4]> = (0..16).map(|i| [i * 16, 0, 0, 255]).collect();
+        let rgb: Vec<[u8; 3]> = colors.iter().map(|c| [c[0], c[1], c[2]]).collect();
+        let normal = palette::write(palette::PaletteFormat::Jasc, &colors, None).unwrap();
+        let shiny = palette::write(palette::PaletteFormat::Jasc, &colors[..15], None).unwrap();
+
+        // Off by less than one RGB555 step still matches.
+        let mut nudged = rgb.clone();
+        nudged[3][0] += 1;
+        let mut changed = rgb.clone();
+        changed[5] = [0, 255, 0];
+        let folder = Folder {
+            pngs: vec![
+                ("mon/front.png".into(), indexed_png(8, 8, nudged, &[1; 64])),
+                ("mon/back.png".into(), indexed_png(8, 12, changed, &[2; 96])),
+                ("mon/anim_front.png".into(), indexed_png(8, 8, rgb[..8].to_vec(), &[1; 64])),
+                ("mon/front_sketch.png".into(), indexed_png(8, 8, vec![[0, 255, 0]; 16], &[1; 64])),
+                ("mon/icon.png".into(), indexed_png(8, 8, vec![[9, 9, 9]; 32], &(0..64).map(|i| i / 2).collect::<Vec<_>>())),
+                ("mon/footprint.png".into(), header_only_png(8, 2)),
+                ("mon/broken.png".into(), header_only_png(3, 3)),
+            ],
+            normal: Some(("mon/normal.pal".into(), normal)),
+            shiny: Some(("mon/shiny.pal".into(), shiny)),
+        };
+        let found: Vec<_> = check_folder(&folder).iter().map(|d| (d.path.clone(), d.code)).collect();
+        assert_eq!(
+            found,
+            [
+                ("mon/shiny.pal".to_string(), "shiny-count"),
+                ("mon/back.png".to_string(), "size-not-tiles"),
+                ("mon/back.png".to_string(), "palette-mismatch"),
+                ("mon/anim_front.png".to_string(), "palette-count"),
+                ("mon/icon.png".to_string(), "too-many-colors"),
+                ("mon/footprint.png".to_string(), "not-indexed"),
+                ("mon/broken.png".to_string(), "unreadable"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Reorder a palette and every file in its folder that uses it.
+//!
+//! `order[new] = old`: slot `new` of the reordered palette holds what slot
+//! `old` held. The palette file is permuted, and so is every sibling that
+//! shares it: PNGs whose PLTE starts with the same colors (compared as
+//! RGB555, like `palette_check`) get their PLTE and tRNS permuted and their
+//! pixels remapped so they look the same, and the counterpart palette
+//! (`shiny.pal` for `normal.pal` and the other way around) is permuted slot
+//! for slot when it has the same number of colors. Palette files keep their
+//! own text or records (see `palette::reorder`). Entries past the palette's
+//! length are left alone.
+use serde::{Deserialize, Serialize};
+
+use crate::export::sha256_hex;
+use crate::palette;
+use crate::palette_check::rgb555;
+use crate::png;
+use crate::png_chunks;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Png,
+    Palette,
+}
+
+/// One file the reorder rewrites, with enough to show a diff.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReorderedFile {
+    pub path: String,
+    pub kind: FileKind,
+    /// Pixels whose stored index changes (always 0 for palettes).
+    pub changed_pixels: usize,
+    pub before: Vec<[u8; 3]>,
+    pub after: Vec<[u8; 3]>,
+    /// Hash of the file as it was read, for `select`.
+    pub sha256: String,
+}
+
+/// A file the user chose to rewrite, with the hash its preview showed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PreviewedFile {
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rewrite {
+    pub info: ReorderedFile,
+    pub data: Vec<u8>,
+}
+
+/// `order` must name every slot of an `len`-color palette exactly once.
+pub fn validate_order(order: &[usize], len: usize) -> Result<(), String> {
+    if order.len() != len {
+        return Err(format!("the order has {} entries but the palette has {} colors", order.len(), len));
+    }
+    let mut seen = vec![false; len];
+    for &i in order {
+        if i >= len || std::mem::replace(&mut seen[i], true) {
+            return Err("the order is not a permutation of the palette".into());
+        }
+    }
+    Ok(())
+}
+
+fn permute<T: Copy>(items: &[T], order: &[usize]) -> Vec<T> {
+    let mut out = items.to_vec();
+    for (new, &old) in order.iter().enumerate() {
+        out[new] = items[old];
+    }
+    out
+}
+
+fn rgb(colors: &[[u8; 4]]) -> Vec<[u8; 3]> {
+    colors.iter().map(|c| [c[0], c[1], c[2]]).collect()
+}
+
+fn file_stem(path: &str) -> String {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    name.split('.').next().unwrap_or(name).to_ascii_lowercase()
+}
+
+/// The stem of the palette that shares slots with `path`'s.
+fn counterpart_stem(path: &str) -> Option<&'static str> {
+    match file_stem(path).as_str() {
+        "normal" => Some("shiny"),
+        "shiny" => Some("normal"),
+        _ => None,
+    }
+}
+
+fn permute_palette(path: &str, bytes: &[u8], file: &palette::PaletteFile, order: &[usize]) -> Result<Rewrite, String> {
+    let colors = permute(&file.colors, order);
+    Ok(Rewrite {
+        info: ReorderedFile {
+            path: path.to_string(),
+            kind: FileKind::Palette,
+            changed_pixels: 0,
+            before: rgb(&file.colors),
+            after: rgb(&colors),
+            sha256: sha256_hex(bytes),
+        },
+        data: palette::reorder(bytes, order)?,
+    })
+}
+
+/// The PNG with its palette permuted and pixels remapped, or `None` when
+/// it doesn't use `colors`.
+fn permute_png(path: &str, bytes: &[u8], colors: &[[u8; 3]], order: &[usize]) -> Result<Option<Rewrite>, String> {
+    let Ok(image) = png::decode_indexed(bytes) else {
+        return Ok(None);
+    };
+    let plte = &image.info.palette;
+    if plte.len() < colors.len() || plte.iter().zip(colors).any(|(a, b)| rgb555(*a) != rgb555(*b)) {
+        return Ok(None);
+    }
+    let mut new_index: Vec<u8> = (0..=255).collect();
+    for (new, &old) in order.iter().enumerate() {
+        new_index[old] = new as u8;
+    }
+    let indices: Vec<u8> = image.indices.iter().map(|&i| new_index[i as usize]).collect();
+    let changed_pixels = image.indices.iter().zip(&indices).filter(|(a, b)| a != b).count();
+
+    let palette = [permute(&plte[..order.len()], order), plte[order.len()..].to_vec()].concat();
+    let mut alpha = image.info.alpha.clone();
+    alpha.resize(plte.len(), 255);
+    let alpha = [permute(&alpha[..order.len()], order), alpha[order.len()..].to_vec()].concat();
+    let srgb = png::parse_chunks(bytes)?.iter().find(|c| c.is(b"sRGB")).and_then(|c| c.data.first().copied());
+    let opts = png::IndexedPngOptions {
+        width: image.info.width,
+        height: image.info.height,
+        palette: palette.clone(),
+        alpha,
+        bit_depth: Some(image.info.bit_depth),
+        phys: None,
+        srgb,
+    };
+    // pHYs, text and other copy-safe chunks ride along unchanged.
+    let data = png_chunks::reinsert(&png::encode_indexed(&opts, &indices)?, &png_chunks::collect(bytes)?)?;
+    Ok(Some(Rewrite {
+        info: ReorderedFile {
+            path: path.to_string(),
+            kind: FileKind::Png,
+            changed_pixels,
+            before: plte.clone(),
+            after: palette,
+            sha256: sha256_hex(bytes),
+        },
+        data,
+    }))
+}
+
+/// Rewrites for reordering the palette `source` and the `siblings` (path
+/// and bytes of the other PNGs and palette files in its folder) that use
+/// it. The palette itself comes first.
+pub fn plan(source: (&str, &[u8]), siblings: &[(String, Vec<u8>)], order: &[usize]) -> Result<Vec<Rewrite>, String> {
+    let file = palette::read(source.1)?;
+    validate_order(order, file.colors.len())?;
+    let colors = rgb(&file.colors);
+    let counterpart = counterpart_stem(source.0);
+    let mut out = vec![permute_palette(source.0, source.1, &file, order)?];
+    for (path, bytes) in siblings {
+        if path.to_ascii_lowercase().ends_with(".png") {
+            out.extend(permute_png(path, bytes, &colors, order)?);
+        } else if counterpart.is_some_and(|stem| file_stem(path) == stem) {
+            if let Some(other) = palette::read(bytes).ok().filter(|other| other.colors.len() == order.len()) {
+                out.push(permute_palette(path, bytes, &other, order)?);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// The rewrites for the files the user kept in the preview. Fails when one
+/// of them isn't part of the plan any more or has changed since, and when
+/// the palette itself was left out.
+pub fn select(rewrites: Vec<Rewrite>, files: &[PreviewedFile]) -> Result<Vec<Rewrite>, String> {
+    let mut rewrites = rewrites.into_iter().map(Some).collect::<Vec<_>>();
+    let mut out = Vec::with_capacity(files.len());
+    for file in files {
+        let rewrite = rewrites
+            .iter_mut()
+            .find_map(|slot| slot.take_if(|r| r.info.path == file.path))
+            .ok_or_else(|| format!("{} no longer uses this palette", file.path))?;
+        if rewrite.info.sha256 != file.sha256 {
+            return Err(format!("{} changed since the preview", file.path));
+        }
+        out.push(rewrite);
+    }
+    if rewrites.first().is_some_and(Option::is_some) {
+        return Err("the palette itself must be rewritten".into());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permutes_palette_siblings_together() {
+        // 5-bit JASC with an alpha column, and a GIMP palette with names.
+        let normal = b"JASC-PAL\r\n0100\r\n4\r\n0 0 0 0\r\n7 0 0 255\r\n15 0 0 255\r\n31 0 0 255\r\n".to_vec();
+        let shiny = b"GIMP Palette\nName: Shiny\n  0   0   0\tClear\n  0  60   0\tLeaf\n  0 120   0\tMoss\n  0 180   0\tPine\n".to_vec();
+        let colors = rgb(&palette::read(&normal).unwrap().colors);
+        let png_with = |palette: Vec<[u8; 3]>| {
+            let opts = png::IndexedPngOptions {
+                width: 4,
+                height: 1,
+                palette,
+                alpha: vec![0],
+                bit_depth: Some(4),
+                phys: None,
+                srgb: None,
+            };
+            png::encode_indexed(&opts, &[0, 1, 2, 3]).unwrap()
+        };
+        let siblings = vec![
+            ("front.png".to_string(), png_with(colors.clone())),
+            ("icon.png".to_string(), png_with(vec![[1, 2, 3]; 4])),
+            ("other.pal".to_string(), normal.clone()),
+            ("shiny.gpl".to_string(), shiny),
+        ];
+        // Swap slots 1 and 3.
+        let order = [0, 3, 2, 1];
+        let out = plan(("normal.pal", &normal), &siblings, &order).unwrap();
+        let paths: Vec<_> = out.iter().map(|r| r.info.path.as_str()).collect();
+        assert_eq!(paths, ["normal.pal", "front.png", "shiny.gpl"]);
+
+        assert_eq!(out[0].data, b"JASC-PAL\r\n0100\r\n4\r\n0 0 0 0\r\n31 0 0 255\r\n15 0 0 255\r\n7 0 0 255\r\n");
+        assert_eq!(
+            out[2].data,
+            b"GIMP Palette\nName: Shiny\n  0   0   0\tClear\n  0 180   0\tPine\n  0 120   0\tMoss\n  0  60   0\tLeaf\n"
+        );
+        let front = png::decode_indexed(&out[1].data).unwrap();
+        assert_eq!(front.indices, [0, 3, 2, 1]);
+        assert_eq!(front.info.palette[1], colors[3]);
+        assert_eq!(front.info.alpha[0], 0);
+        assert_eq!(out[1].info.changed_pixels, 2);
+
+        assert!(plan(("normal.pal", &normal), &siblings, &[0, 1, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn select_keeps_only_unchanged_previewed_files() {
+        let rewrite = |path: &str, data: &[u8]| Rewrite {
+            info: ReorderedFile {
+                path: path.to_string(),
+                kind: FileKind::Palette,
+                changed_pixels: 0,
+                before: Vec::new(),
+                after: Vec::new(),
+                sha256: sha256_hex(data),
+            },
+            data: data.to_vec(),
+        };
+        let rewrites = || vec![rewrite("normal.pal", b"a"), rewrite("front.png", b"b"), rewrite("shiny.pal", b"c")];
+        let kept = |path: &str, data: &[u8]| PreviewedFile {
+            path: path.to_string(),
+            sha256: sha256_hex(data),
+        };
+
+        let out = select(rewrites(), &[kept("normal.pal", b"a"), kept("shiny.pal", b"c")]).unwrap();
+        let paths: Vec<_> = out.iter().map(|r| r.info.path.as_str()).collect();
+        assert_eq!(paths, ["normal.pal", "shiny.pal"]);
+
+        let changed = select(rewrites(), &[kept("normal.pal", b"a"), kept("front.png", b"old")]).unwrap_err();
+        assert!(changed.contains("changed since the preview"));
+        assert!(select(rewrites(), &[kept("front.png", b"b")]).is_err());
+        assert!(select(rewrites(), &[kept("normal.pal", b"a"), kept("gone.png", b"d")]).is_err());
+    }
+}
@@ -0,0 +1,528 @@
+//! PNG container handling for indexed (palette) images.
+//!
+//! The webview decodes PNGs to RGBA, which loses which palette slot a pixel
+//! used whenever two slots share a color, and drops unused slots entirely.
+//! Parsing IHDR/PLTE/tRNS/IDAT here keeps the palette and the per-pixel
+//! indices exactly as stored, and `encode_indexed` writes them back the way
+//! `gbagfx` expects: minimal bit depth, full PLTE, trimmed tRNS.
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+pub const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/// Refuse images whose index buffer would exceed this many pixels.
+const MAX_PIXELS: u64 = 1 << 28;
+
+const COLOR_TYPE_INDEXED: u8 = 3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Chunk<'a> {
+    pub kind: [u8; 4],
+    pub data: &'a [u8],
+}
+
+impl Chunk<'_> {
+    pub fn is(&self, kind: &[u8; 4]) -> bool {
+        &self.kind == kind
+    }
+}
+
+/// Split a PNG into its chunks, verifying the signature, every length and
+/// every CRC. Stops after IEND.
+pub fn parse_chunks(bytes: &[u8]) -> Result<Vec<Chunk<'_>>, String> {
+    if bytes.len() < SIGNATURE.len() || bytes[..8] != SIGNATURE {
+        return Err("not a PNG file".into());
+    }
+    let mut chunks = Vec::new();
+    let mut pos = SIGNATURE.len();
+    loop {
+        let header = bytes
+            .get(pos..pos + 8)
+            .ok_or_else(|| "PNG is truncated (missing IEND)".to_string())?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = [header[4], header[5], header[6], header[7]];
+        if !kind.iter().all(u8::is_ascii_alphabetic) {
+            return Err("corrupt PNG chunk type".into());
+        }
+        let data_end = (pos + 8)
+            .checked_add(len)
+            .filter(|end| end + 4 <= bytes.len())
+            .ok_or_else(|| format!("PNG chunk {} is truncated", String::from_utf8_lossy(&kind)))?;
+        let data = &bytes[pos + 8..data_end];
+        let stored_crc = u32::from_be_bytes([
+            bytes[data_end],
+            bytes[data_end + 1],
+            bytes[data_end + 2],
+            bytes[data_end + 3],
+        ]);
+        if crc32fast::hash(&bytes[pos + 4..data_end]) != stored_crc {
+            return Err(format!("PNG chunk {} has a bad CRC", String::from_utf8_lossy(&kind)));
+        }
+        chunks.push(Chunk { kind, data });
+        pos = data_end + 4;
+        if &kind == b"IEND" {
+            return Ok(chunks);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexedPngInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Bits per index as stored: 1, 2, 4 or 8.
+    pub bit_depth: u8,
+    pub interlaced: bool,
+    /// Every PLTE entry, used or not, in file order.
+    pub palette: Vec<[u8; 3]>,
+    /// Alpha for each palette entry (255 where tRNS doesn't cover it).
+    pub alpha: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexedImage {
+    pub info: IndexedPngInfo,
+    /// One index per pixel, row-major.
+    pub indices: Vec<u8>,
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+fn parse_ihdr(data: &[u8]) -> Result<Header, String> {
+    if data.len() != 13 {
+        return Err("PNG IHDR has the wrong length".into());
+    }
+    let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    if width == 0 || height == 0 {
+        return Err("PNG has zero width or height".into());
+    }
+    if data[10] != 0 || data[11] != 0 || data[12] > 1 {
+        return Err("PNG uses an unknown compression, filter or interlace method".into());
+    }
+    Ok(Header {
+        width,
+        height,
+        bit_depth: data[8],
+        color_type: data[9],
+        interlaced: data[12] == 1,
+    })
+}
+
+fn header(bytes: &[u8]) -> Result<Header, String> {
+    let chunks = parse_chunks(bytes)?;
+    let ihdr = chunks.first().filter(|c| c.is(b"IHDR")).ok_or("PNG does not start with IHDR")?;
+    parse_ihdr(ihdr.data)
+}
+
+/// Width and height from a PNG's IHDR.
+pub fn dimensions(bytes: &[u8]) -> Result<(u32, u32), String> {
+    let header = header(bytes)?;
+    Ok((header.width, header.height))
+}
+
+/// Whether a PNG's IHDR declares a palette image.
+pub fn is_indexed(bytes: &[u8]) -> Result<bool, String> {
+    Ok(header(bytes)?.color_type == COLOR_TYPE_INDEXED)
+}
+
+fn row_bytes(width: u32, bit_depth: u8) -> usize {
+    (width as usize * bit_depth as usize).div_ceil(8)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Undo the per-row filters of one (sub)image in place. Palette images
+/// filter whole bytes, so the "previous pixel" is always one byte back.
+fn unfilter(data: &mut [u8], rows: usize, stride: usize) -> Result<(), String> {
+    for y in 0..rows {
+        let (done, rest) = data.split_at_mut(y * (stride + 1));
+        let prev = if y == 0 {
+            None
+        } else {
+            Some(&done[done.len() - stride..])
+        };
+        let filter = rest[0];
+        let row = &mut rest[1..=stride];
+        for x in 0..stride {
+            let a = if x > 0 { row[x - 1] } else { 0 };
+            let b = prev.map_or(0, |p| p[x]);
+            let c = if x > 0 { prev.map_or(0, |p| p[x - 1]) } else { 0 };
+            row[x] = match filter {
+                0 => row[x],
+                1 => row[x].wrapping_add(a),
+                2 => row[x].wrapping_add(b),
+                3 => row[x].wrapping_add(((a as u16 + b as u16) / 2) as u8),
+                4 => row[x].wrapping_add(paeth(a, b, c)),
+                f => return Err(format!("PNG uses unknown row filter {}", f)),
+            };
+        }
+    }
+    Ok(())
+}
+
+fn unpack_index(row: &[u8], x: usize, bit_depth: u8) -> u8 {
+    match bit_depth {
+        8 => row[x],
+        bd => {
+            let per_byte = 8 / bd as usize;
+            let shift = 8 - bd as usize * (x % per_byte + 1);
+            (row[x / per_byte] >> shift) & ((1u16 << bd) - 1) as u8
+        }
+    }
+}
+
+/// Adam7 passes as (x0, y0, dx, dy).
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+fn pass_size(len: u32, start: u32, step: u32) -> u32 {
+    if len > start {
+        (len - start).div_ceil(step)
+    } else {
+        0
+    }
+}
+
+/// Decode a palette PNG (color type 3, bit depth 1/2/4/8) without touching
+/// colors: the result holds the stored indices and the full palette.
+pub fn decode_indexed(bytes: &[u8]) -> Result<IndexedImage, String> {
+    let chunks = parse_chunks(bytes)?;
+    let first = chunks.first().filter(|c| c.is(b"IHDR")).ok_or_else(|| "PNG does not start with IHDR".to_string())?;
+    let header = parse_ihdr(first.data)?;
+    if header.color_type != COLOR_TYPE_INDEXED {
+        return Err("PNG is not an indexed (palette) image".into());
+    }
+    if !matches!(header.bit_depth, 1 | 2 | 4 | 8) {
+        return Err(format!("invalid bit depth {} for an indexed PNG", header.bit_depth));
+    }
+    if header.width as u64 * header.height as u64 > MAX_PIXELS {
+        return Err("PNG dimensions are too large".into());
+    }
+
+    let plte = chunks.iter().find(|c| c.is(b"PLTE")).ok_or_else(|| "indexed PNG has no PLTE chunk".to_string())?;
+    if plte.data.is_empty() || plte.data.len() % 3 != 0 || plte.data.len() / 3 > 256 {
+        return Err("PNG PLTE chunk has an invalid length".into());
+    }
+    let palette: Vec<[u8; 3]> = plte.data.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+    let mut alpha = vec![255u8; palette.len()];
+    if let Some(trns) = chunks.iter().find(|c| c.is(b"tRNS")) {
+        if trns.data.len() > palette.len() {
+            return Err("PNG tRNS chunk is longer than the palette".into());
+        }
+        alpha[..trns.data.len()].copy_from_slice(trns.data);
+    }
+
+    let (w, h, bd) = (header.width, header.height, header.bit_depth);
+    let passes: Vec<(u32, u32, u32, u32, u32, u32)> = if header.interlaced {
+        ADAM7
+            .iter()
+            .map(|&(x0, y0, dx, dy)| (x0, y0, dx, dy, pass_size(w, x0, dx), pass_size(h, y0, dy)))
+            .filter(|p| p.4 > 0 && p.5 > 0)
+            .collect()
+    } else {
+        vec![(0, 0, 1, 1, w, h)]
+    };
+    let expected: usize = passes
+        .iter()
+        .map(|p| (row_bytes(p.4, bd) + 1) * p.5 as usize)
+        .sum();
+
+    let compressed: Vec<u8> = chunks
+        .iter()
+        .filter(|c| c.is(b"IDAT"))
+        .flat_map(|c| c.data.iter().copied())
+        .collect();
+    let mut raw = Vec::with_capacity(expected);
+    flate2::read::ZlibDecoder::new(compressed.as_slice())
+        .take(expected as u64 + 1)
+        .read_to_end(&mut raw)
+        .map_err(|e| format!("PNG image data is corrupt: {}", e))?;
+    if raw.len() < expected {
+        return Err("PNG image data is truncated".into());
+    }
+    raw.truncate(expected);
+
+    let mut indices = vec![0u8; w as usize * h as usize];
+    let mut offset = 0;
+    for (x0, y0, dx, dy, pw, ph) in passes {
+        let stride = row_bytes(pw, bd);
+        let len = (stride + 1) * ph as usize;
+        let sub = &mut raw[offset..offset + len];
+        offset += len;
+        unfilter(sub, ph as usize, stride)?;
+        for py in 0..ph as usize {
+            let row = &sub[py * (stride + 1) + 1..(py + 1) * (stride + 1)];
+            let y = y0 as usize + py * dy as usize;
+            for px in 0..pw as usize {
+                let x = x0 as usize + px * dx as usize;
+                indices[y * w as usize + x] = unpack_index(row, px, bd);
+            }
+        }
+    }
+    if let Some(bad) = indices.iter().find(|&&i| i as usize >= palette.len()) {
+        return Err(format!("PNG pixel uses index {} outside its {}-entry palette", bad, palette.len()));
+    }
+
+    Ok(IndexedImage {
+        info: IndexedPngInfo {
+            width: w,
+            height: h,
+            bit_depth: bd,
+            interlaced: header.interlaced,
+            palette,
+            alpha,
+        },
+        indices,
+    })
+}
+
+/// pHYs contents: pixels per unit on each axis; `unit` 1 is the meter.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PhysicalDims {
+    pub x: u32,
+    pub y: u32,
+    #[serde(default = "default_phys_unit")]
+    pub unit: u8,
+}
+
+fn default_phys_unit() -> u8 {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexedPngOptions {
+    pub width: u32,
+    pub height: u32,
+    pub palette: Vec<[u8; 3]>,
+    /// Per-entry alpha; missing entries are opaque.
+    #[serde(default)]
+    pub alpha: Vec<u8>,
+    /// 1, 2, 4 or 8. Defaults to the smallest depth that holds the palette.
+    #[serde(default)]
+    pub bit_depth: Option<u8>,
+    #[serde(default)]
+    pub phys: Option<PhysicalDims>,
+    /// sRGB rendering intent (0 = perceptual).
+    #[serde(default)]
+    pub srgb: Option<u8>,
+}
+
+pub fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+/// Smallest PNG palette bit depth with room for `entries` palette slots.
+pub fn minimal_bit_depth(entries: usize) -> u8 {
+    match entries {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    }
+}
+
+/// Encode one index per pixel as a palette PNG. Rows are left unfiltered
+/// (the usual choice for palette images) and deflated at the highest level,
+/// so identical input always gives identical bytes.
+pub fn encode_indexed(opts: &IndexedPngOptions, indices: &[u8]) -> Result<Vec<u8>, String> {
+    let (w, h) = (opts.width, opts.height);
+    if w == 0 || h == 0 {
+        return Err("image has zero width or height".into());
+    }
+    if w as u64 * h as u64 > MAX_PIXELS {
+        return Err("image dimensions are too large".into());
+    }
+    if indices.len() as u64 != w as u64 * h as u64 {
+        return Err("index buffer does not match the image size".into());
+    }
+    if opts.palette.is_empty() || opts.palette.len() > 256 {
+        return Err("palette must have 1 to 256 entries".into());
+    }
+    if opts.alpha.len() > opts.palette.len() {
+        return Err("alpha has more entries than the palette".into());
+    }
+    if let Some(bad) = indices.iter().find(|&&i| i as usize >= opts.palette.len()) {
+        return Err(format!("index {} is outside the {}-entry palette", bad, opts.palette.len()));
+    }
+    let needed = minimal_bit_depth(opts.palette.len());
+    let bd = match opts.bit_depth {
+        None => needed,
+        Some(bd @ (1 | 2 | 4 | 8)) if bd >= needed => bd,
+        Some(bd @ (1 | 2 | 4 | 8)) => {
+            return Err(format!("{} palette entries do not fit in {} bits", opts.palette.len(), bd))
+        }
+        Some(bd) => return Err(format!("invalid bit depth {} for an indexed PNG", bd)),
+    };
+
+    let stride = row_bytes(w, bd);
+    let per_byte = 8 / bd as usize;
+    let mut raw = vec![0u8; (stride + 1) * h as usize];
+    for (y, row) in indices.chunks_exact(w as usize).enumerate() {
+        let out = &mut raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        for (x, &index) in row.iter().enumerate() {
+            let shift = 8 - bd as usize * (x % per_byte + 1);
+            out[x / per_byte] |= index << shift;
+        }
+    }
+    let mut z = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+    z.write_all(&raw).map_err(|e| format!("deflate failed: {}", e))?;
+    let idat = z.finish().map_err(|e| format!("deflate failed: {}", e))?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&w.to_be_bytes());
+    ihdr.extend_from_slice(&h.to_be_bytes());
+    ihdr.extend_from_slice(&[bd, COLOR_TYPE_INDEXED, 0, 0, 0]);
+    let plte: Vec<u8> = opts.palette.iter().flatten().copied().collect();
+    // Trailing opaque entries are implied, so tRNS stops at the last translucent one.
+    let trns_len = opts.alpha.iter().rposition(|&a| a != 255).map_or(0, |i| i + 1);
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    if let Some(intent) = opts.srgb {
+        write_chunk(&mut out, b"sRGB", &[intent]);
+    }
+    if let Some(phys) = opts.phys {
+        let mut data = Vec::with_capacity(9);
+        data.extend_from_slice(&phys.x.to_be_bytes());
+        data.extend_from_slice(&phys.y.to_be_bytes());
+        data.push(phys.unit);
+        write_chunk(&mut out, b"pHYs", &data);
+    }
+    write_chunk(&mut out, b"PLTE", &plte);
+    if trns_len > 0 {
+        write_chunk(&mut out, b"tRNS", &opts.alpha[..trns_len]);
+    }
+    write_chunk(&mut out, b"IDAT", &idat);
+    write_chunk(&mut out, b"IEND", &[]);
+    Ok(out)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_chunk(&mut out, kind, data);
+        out
+    }
+
+    /// 3x2, 2-bit, palette with a duplicate color in slots 1 and 2 and an
+    /// unused fourth slot; rows use the None and Up filters.
+    fn sample_png() -> Vec<u8> {
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&3u32.to_be_bytes());
+        ihdr.extend_from_slice(&2u32.to_be_bytes());
+        ihdr.extend_from_slice(&[2, 3, 0, 0, 0]);
+        // Row 0 indices 0,1,2 -> 00 01 10 00; row 1 indices 2,1,0 -> 10 01 00 00.
+        let row0 = 0b0001_1000u8;
+        let row1 = 0b1001_0000u8;
+        let raw = [0, row0, 2, row1.wrapping_sub(row0)];
+        let mut z = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        z.write_all(&raw).unwrap();
+        let idat = z.finish().unwrap();
+
+        let mut png = SIGNATURE.to_vec();
+        png.extend(chunk(b"IHDR", &ihdr));
+        png.extend(chunk(b"PLTE", &[0, 0, 0, 10, 20, 30, 10, 20, 30, 255, 0, 255]));
+        png.extend(chunk(b"tRNS", &[0]));
+        png.extend(chunk(b"IDAT", &idat));
+        png.extend(chunk(b"IEND", &[]));
+        png
+    }
+
+    #[test]
+    fn decodes_indices_and_full_palette() {
+        let img = decode_indexed(&sample_png()).unwrap();
+        assert_eq!((img.info.width, img.info.height, img.info.bit_depth), (3, 2, 2));
+        assert_eq!(img.indices, [0, 1, 2, 2, 1, 0]);
+        assert_eq!(img.info.palette.len(), 4);
+        assert_eq!(img.info.palette[1], img.info.palette[2]);
+        assert_eq!(img.info.alpha, [0, 255, 255, 255]);
+    }
+
+    #[test]
+    fn rejects_bad_crc_and_non_indexed() {
+        let mut png = sample_png();
+        let last = png.len() - 1;
+        png[last] ^= 1;
+        assert!(decode_indexed(&png).is_err());
+        assert!(decode_indexed(b"not a png").is_err());
+    }
+
+    #[test]
+    fn rejects_indices_past_the_palette() {
+        // The sample uses index 2; cut its palette down to two entries.
+        let mut png = SIGNATURE.to_vec();
+        for c in parse_chunks(&sample_png()).unwrap() {
+            let data = if c.is(b"PLTE") { &c.data[..6] } else { c.data };
+            png.extend(chunk(&c.kind, data));
+        }
+        let err = decode_indexed(&png).unwrap_err();
+        assert!(err.contains("index 2"), "{}", err);
+    }
+
+    #[test]
+    fn encode_round_trips_at_minimal_depth() {
+        let mut palette = vec![[0, 0, 0]; 16];
+        palette[3] = [255, 0, 255];
+        palette[4] = [255, 0, 255];
+        let opts = IndexedPngOptions {
+            width: 5,
+            height: 3,
+            palette: palette.clone(),
+            alpha: vec![0, 255, 255],
+            bit_depth: None,
+            phys: Some(PhysicalDims { x: 3779, y: 3779, unit: 1 }),
+            srgb: Some(0),
+        };
+        let indices: Vec<u8> = (0..15).map(|i| i % 16).collect();
+        let png = encode_indexed(&opts, &indices).unwrap();
+        assert_eq!(png, encode_indexed(&opts, &indices).unwrap());
+
+        let kinds: Vec<[u8; 4]> = parse_chunks(&png).unwrap().iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, [*b"IHDR", *b"sRGB", *b"pHYs", *b"PLTE", *b"tRNS", *b"IDAT", *b"IEND"]);
+        let img = decode_indexed(&png).unwrap();
+        assert_eq!(img.info.bit_depth, 4);
+        assert_eq!(img.indices, indices);
+        assert_eq!(img.info.palette, palette);
+        assert_eq!(img.info.alpha[..2], [0, 255]);
+
+        let too_small = IndexedPngOptions { bit_depth: Some(2), ..opts.clone() };
+        assert!(encode_indexed(&too_small, &indices).is_err());
+        let two = IndexedPngOptions { palette: vec![[0, 0, 0]; 2], alpha: Vec::new(), ..opts };
+        assert_eq!(decode_indexed(&encode_indexed(&two, &[0, 1, 1, 0, 1, 0, 0, 1, 1, 1, 0, 0, 0, 1, 0]).unwrap()).unwrap().info.bit_depth, 1);
+    }
+}
@@ -0,0 +1,238 @@
+//! Keeps the PNG chunks CDPaint doesn't understand across an open/save.
+//!
+//! The webview re-encodes images from the canvas, which drops every chunk a
+//! decomp repo or another tool put there (tEXt/zTXt/iTXt notes, private
+//! chunks). `keep_png_chunks` records the ancillary, safe-to-copy chunks of
+//! each PNG a window opens as its document, and `write_allowed_file` puts
+//! them back when that window saves the same path.
+//!
+//! Only safe-to-copy chunks are kept: the PNG spec says the others describe
+//! the image data and must be dropped once it has been modified. Each chunk
+//! remembers whether it sat before PLTE, between PLTE and IDAT or after IDAT,
+//! and is re-inserted in the same position and order.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{editor_meta, png};
+
+/// Documents remembered per window; the oldest is forgotten past this.
+const MAX_FILES_PER_WINDOW: usize = 64;
+/// Files whose kept chunks add up to more than this aren't remembered.
+const MAX_KEPT_BYTES: usize = 4 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkSlot {
+    /// After IHDR, before PLTE (or before IDAT when there is no PLTE).
+    BeforePlte,
+    /// Between PLTE and the first IDAT.
+    BeforeIdat,
+    /// After the IDAT run, before IEND.
+    AfterIdat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeptChunk {
+    pub kind: [u8; 4],
+    pub data: Vec<u8>,
+    pub slot: ChunkSlot,
+}
+
+/// Ancillary (lowercase first letter) and safe-to-copy (lowercase last
+/// letter).
+pub fn is_safe_to_copy(kind: &[u8; 4]) -> bool {
+    kind[0].is_ascii_lowercase() && kind[3].is_ascii_lowercase()
+}
+
+/// The chunks of `bytes` worth carrying over to a re-encoded copy. Our own
+/// `CDPaint` chunk is left out: each save writes it afresh or not at all.
+pub fn collect(bytes: &[u8]) -> Result<Vec<KeptChunk>, String> {
+    let mut slot = ChunkSlot::BeforePlte;
+    let mut kept = Vec::new();
+    for chunk in png::parse_chunks(bytes)? {
+        if chunk.is(b"PLTE") {
+            slot = ChunkSlot::BeforeIdat;
+        } else if chunk.is(b"IDAT") {
+            slot = ChunkSlot::AfterIdat;
+        } else if is_safe_to_copy(&chunk.kind) && !editor_meta::is_ours(&chunk) {
+            kept.push(KeptChunk {
+                kind: chunk.kind,
+                data: chunk.data.to_vec(),
+                slot,
+            });
+        }
+    }
+    Ok(kept)
+}
+
+fn is_text(kind: &[u8; 4]) -> bool {
+    matches!(kind, b"tEXt" | b"zTXt" | b"iTXt")
+}
+
+fn text_keyword(data: &[u8]) -> &[u8] {
+    data.split(|&b| b == 0).next().unwrap_or_default()
+}
+
+/// Whether the new file already has its own version of `kept`: the same
+/// chunk type, or for text chunks the same keyword.
+fn superseded(kept: &KeptChunk, chunk: &png::Chunk<'_>) -> bool {
+    if is_text(&kept.kind) {
+        is_text(&chunk.kind) && text_keyword(chunk.data) == text_keyword(&kept.data)
+    } else {
+        chunk.kind == kept.kind
+    }
+}
+
+/// Insert `kept` into the PNG `bytes`. Chunks `bytes` already carries win
+/// over the kept copies, so a fresh pHYs is never doubled up.
+pub fn reinsert(bytes: &[u8], kept: &[KeptChunk]) -> Result<Vec<u8>, String> {
+    let chunks = png::parse_chunks(bytes)?;
+    let kept: Vec<&KeptChunk> = kept
+        .iter()
+        .filter(|k| !chunks.iter().any(|c| superseded(k, c)))
+        .collect();
+    let has_plte = chunks.iter().any(|c| c.is(b"PLTE"));
+    let extra: usize = kept.iter().map(|k| k.data.len() + 12).sum();
+    let mut out = Vec::with_capacity(bytes.len() + extra);
+    out.extend_from_slice(&png::SIGNATURE);
+    let put = |out: &mut Vec<u8>, slot: ChunkSlot| {
+        for k in kept.iter().filter(|k| k.slot == slot) {
+            png::write_chunk(out, &k.kind, &k.data);
+        }
+    };
+    let mut seen_idat = false;
+    for chunk in &chunks {
+        if chunk.is(b"PLTE") {
+            put(&mut out, ChunkSlot::BeforePlte);
+        } else if chunk.is(b"IDAT") && !seen_idat {
+            seen_idat = true;
+            if !has_plte {
+                put(&mut out, ChunkSlot::BeforePlte);
+            }
+            put(&mut out, ChunkSlot::BeforeIdat);
+        } else if chunk.is(b"IEND") {
+            put(&mut out, ChunkSlot::AfterIdat);
+        }
+        png::write_chunk(&mut out, &chunk.kind, chunk.data);
+    }
+    Ok(out)
+}
+
+/// (canonical path, kept chunks) for the files one window has read, oldest first.
+type WindowFiles = Vec<(PathBuf, Vec<KeptChunk>)>;
+
+/// Window label -> the files that window has read.
+#[derive(Default)]
+pub struct KeptPngChunks(Mutex<HashMap<String, WindowFiles>>);
+
+impl KeptPngChunks {
+    /// Remember what `bytes` (just read from `path`) carries for `label`.
+    pub fn remember(&self, label: &str, path: &Path, bytes: &[u8]) {
+        let kept = collect(bytes).unwrap_or_default();
+        let too_big = kept.iter().map(|k| k.data.len()).sum::<usize>() > MAX_KEPT_BYTES;
+        let Ok(mut guard) = self.0.lock() else { return };
+        let files = guard.entry(label.to_string()).or_default();
+        files.retain(|(p, _)| p != path);
+        if kept.is_empty() || too_big {
+            return;
+        }
+        files.push((path.to_path_buf(), kept));
+        if files.len() > MAX_FILES_PER_WINDOW {
+            files.remove(0);
+        }
+    }
+
+    /// `bytes` about to be written to `path` by `label`, with that file's
+    /// kept chunks put back. `None` when nothing was kept for it or `bytes`
+    /// isn't a well-formed PNG.
+    pub fn restore(&self, label: &str, path: &Path, bytes: &[u8]) -> Option<Vec<u8>> {
+        let guard = self.0.lock().ok()?;
+        let (_, kept) = guard.get(label)?.iter().find(|(p, _)| p == path)?;
+        reinsert(bytes, kept).ok()
+    }
+
+    pub fn forget_window(&self, label: &str) {
+        if let Ok(mut guard) = self.0.lock() {
+            guard.remove(label);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::chunk;
+
+    fn file(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = png::SIGNATURE.to_vec();
+        for c in chunks {
+            out.extend_from_slice(c);
+        }
+        out
+    }
+
+    #[test]
+    fn kept_chunks_return_to_their_slots() {
+        let original = file(&[
+            chunk(b"IHDR", &[0; 13]),
+            chunk(b"tEXt", b"Title\0front"),
+            chunk(b"sRGB", &[0]),
+            chunk(b"PLTE", &[0; 6]),
+            chunk(b"prVt", b"decomp"),
+            chunk(b"tRNS", &[0]),
+            chunk(b"IDAT", b"old"),
+            chunk(b"zTXt", b"Note\0\0x"),
+            chunk(b"IEND", &[]),
+        ]);
+        let kept = collect(&original).unwrap();
+        let kinds: Vec<&[u8; 4]> = kept.iter().map(|k| &k.kind).collect();
+        // sRGB and tRNS describe the pixels and are not safe to copy.
+        assert_eq!(kinds, [b"tEXt", b"prVt", b"zTXt"]);
+
+        let saved = file(&[
+            chunk(b"IHDR", &[0; 13]),
+            chunk(b"PLTE", &[0; 6]),
+            chunk(b"IDAT", b"new"),
+            chunk(b"IDAT", b"more"),
+            chunk(b"IEND", &[]),
+        ]);
+        let merged = reinsert(&saved, &kept).unwrap();
+        let order: Vec<[u8; 4]> = png::parse_chunks(&merged).unwrap().iter().map(|c| c.kind).collect();
+        assert_eq!(
+            order,
+            [*b"IHDR", *b"tEXt", *b"PLTE", *b"prVt", *b"IDAT", *b"IDAT", *b"zTXt", *b"IEND"]
+        );
+
+        // Without a PLTE the pre-PLTE chunks still land ahead of the image data,
+        // and text the new file already has under the same keyword wins.
+        let rgba = file(&[
+            chunk(b"IHDR", &[0; 13]),
+            chunk(b"tEXt", b"Title\0back"),
+            chunk(b"IDAT", b"px"),
+            chunk(b"IEND", &[]),
+        ]);
+        let order: Vec<[u8; 4]> = png::parse_chunks(&reinsert(&rgba, &kept).unwrap())
+            .unwrap()
+            .iter()
+            .map(|c| c.kind)
+            .collect();
+        assert_eq!(order, [*b"IHDR", *b"tEXt", *b"prVt", *b"IDAT", *b"zTXt", *b"IEND"]);
+    }
+
+    #[test]
+    fn editor_metadata_is_not_kept() {
+        let mut ours = editor_meta::KEYWORD.to_vec();
+        ours.extend_from_slice(&[0, 0, 0, 0, 0]);
+        ours.extend_from_slice(b"{}");
+        let original = file(&[
+            chunk(b"IHDR", &[0; 13]),
+            chunk(b"iTXt", &ours),
+            chunk(b"iTXt", b"Comment\0\0\0\0\0kept"),
+            chunk(b"IDAT", b"px"),
+            chunk(b"IEND", &[]),
+        ]);
+        let kept = collect(&original).unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(text_keyword(&kept[0].data), b"Comment");
+    }
+}
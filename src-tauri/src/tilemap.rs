@@ -0,0 +1,188 @@
+//! Background tilemaps: an indexed image cut into 8x8 tiles, duplicates
+//! (also mirrored ones) folded together, and a GBA text-BG screen-entry map.
+//!
+//! Each map entry is a little-endian u16: tile number in bits 0-9, horizontal
+//! flip in bit 10, vertical flip in bit 11 and the palette bank in bits
+//! 12-15. Entries are row-major over the image, like the `.bin` tilemaps in
+//! pokeemerald. In 4bpp an index's high nibble picks the tile's palette bank,
+//! so a 256-color image built from sixteen 16-color palettes converts
+//! directly; tiles that only differ in bank share their tile data.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::gba_tiles;
+
+/// Tile numbers a screen entry can address.
+pub const TILE_LIMIT: usize = 1024;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TilemapOptions {
+    pub width: u32,
+    pub height: u32,
+    /// 4 or 8.
+    pub bit_depth: u8,
+    /// Bank for 4bpp images whose indices all fit in one 16-color palette.
+    #[serde(default)]
+    pub palette_bank: u8,
+    /// Match tiles against mirrored copies of earlier ones.
+    #[serde(default = "default_true")]
+    pub allow_flips: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TilemapInfo {
+    /// Tiles in the image.
+    pub tile_count: usize,
+    /// Tiles in the tileset after deduplication.
+    pub unique_tiles: usize,
+    pub tile_limit: usize,
+    /// More unique tiles than a screen entry can address; the map is
+    /// still produced but entries past the limit are wrong.
+    pub over_limit: bool,
+    /// Map size in tiles.
+    pub map_width: u32,
+    pub map_height: u32,
+    pub tileset_len: usize,
+    pub map_len: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tilemap {
+    pub info: TilemapInfo,
+    /// `.4bpp` / `.8bpp` tile data.
+    pub tileset: Vec<u8>,
+    /// Screen entries, the `.bin`.
+    pub map: Vec<u8>,
+}
+
+type Tile = [u8; 64];
+
+fn hflip(t: &Tile) -> Tile {
+    std::array::from_fn(|i| t[i / 8 * 8 + 7 - i % 8])
+}
+
+fn vflip(t: &Tile) -> Tile {
+    std::array::from_fn(|i| t[(7 - i / 8) * 8 + i % 8])
+}
+
+pub fn generate(opts: &TilemapOptions, indices: &[u8]) -> Result<Tilemap, String> {
+    let (w, h) = (opts.width as usize, opts.height as usize);
+    if !matches!(opts.bit_depth, 4 | 8) {
+        return Err(format!("GBA tiles are 4 or 8 bits per pixel, not {}", opts.bit_depth));
+    }
+    if w == 0 || h == 0 || !w.is_multiple_of(8) || !h.is_multiple_of(8) {
+        return Err(format!("image size {}x{} is not a multiple of 8", w, h));
+    }
+    if indices.len() != w * h {
+        return Err("index buffer does not match the image size".into());
+    }
+    if opts.palette_bank > 15 {
+        return Err("palette bank must be 0 to 15".into());
+    }
+    let banked = opts.bit_depth == 4 && indices.iter().any(|&i| i > 15);
+
+    let mut unique: Vec<Tile> = Vec::new();
+    let mut lookup: HashMap<Tile, usize> = HashMap::new();
+    let mut map = Vec::with_capacity(w * h / 32);
+    for ty in (0..h).step_by(8) {
+        for tx in (0..w).step_by(8) {
+            let raw: Tile = std::array::from_fn(|i| indices[(ty + i / 8) * w + tx + i % 8]);
+            let mut bank = if banked { None } else { Some(opts.palette_bank) };
+            let mut tile = raw;
+            if banked {
+                // Color 0 is transparent in every bank, so it doesn't pin one.
+                for &i in raw.iter().filter(|&&i| i & 0xF != 0) {
+                    match bank {
+                        None => bank = Some(i >> 4),
+                        Some(b) if b != i >> 4 => {
+                            return Err(format!("tile at {},{} mixes palette banks {} and {}", tx, ty, b, i >> 4));
+                        }
+                        _ => {}
+                    }
+                }
+                tile = raw.map(|i| i & 0xF);
+            }
+            let bank = bank.unwrap_or(0) as u16;
+
+            let candidates = if opts.allow_flips {
+                vec![(tile, 0u16), (hflip(&tile), 1 << 10), (vflip(&tile), 1 << 11), (vflip(&hflip(&tile)), 3 << 10)]
+            } else {
+                vec![(tile, 0u16)]
+            };
+            let (number, flips) = match candidates.iter().find_map(|(t, f)| lookup.get(t).map(|&n| (n, *f))) {
+                Some(found) => found,
+                None => {
+                    lookup.insert(tile, unique.len());
+                    unique.push(tile);
+                    (unique.len() - 1, 0)
+                }
+            };
+            let entry = (number as u16 & 0x3FF) | flips | bank << 12;
+            map.extend_from_slice(&entry.to_le_bytes());
+        }
+    }
+
+    let strip: Vec<u8> = unique.iter().flatten().copied().collect();
+    let tileset = gba_tiles::encode_tiles(8, unique.len() * 8, opts.bit_depth, &strip)?;
+    Ok(Tilemap {
+        info: TilemapInfo {
+            tile_count: w * h / 64,
+            unique_tiles: unique.len(),
+            tile_limit: TILE_LIMIT,
+            over_limit: unique.len() > TILE_LIMIT,
+            map_width: opts.width / 8,
+            map_height: opts.height / 8,
+            tileset_len: tileset.len(),
+            map_len: map.len(),
+        },
+        tileset,
+        map,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(map: &[u8]) -> Vec<u16> {
+        map.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect()
+    }
+
+    #[test]
+    fn mirrored_tiles_share_one_tileset_entry() {
+        // Four tiles side by side: a pattern, its horizontal mirror, its
+        // vertical mirror, and the pattern again in palette bank 2.
+        let pattern: Tile = std::array::from_fn(|i| if i % 8 < i / 8 { 3 } else { (i % 3) as u8 });
+        let tiles = [pattern, hflip(&pattern), vflip(&pattern), pattern.map(|i| if i == 0 { 0 } else { 0x20 | i })];
+        let mut indices = vec![0u8; 32 * 8];
+        for (n, t) in tiles.iter().enumerate() {
+            for (i, &v) in t.iter().enumerate() {
+                indices[(i / 8) * 32 + n * 8 + i % 8] = v;
+            }
+        }
+        let opts = TilemapOptions {
+            width: 32,
+            height: 8,
+            bit_depth: 4,
+            palette_bank: 0,
+            allow_flips: true,
+        };
+        let out = generate(&opts, &indices).unwrap();
+        assert_eq!(out.info.unique_tiles, 1);
+        assert_eq!(out.tileset.len(), 32);
+        assert_eq!(entries(&out.map), [0x0000, 0x0400, 0x0800, 0x2000]);
+
+        let no_flips = generate(&TilemapOptions { allow_flips: false, ..opts.clone() }, &indices).unwrap();
+        assert_eq!(no_flips.info.unique_tiles, 3);
+        assert_eq!(entries(&no_flips.map), [0, 1, 2, 0x2000]);
+
+        // A tile drawing from two banks can't be expressed.
+        indices[0] = 0x11;
+        indices[1] = 0x21;
+        assert!(generate(&opts, &indices).is_err());
+    }
+}
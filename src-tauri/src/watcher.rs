@@ -0,0 +1,293 @@
+//! Watches the file open in each window and each window's hooked project
+//! root, so edits made outside CDPaint (a `git pull`, a `make` run that
+//! regenerates assets) reach the frontend.
+//!
+//! Events are debounced and sent only to the window that cares:
+//! - `file-changed` / `file-removed` with `{ path }` for a window's document;
+//! - `project-tree-changed` with `{ root, paths }` listing the changed
+//!   directories and project files, so the tree can be patched in place.
+//!
+//! Writes CDPaint makes itself are recorded with `note_own_write` and not
+//! echoed back as external changes.
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+
+use crate::grants;
+
+const DEBOUNCE: Duration = Duration::from_millis(400);
+/// How long after our own write an event on that path is ignored.
+const OWN_WRITE_GRACE: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+struct WatchState {
+    debouncer: Option<Debouncer<RecommendedWatcher>>,
+    /// Window label -> document path.
+    documents: HashMap<String, PathBuf>,
+    /// Window label -> hooked project root.
+    projects: HashMap<String, PathBuf>,
+    /// Paths currently registered with the OS watcher.
+    watched: HashMap<PathBuf, RecursiveMode>,
+    own_writes: HashMap<PathBuf, Instant>,
+}
+
+#[derive(Default)]
+pub struct FileWatcher(Mutex<WatchState>);
+
+#[derive(Debug, Clone, Serialize)]
+struct FileEventPayload {
+    path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ProjectTreePayload {
+    root: String,
+    paths: Vec<String>,
+}
+
+/// The files `scan_dir` lists: PNGs and palettes in any format.
+fn is_project_file(path: &Path) -> bool {
+    let is_png = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("png"));
+    is_png || crate::palette::is_palette_file(path)
+}
+
+/// Atomic-save temp files, originals kept aside during bundle saves and
+/// rotated backups come and go on every save.
+fn is_scratch_file(path: &Path) -> bool {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    ["tmp", "orig", "bak"].iter().any(|s| ext.eq_ignore_ascii_case(s))
+}
+
+fn is_denylisted(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root).is_ok_and(|rel| {
+        rel.components().any(|c| {
+            let name = c.as_os_str().to_string_lossy();
+            crate::SCAN_DIR_DENYLIST.iter().any(|d| d.eq_ignore_ascii_case(&name))
+        })
+    })
+}
+
+impl WatchState {
+    /// The OS watches we need: each document's directory (atomic saves
+    /// replace the file, so watching the file itself would lose track of
+    /// it) plus each project root recursively.
+    fn wanted(&self) -> HashMap<PathBuf, RecursiveMode> {
+        let mut out = HashMap::new();
+        for root in self.projects.values() {
+            out.insert(root.clone(), RecursiveMode::Recursive);
+        }
+        for doc in self.documents.values() {
+            if let Some(dir) = doc.parent() {
+                out.entry(dir.to_path_buf()).or_insert(RecursiveMode::NonRecursive);
+            }
+        }
+        out
+    }
+
+    fn sync_watches(&mut self) -> Result<(), String> {
+        let wanted = self.wanted();
+        let Some(debouncer) = self.debouncer.as_mut() else {
+            return Err("file watcher unavailable".into());
+        };
+        let watcher = debouncer.watcher();
+        for (path, mode) in &self.watched {
+            if wanted.get(path) != Some(mode) {
+                let _ = notify::Watcher::unwatch(watcher, path);
+            }
+        }
+        let mut registered = HashMap::new();
+        let mut first_error = None;
+        for (path, mode) in wanted {
+            if self.watched.get(&path) == Some(&mode) {
+                registered.insert(path, mode);
+                continue;
+            }
+            match notify::Watcher::watch(watcher, &path, mode) {
+                Ok(()) => {
+                    registered.insert(path, mode);
+                }
+                Err(e) => {
+                    first_error.get_or_insert(format!("watch failed ({}): {}", path.display(), e));
+                }
+            }
+        }
+        self.watched = registered;
+        first_error.map_or(Ok(()), Err)
+    }
+
+    fn is_own_write(&mut self, path: &Path) -> bool {
+        let now = Instant::now();
+        self.own_writes.retain(|_, at| now.duration_since(*at) < OWN_WRITE_GRACE);
+        self.own_writes.contains_key(path)
+    }
+}
+
+/// Start the debouncer. Called once from `setup()`.
+pub fn init(app: &tauri::AppHandle) -> Result<(), String> {
+    let handle = app.clone();
+    let debouncer = new_debouncer(DEBOUNCE, move |res: DebounceEventResult| {
+        if let Ok(events) = res {
+            let paths: HashSet<PathBuf> = events.into_iter().map(|e| grants::canonical_path(&e.path)).collect();
+            dispatch(&handle, paths);
+        }
+    })
+    .map_err(|e| format!("start file watcher failed: {}", e))?;
+    let state = app.state::<FileWatcher>();
+    let mut guard = state.0.lock().map_err(|_| "file watcher state poisoned".to_string())?;
+    guard.debouncer = Some(debouncer);
+    Ok(())
+}
+
+fn dispatch(app: &tauri::AppHandle, paths: HashSet<PathBuf>) {
+    let mut doc_events: Vec<(String, &'static str, PathBuf)> = Vec::new();
+    let mut tree_events: Vec<(String, PathBuf, Vec<String>)> = Vec::new();
+    {
+        let state = app.state::<FileWatcher>();
+        let Ok(mut guard) = state.0.lock() else { return };
+        let paths: Vec<PathBuf> = paths.into_iter().filter(|p| !guard.is_own_write(p)).collect();
+        for (label, doc) in &guard.documents {
+            if paths.iter().any(|p| p == doc) {
+                let event = if doc.exists() { "file-changed" } else { "file-removed" };
+                doc_events.push((label.clone(), event, doc.clone()));
+            }
+        }
+        for (label, root) in &guard.projects {
+            let mut changed: Vec<String> = paths
+                .iter()
+                .filter(|p| p.starts_with(root) && !is_denylisted(root, p))
+                .filter(|p| is_project_file(p) || p.is_dir() || (!p.exists() && !is_scratch_file(p)))
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+            if !changed.is_empty() {
+                changed.sort();
+                tree_events.push((label.clone(), root.clone(), changed));
+            }
+        }
+    }
+    for (label, event, path) in doc_events {
+        let payload = FileEventPayload {
+            path: path.to_string_lossy().to_string(),
+        };
+        let _ = app.emit_to(label.as_str(), event, payload);
+    }
+    for (label, root, paths) in tree_events {
+        let payload = ProjectTreePayload {
+            root: root.to_string_lossy().to_string(),
+            paths,
+        };
+        let _ = app.emit_to(label.as_str(), "project-tree-changed", payload);
+    }
+}
+
+/// Remember that CDPaint itself just wrote `path`.
+pub fn note_own_write(app: &tauri::AppHandle, path: &Path) {
+    if let Ok(mut guard) = app.state::<FileWatcher>().0.lock() {
+        guard.own_writes.insert(grants::canonical_path(path), Instant::now());
+    }
+}
+
+/// Drop everything a closed window was watching.
+pub fn forget_window(app: &tauri::AppHandle, label: &str) {
+    if let Ok(mut guard) = app.state::<FileWatcher>().0.lock() {
+        let had_doc = guard.documents.remove(label).is_some();
+        let had_project = guard.projects.remove(label).is_some();
+        if had_doc || had_project {
+            let _ = guard.sync_watches();
+        }
+    }
+}
+
+fn update(state: &FileWatcher, apply: impl FnOnce(&mut WatchState)) -> Result<(), String> {
+    let mut guard = state.0.lock().map_err(|_| "file watcher state poisoned".to_string())?;
+    apply(&mut guard);
+    guard.sync_watches()
+}
+
+#[tauri::command]
+pub fn watch_document(
+    window: tauri::Window,
+    state: tauri::State<'_, FileWatcher>,
+    grants: tauri::State<'_, grants::FileGrants>,
+    path: String,
+) -> Result<(), String> {
+    let p = crate::normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    if !p.is_file() {
+        return Err("path is not an existing file".into());
+    }
+    let p = grants::canonical_path(&p);
+    let label = window.label().to_string();
+    update(&state, |s| {
+        s.documents.insert(label, p);
+    })
+}
+
+#[tauri::command]
+pub fn unwatch_document(window: tauri::Window, state: tauri::State<'_, FileWatcher>) -> Result<(), String> {
+    let label = window.label().to_string();
+    update(&state, |s| {
+        s.documents.remove(&label);
+    })
+}
+
+#[tauri::command]
+pub fn watch_project(
+    window: tauri::Window,
+    state: tauri::State<'_, FileWatcher>,
+    grants: tauri::State<'_, grants::FileGrants>,
+    path: String,
+) -> Result<(), String> {
+    let p = crate::normalize_to_absolute_path(&path)?;
+    grants.check(&p)?;
+    if !p.is_dir() {
+        return Err("path is not an existing directory".into());
+    }
+    let p = grants::canonical_path(&p);
+    let label = window.label().to_string();
+    update(&state, |s| {
+        s.projects.insert(label, p);
+    })
+}
+
+#[tauri::command]
+pub fn unwatch_project(window: tauri::Window, state: tauri::State<'_, FileWatcher>) -> Result<(), String> {
+    let label = window.label().to_string();
+    update(&state, |s| {
+        s.projects.remove(&label);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wanted_watches_document_dirs_and_project_roots() {
+        let mut state = WatchState::default();
+        state.documents.insert("main".into(), PathBuf::from("/proj/graphics/front.png"));
+        state.documents.insert("file-1".into(), PathBuf::from("/proj/graphics/back.png"));
+        state.projects.insert("main".into(), PathBuf::from("/proj"));
+        let wanted = state.wanted();
+        assert_eq!(wanted.len(), 2);
+        assert_eq!(wanted.get(Path::new("/proj")), Some(&RecursiveMode::Recursive));
+        assert_eq!(wanted.get(Path::new("/proj/graphics")), Some(&RecursiveMode::NonRecursive));
+    }
+
+    #[test]
+    fn project_filter_skips_denylisted_dirs_and_other_files() {
+        let root = Path::new("/proj");
+        assert!(is_denylisted(root, Path::new("/proj/.git/index")));
+        assert!(!is_denylisted(root, Path::new("/proj/graphics/front.png")));
+        assert!(is_project_file(Path::new("/proj/a/normal.PAL")));
+        assert!(is_project_file(Path::new("/proj/a/shiny.gbapal")));
+        assert!(!is_project_file(Path::new("/proj/a/missing-notes.txt")));
+        assert!(!is_project_file(Path::new("/proj/a/front.png.1.bak")));
+        assert!(is_scratch_file(Path::new("/proj/a/.front.png.0f3a.tmp")));
+        assert!(is_scratch_file(Path::new("/proj/a/.normal.pal.0f3a.orig")));
+    }
+}
@@ -0,0 +1,222 @@
+//! Minimal read-only ZIP parser for OpenRaster archives.
+//!
+//! Only what ORA needs is supported: single-disk archives, stored (0) and
+//! deflate (8) entries, no encryption and no Zip64. Everything is bounds
+//! checked against the input slice; nothing is trusted from the headers.
+use std::io::Read;
+
+const EOCD_SIG: u32 = 0x0605_4b50;
+const CENTRAL_SIG: u32 = 0x0201_4b50;
+const LOCAL_SIG: u32 = 0x0403_4b50;
+const EOCD_LEN: usize = 22;
+const CENTRAL_LEN: usize = 46;
+pub const LOCAL_LEN: usize = 30;
+
+pub const METHOD_STORED: u16 = 0;
+pub const METHOD_DEFLATE: u16 = 8;
+
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    pub name: String,
+    pub method: u16,
+    pub flags: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub local_header_offset: u64,
+    /// Length of the extra field in the local header (not the central one).
+    pub local_extra_len: usize,
+    data_offset: usize,
+}
+
+/// Caps applied before anything is inflated, so a zip bomb is refused from
+/// its headers alone (`read` separately stops at each entry's declared size).
+pub struct ZipLimits {
+    pub max_entries: usize,
+    pub max_total_size: u64,
+    /// Largest allowed uncompressed/compressed ratio for a single entry.
+    pub max_ratio: u64,
+}
+
+/// Entry names must stay inside the archive: relative, `/`-separated, and
+/// free of `..`, drive prefixes and backslashes.
+pub fn is_safe_entry_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('/')
+        && !name.contains('\\')
+        && !name.contains(':')
+        && !name.contains('\0')
+        && name.split('/').all(|part| part != "..")
+}
+
+pub struct ZipArchive<'a> {
+    data: &'a [u8],
+    entries: Vec<ZipEntry>,
+}
+
+fn u16_at(b: &[u8], off: usize) -> Option<u16> {
+    b.get(off..off + 2).map(|s| u16::from_le_bytes([s[0], s[1]]))
+}
+
+fn u32_at(b: &[u8], off: usize) -> Option<u32> {
+    b.get(off..off + 4).map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+fn truncated() -> String {
+    "zip archive is truncated".to_string()
+}
+
+impl<'a> ZipArchive<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, String> {
+        if data.len() < EOCD_LEN {
+            return Err("not a zip archive".into());
+        }
+        // The EOCD record sits at the end, followed by a comment of up to 64 KiB.
+        let min_start = data.len().saturating_sub(EOCD_LEN + u16::MAX as usize);
+        let eocd = (min_start..=data.len() - EOCD_LEN)
+            .rev()
+            .find(|&i| u32_at(data, i) == Some(EOCD_SIG))
+            .ok_or_else(|| "not a zip archive".to_string())?;
+        let disk = u16_at(data, eocd + 4).ok_or_else(truncated)?;
+        let cd_disk = u16_at(data, eocd + 6).ok_or_else(truncated)?;
+        if disk != 0 || cd_disk != 0 {
+            return Err("multi-disk zip archives are not supported".into());
+        }
+        let count = u16_at(data, eocd + 10).ok_or_else(truncated)? as usize;
+        let cd_size = u32_at(data, eocd + 12).ok_or_else(truncated)? as usize;
+        let cd_offset = u32_at(data, eocd + 16).ok_or_else(truncated)?;
+        if cd_offset == u32::MAX || count == u16::MAX as usize {
+            return Err("zip64 archives are not supported".into());
+        }
+        let cd_offset = cd_offset as usize;
+        if cd_offset.checked_add(cd_size).is_none_or(|end| end > eocd) {
+            return Err("zip central directory is out of bounds".into());
+        }
+
+        let mut entries = Vec::with_capacity(count);
+        let mut pos = cd_offset;
+        for _ in 0..count {
+            if u32_at(data, pos) != Some(CENTRAL_SIG) {
+                return Err("corrupt zip central directory".into());
+            }
+            let flags = u16_at(data, pos + 8).ok_or_else(truncated)?;
+            let method = u16_at(data, pos + 10).ok_or_else(truncated)?;
+            let crc32 = u32_at(data, pos + 16).ok_or_else(truncated)?;
+            let compressed_size = u32_at(data, pos + 20).ok_or_else(truncated)?;
+            let uncompressed_size = u32_at(data, pos + 24).ok_or_else(truncated)?;
+            let name_len = u16_at(data, pos + 28).ok_or_else(truncated)? as usize;
+            let extra_len = u16_at(data, pos + 30).ok_or_else(truncated)? as usize;
+            let comment_len = u16_at(data, pos + 32).ok_or_else(truncated)? as usize;
+            let local_header_offset = u32_at(data, pos + 42).ok_or_else(truncated)?;
+            if compressed_size == u32::MAX || uncompressed_size == u32::MAX || local_header_offset == u32::MAX {
+                return Err("zip64 archives are not supported".into());
+            }
+            let name_bytes = data
+                .get(pos + CENTRAL_LEN..pos + CENTRAL_LEN + name_len)
+                .ok_or_else(truncated)?;
+            let name = String::from_utf8(name_bytes.to_vec())
+                .map_err(|_| "zip entry name is not valid UTF-8".to_string())?;
+            pos += CENTRAL_LEN + name_len + extra_len + comment_len;
+
+            let lh = local_header_offset as usize;
+            if u32_at(data, lh) != Some(LOCAL_SIG) {
+                return Err(format!("corrupt local header for {}", name));
+            }
+            let local_name_len = u16_at(data, lh + 26).ok_or_else(truncated)? as usize;
+            let local_extra_len = u16_at(data, lh + 28).ok_or_else(truncated)? as usize;
+            let local_name = data
+                .get(lh + LOCAL_LEN..lh + LOCAL_LEN + local_name_len)
+                .ok_or_else(truncated)?;
+            if local_name != name_bytes {
+                return Err(format!("local header name mismatch for {}", name));
+            }
+            let data_offset = lh + LOCAL_LEN + local_name_len + local_extra_len;
+            if data_offset
+                .checked_add(compressed_size as usize)
+                .is_none_or(|end| end > data.len())
+            {
+                return Err(format!("zip entry data out of bounds: {}", name));
+            }
+            entries.push(ZipEntry {
+                name,
+                method,
+                flags,
+                crc32,
+                compressed_size: compressed_size as u64,
+                uncompressed_size: uncompressed_size as u64,
+                local_header_offset: local_header_offset as u64,
+                local_extra_len,
+                data_offset,
+            });
+        }
+        Ok(ZipArchive { data, entries })
+    }
+
+    pub fn enforce_limits(&self, limits: &ZipLimits) -> Result<(), String> {
+        if self.entries.len() > limits.max_entries {
+            return Err("zip archive has too many entries".into());
+        }
+        let mut total: u64 = 0;
+        for entry in &self.entries {
+            if !is_safe_entry_name(&entry.name) {
+                return Err(format!("zip entry path escapes the archive: {}", entry.name));
+            }
+            let ratio = entry.uncompressed_size / entry.compressed_size.max(1);
+            if ratio > limits.max_ratio {
+                return Err(format!("zip entry compression ratio too high: {}", entry.name));
+            }
+            total = total.saturating_add(entry.uncompressed_size);
+        }
+        if total > limits.max_total_size {
+            return Err("zip archive expands beyond the size limit".into());
+        }
+        Ok(())
+    }
+
+    pub fn find(&self, name: &str) -> Option<&ZipEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// The entry whose local header starts the archive.
+    pub fn first_local_entry(&self) -> Option<&ZipEntry> {
+        self.entries.iter().find(|e| e.local_header_offset == 0)
+    }
+
+    /// Raw (possibly compressed) bytes of an entry as stored in the archive.
+    pub fn raw_data(&self, entry: &ZipEntry) -> &'a [u8] {
+        &self.data[entry.data_offset..entry.data_offset + entry.compressed_size as usize]
+    }
+
+    /// Decompress an entry, refusing to produce more than `max_size` bytes and
+    /// verifying both the declared size and the CRC.
+    pub fn read(&self, entry: &ZipEntry, max_size: u64) -> Result<Vec<u8>, String> {
+        if entry.flags & 0x1 != 0 {
+            return Err(format!("encrypted zip entry: {}", entry.name));
+        }
+        if entry.uncompressed_size > max_size {
+            return Err(format!("zip entry too large: {}", entry.name));
+        }
+        let raw = self.raw_data(entry);
+        let out = match entry.method {
+            METHOD_STORED => raw.to_vec(),
+            METHOD_DEFLATE => {
+                let mut out = Vec::with_capacity(entry.uncompressed_size as usize);
+                // Read one byte past the declared size so a lying header is
+                // caught without inflating an unbounded stream.
+                flate2::read::DeflateDecoder::new(raw)
+                    .take(entry.uncompressed_size + 1)
+                    .read_to_end(&mut out)
+                    .map_err(|e| format!("inflate failed ({}): {}", entry.name, e))?;
+                out
+            }
+            m => return Err(format!("unsupported zip compression method {} ({})", m, entry.name)),
+        };
+        if out.len() as u64 != entry.uncompressed_size {
+            return Err(format!("zip entry size mismatch: {}", entry.name));
+        }
+        if crc32fast::hash(&out) != entry.crc32 {
+            return Err(format!("zip entry CRC mismatch: {}", entry.name));
+        }
+        Ok(out)
+    }
+}
@@ -1629,11 +1629,22 @@
             tCanvas.getContext('2d').drawImage(mergeCanvas, 0, 0, tw, th);
             entries.push({ name: 'Thumbnails/thumbnail.png', data: await _canvasToPngBytes(tCanvas), store: false });
 
-            /* Build ZIP and download */
+            /* Build ZIP; desktop writes it through the Rust file commands
+               (which validate the archive), the browser downloads it */
             const zipBytes = await _oraZip.build(entries);
-            const blob = new Blob([zipBytes], { type: 'image/openraster' });
             const baseName = (this.state.fileName || 'untitled').replace(/\.[^/.]+$/, '');
             const fname = baseName + '.ora';
+            if (this.getTauriInvokeFn && this.getTauriInvokeFn()) {
+                const dir = await this.tauriWriteExportFileWithSaveDialog({ name: fname, bytes: zipBytes }, this.state.exportDir || '');
+                if (dir === null) return;
+                if (dir !== undefined) {
+                    if (dir) this.state.exportDir = dir;
+                    this.markSaved(fname);
+                    this.resetSaveReminderTimer();
+                    return;
+                }
+            }
+            const blob = new Blob([zipBytes], { type: 'image/openraster' });
             const url = URL.createObjectURL(blob);
             const a = document.createElement('a');
             a.href = url; a.download = fname; a.click();
@@ -2790,6 +2790,7 @@
             this.initRibbonCustomization();
             this.initTabScrollSwitch();
             this.initSaveReminder();
+            this.initBackupControls();
             this.initGridlines();
             this.initTileMode();
             this.initHistoryLimitControls();
@@ -2799,7 +2800,8 @@
             // Mark app as ready — fades out the startup loading bar
             document.body.classList.add('app-ready');
             this.revealStartupWindow();
-            Promise.resolve(this.initTauriFileOpenListener());
+            Promise.resolve(this.initTauriFileOpenListener()).then(() => this.offerRecoveredDocuments());
+            this.initAutosave();
             this.setActiveTab('home');
         }
 
@@ -3757,6 +3759,203 @@
             if (minsInput) minsInput.value = mins;
             this.applySaveReminderTimer();
         }
+        /* Rotating .bak copies are kept by the Rust save path (src-tauri
+           atomic_save.rs); the count lives in the app config, not localStorage. */
+        async initBackupControls() {
+            const input = document.getElementById('backup-generations');
+            const button = document.getElementById('backup-restore-btn');
+            if (!input) return;
+            if (!this.getTauriInvokeFn()) {
+                input.disabled = true;
+                if (button) button.disabled = true;
+                return;
+            }
+            try {
+                const settings = await this.tauriInvoke('get_backup_settings');
+                input.value = settings.generations;
+            } catch (e) {
+                console.warn('Backup settings unavailable', e);
+            }
+            input.addEventListener('change', () => this.setBackupGenerations(input.value));
+        }
+        async setBackupGenerations(value) {
+            const input = document.getElementById('backup-generations');
+            let generations = parseInt(value, 10);
+            if (!Number.isFinite(generations) || generations < 0) generations = 0;
+            try {
+                const saved = await this.tauriInvoke('set_backup_settings', { settings: { generations } });
+                if (input) input.value = saved.generations;
+            } catch (e) {
+                showToast('Could not save backup settings: ' + this.getErrorText(e), 'error');
+            }
+        }
+        async openBackupsModal() {
+            const modal = document.getElementById('modal-backups');
+            const list = document.getElementById('backups-list');
+            if (!modal || !list) return;
+            const path = this.state.filePath;
+            if (!this.getTauriInvokeFn() || !path) {
+                showToast('Backups are kept for files saved from the desktop app', 'warning');
+                return;
+            }
+            let backups;
+            try {
+                backups = await this.tauriInvoke('list_file_backups', { path });
+            } catch (e) {
+                showToast('Could not list backups: ' + this.getErrorText(e), 'error');
+                return;
+            }
+            list.innerHTML = '';
+            if (!backups.length) {
+                const empty = document.createElement('div');
+                empty.textContent = `No backups of ${this.getFilenameFromPath(path)} yet.`;
+                list.appendChild(empty);
+            }
+            backups.forEach((b) => {
+                const row = document.createElement('div');
+                row.style.display = 'flex';
+                row.style.alignItems = 'center';
+                row.style.gap = '8px';
+                row.style.margin = '4px 0';
+                const label = document.createElement('span');
+                label.style.flex = '1';
+                const when = b.modified_ms ? new Date(b.modified_ms).toLocaleString() : 'unknown date';
+                label.textContent = `#${b.generation} \u2014 ${when}, ${this.formatBytes(b.size)}`;
+                label.title = b.path;
+                const restore = document.createElement('button');
+                restore.textContent = 'Restore';
+                restore.onclick = () => {
+                    modal.style.display = 'none';
+                    const run = () => this.restoreFileBackup(path, b.generation);
+                    if (this.hasUnsavedChanges()) this.showOpenConfirm(run, ' before restoring a backup');
+                    else run();
+                };
+                row.appendChild(label);
+                row.appendChild(restore);
+                list.appendChild(row);
+            });
+            document.getElementById('backups-close').onclick = () => { modal.style.display = 'none'; };
+            modal.style.display = 'flex';
+        }
+        /* The current file goes through the normal rotation before it is
+           replaced, so restoring can itself be undone from the list. */
+        async restoreFileBackup(path, generation) {
+            try {
+                await this.tauriInvoke('restore_file_backup', { path, generation });
+            } catch (e) {
+                showToast('Restore failed: ' + this.getErrorText(e), 'error');
+                return;
+            }
+            await this.openFileFromPath(path, true);
+            showToast(`Restored backup #${generation}`, 'info');
+        }
+        /* Crash recovery (src-tauri autosave.rs): while the document has unsaved
+           changes it is snapshotted as ORA into this window's autosave slot.
+           Saving clears the slot; a slot still there at the next start belongs
+           to a session that crashed and is offered back. */
+        initAutosave() {
+            if (!this.getTauriInvokeFn() || this._autosaveTimer) return;
+            // Once a minute; a snapshot is skipped when nothing changed.
+            this._autosaveTimer = setInterval(() => this.writeAutosave(), 60 * 1000);
+        }
+        async writeAutosave() {
+            if (this._autosaveBusy || !this.state.hasDocument || !this.hasUnsavedChanges()) return;
+            if (this.state.isFileLoading || !this.buildORABytes) return;
+            // Nothing new since the last snapshot.
+            if (this._autosaveStep === this.state.step) return;
+            this._autosaveBusy = true;
+            const step = this.state.step;
+            try {
+                const bytes = await this.buildORABytes();
+                await this.tauriInvoke('write_autosave', bytes, this.tauriBinaryHeaders({
+                    mode: `${this.bitDepth}bpp`,
+                    format: 'ora',
+                    'original-path': this.state.filePath || this.state.fileName || ''
+                }));
+                this._autosaveStep = step;
+            } catch (e) {
+                console.warn('Autosave failed', e);
+            } finally {
+                this._autosaveBusy = false;
+            }
+        }
+        clearAutosave() {
+            this._autosaveStep = null;
+            if (!this.getTauriInvokeFn()) return;
+            Promise.resolve(this.tauriInvoke('clear_autosave')).catch(() => {});
+        }
+        async offerRecoveredDocuments() {
+            const modal = document.getElementById('modal-recover');
+            const list = document.getElementById('recover-list');
+            if (!modal || !list || !this.getTauriInvokeFn()) return;
+            let docs;
+            try {
+                docs = await this.tauriInvoke('list_recoverable_documents');
+            } catch (e) {
+                return;
+            }
+            if (!docs || !docs.length) return;
+            const close = () => { modal.style.display = 'none'; };
+            const render = () => {
+                list.innerHTML = '';
+                if (!docs.length) {
+                    close();
+                    return;
+                }
+                docs.forEach((doc) => {
+                    const m = doc.manifest;
+                    const row = document.createElement('div');
+                    row.style.display = 'flex';
+                    row.style.alignItems = 'center';
+                    row.style.gap = '8px';
+                    row.style.margin = '4px 0';
+                    const label = document.createElement('span');
+                    label.style.flex = '1';
+                    const name = m.original_path ? this.getFilenameFromPath(m.original_path) : 'untitled';
+                    label.textContent = `${name} \u2014 ${new Date(m.timestamp_ms).toLocaleString()}`;
+                    label.title = m.original_path || '';
+                    const restore = document.createElement('button');
+                    restore.textContent = 'Restore';
+                    restore.onclick = async () => {
+                        close();
+                        await this.restoreRecoveredDocument(doc);
+                    };
+                    const discard = document.createElement('button');
+                    discard.textContent = 'Discard';
+                    discard.onclick = async () => {
+                        await Promise.resolve(this.tauriInvoke('discard_recoverable_document', { id: doc.id })).catch(() => {});
+                        docs = docs.filter((d) => d !== doc);
+                        render();
+                    };
+                    row.appendChild(label);
+                    row.appendChild(restore);
+                    row.appendChild(discard);
+                    list.appendChild(row);
+                });
+            };
+            render();
+            document.getElementById('recover-later').onclick = close;
+            modal.style.display = 'flex';
+        }
+        /* The recovered snapshot opens as an unsaved document named after the
+           file it came from; Save As puts it back on disk. */
+        async restoreRecoveredDocument(doc) {
+            const m = doc.manifest;
+            try {
+                const data = await this.tauriInvoke('restore_document', { id: doc.id });
+                const bytes = data instanceof Uint8Array ? data : new Uint8Array(data);
+                const name = m.original_path ? this.getFilenameFromPath(m.original_path) : 'untitled.png';
+                const base = name.replace(/\.[^/.]+$/, '');
+                await this.loadORAFile(new File([bytes], `${base}.ora`, { type: 'image/openraster' }));
+                this.state.fileName = name;
+                this.state.isDirty = true;
+                this.updateTitleFilename();
+                await Promise.resolve(this.tauriInvoke('discard_recoverable_document', { id: doc.id })).catch(() => {});
+                showToast(`Recovered ${name}`, 'info');
+            } catch (e) {
+                showToast('Recovery failed: ' + this.getErrorText(e), 'error');
+            }
+        }
         initHistoryLimitControls() {
             const savedEnabled = this.lsGet('paint.history.limit.enabled');
             const savedLimit = parseInt(this.lsGet('paint.history.limit.value') || '', 10);
@@ -3867,6 +4066,8 @@
                 this.initializeBlankDocument();
                 return Promise.resolve();
             }
+            tauri.event.listen('file-changed', (event) => this.onDocumentChangedOnDisk(event && event.payload, false));
+            tauri.event.listen('file-removed', (event) => this.onDocumentChangedOnDisk(event && event.payload, true));
             tauri.event.listen('open-file', (event) => {
                 const payload = event && event.payload;
                 if (!payload) return;
@@ -3933,13 +4134,68 @@
             /* Route .ora files through the layer loader */
             if (/\.ora$/i.test(normalizedPath)) {
                 try {
-                    const bytes = await this.getTauriInvokeFn()('read_image_file', { path: normalizedPath });
-                    const file = new File([new Uint8Array(bytes)], this.getFilenameFromPath(normalizedPath), { type: 'image/openraster' });
-                    await this.loadORAFile(file);
+                    const data = await this.tauriInvoke('read_ora_document', { path: normalizedPath, flattenGroups: true });
+                    await this.loadORADocument(this.tauriParseFramedResponse(data), this.getFilenameFromPath(normalizedPath));
                     this.addRecentFile({ name: this.state.fileName, path: normalizedPath });
                     return true;
                 } catch (err) {
                     console.log('Failed to load ORA file', { path: normalizedPath, err });
+                    showToast('Failed to open ORA file: ' + this.getErrorText(err), 'error');
+                    if (!this.state.hasDocument) this.initializeBlankDocument();
+                    return false;
+                }
+            }
+            /* Aseprite documents are parsed in Rust and fed to the layer stack */
+            if (/\.(ase|aseprite)$/i.test(normalizedPath)) {
+                try {
+                    const doc = await this.tauriReadAsepriteDocument(normalizedPath);
+                    const name = this.getFilenameFromPath(normalizedPath);
+                    this.loadAsepriteDocument(doc, name);
+                    this.addRecentFile({ name, path: normalizedPath });
+                    return true;
+                } catch (err) {
+                    console.log('Failed to load Aseprite file', { path: normalizedPath, err });
+                    showToast('Failed to open Aseprite file: ' + this.getErrorText(err), 'error');
+                    if (!this.state.hasDocument) this.initializeBlankDocument();
+                    return false;
+                }
+            }
+            /* GBA tile data (.4bpp/.8bpp, optionally .lz) opens as an indexed image */
+            if (/\.[48]bpp(\.lz)?$/i.test(normalizedPath)) {
+                try {
+                    let tiles;
+                    try {
+                        tiles = await this.tauriReadGbaTiles(normalizedPath, null);
+                    } catch (err) {
+                        // No PNG beside the tiles to take the width from.
+                        if (!/width is needed/.test(this.getErrorText(err))) throw err;
+                        const width = await this.askTileWidth(this.state.gbaTileWidth || 64);
+                        if (!width) {
+                            if (!this.state.hasDocument) this.initializeBlankDocument();
+                            return false;
+                        }
+                        this.state.gbaTileWidth = width;
+                        tiles = await this.tauriReadGbaTiles(normalizedPath, width);
+                    }
+                    tiles.alpha = tiles.palette.map((_, i) => (i === 0 ? 0 : 255));
+                    const img = this.indexedImageToCanvas(tiles);
+                    // Saving writes a PNG, so never point Save at the tile data.
+                    this.state.fileHandle = null;
+                    this.state.filePath = null;
+                    this.state.fileName = this.getFilenameFromPath(normalizedPath).replace(/\.[48]bpp(\.lz)?$/i, '.png');
+                    this.bitDepth = 24;
+                    await this.handleLoadedImage(img, false);
+                    this.palette = tiles.palette.map((c, i) => ({ r: c[0], g: c[1], b: c[2], a: tiles.alpha[i] }));
+                    this.paletteLab = null;
+                    this.bitDepth = tiles.bit_depth;
+                    this.updateModeButtons();
+                    this.renderQuantPalette();
+                    this.addRecentFile({ name: this.getFilenameFromPath(normalizedPath), path: normalizedPath });
+                    if (!tiles.palette_path) showToast('No .gbapal found beside the tiles; showing a gray ramp.', 'info');
+                    return true;
+                } catch (err) {
+                    console.log('Failed to load GBA tiles', { path: normalizedPath, err });
+                    showToast('Failed to open tile data: ' + this.getErrorText(err), 'error');
                     if (!this.state.hasDocument) this.initializeBlankDocument();
                     return false;
                 }
@@ -3948,12 +4204,63 @@
             this.updateBusyIndicator();
             this.state.fileHandle = null;
             this.state.filePath = null;
+            /* GIFs are decoded in Rust so their palette survives; animated
+               ones open with one layer per frame. */
+            const gif = /\.gif$/i.test(normalizedPath)
+                ? await this.tauriDecodeGifFrames(normalizedPath).catch(() => null)
+                : null;
+            if (gif && gif.frames.length > 1 && this.loadLayerStack) {
+                try {
+                    const name = this.getFilenameFromPath(normalizedPath);
+                    const layerDefs = gif.frames.map((frame, i) => ({
+                        canvas: this.rgbaToCanvas(gif.width, gif.height, frame.rgba),
+                        name: `Frame ${i + 1} (${frame.delay_ms} ms)`,
+                        opacity: 1,
+                        visible: i === 0,
+                        blendMode: 'source-over',
+                        locked: false,
+                        alphaLock: false,
+                        isBase: i === 0
+                    }));
+                    this.loadLayerStack(gif.width, gif.height, layerDefs, 0, name);
+                    this.applyGifPalette(gif);
+                    this.addRecentFile({ name, path: normalizedPath });
+                    this.state.isFileLoading = false;
+                    this.updateBusyIndicator();
+                    return true;
+                } catch (err) {
+                    // Fall back to opening the first frame alone.
+                    console.log('Failed to load GIF frames', { path: normalizedPath, err });
+                }
+            }
             try {
-                const img = await this.loadTauriImageFromPath(normalizedPath);
+                /* Paletted BMPs are decoded in Rust so the palette survives
+                   exactly; anything else (24/32-bit) goes through the webview. */
+                const indexedBmp = /\.bmp$/i.test(normalizedPath)
+                    ? await this.tauriDecodeIndexedBmp(normalizedPath).catch(() => null)
+                    : null;
+                const singleGif = gif && gif.frames.length === 1 ? gif : null;
+                const img = indexedBmp
+                    ? this.indexedImageToCanvas(indexedBmp)
+                    : singleGif
+                        ? this.rgbaToCanvas(singleGif.width, singleGif.height, singleGif.frames[0].rgba)
+                        : await this.loadTauriImageFromPath(normalizedPath);
                 this.state.fileHandle = null;
                 this.state.filePath = normalizedPath;
                 this.state.fileName = this.getFilenameFromPath(normalizedPath);
+                if (indexedBmp || singleGif) this.bitDepth = 24;
                 await this.handleLoadedImage(img, false);
+                if (singleGif) this.applyGifPalette(singleGif);
+                if (indexedBmp) {
+                    this.palette = indexedBmp.palette.map((c) => ({ r: c[0], g: c[1], b: c[2], a: 255 }));
+                    this.paletteLab = null;
+                    this.bitDepth = indexedBmp.bit_depth === 1 ? 1 : (indexedBmp.bit_depth === 4 ? 4 : 8);
+                    this.updateModeButtons();
+                    this.renderQuantPalette();
+                }
+                const editorMeta = await this.getTauriInvokeFn()('read_image_metadata', { path: normalizedPath }).catch(() => null);
+                await this.applyEditorMetadata(editorMeta);
+                this.tauriKeepPngChunks(normalizedPath);
                 this.addRecentFile({ name: this.state.fileName, path: normalizedPath });
                 return true;
             } catch (err) {
@@ -5027,6 +5334,8 @@
         }
         markClean() {
             this.state.isDirty = false;
+            this.clearAutosave();
+            this.syncDocumentWatch();
             this.updateTitleFilename();
         }
         getCurrentFilename() {
@@ -5169,7 +5478,7 @@
             return normalizedPath;
         }
         isSupportedImagePath(path) {
-            return /\.(png|jpe?g|bmp|gif|webp|ora)$/i.test(String(path || ''));
+            return /\.(png|jpe?g|bmp|gif|webp|ora|ase|aseprite|[48]bpp|[48]bpp\.lz)$/i.test(String(path || ''));
         }
         loadRecentFiles() {
             const raw = this.lsGet(this.recentFilesStorageKey);
@@ -5278,8 +5587,50 @@
         markSaved(filename = null) {
             if (filename) this.state.fileName = filename;
             this.state.isDirty = false;
+            this.clearAutosave();
+            this.syncDocumentWatch();
             this.updateTitleFilename();
         }
+        /* Point the Rust file watcher (src-tauri watcher.rs) at the file the
+           document now matches on disk, or stop watching when there is none. */
+        syncDocumentWatch() {
+            if (!this.getTauriInvokeFn()) return;
+            const path = this.state.filePath || '';
+            if (path === (this._watchedDocumentPath || '')) return;
+            this._watchedDocumentPath = path;
+            const call = path ? this.tauriInvoke('watch_document', { path }) : this.tauriInvoke('unwatch_document');
+            Promise.resolve(call).catch((e) => console.warn('File watch failed', e));
+        }
+        onDocumentChangedOnDisk(payload, removed) {
+            // Events only arrive for this window's watched document; the path
+            // comes back canonicalized, so compare file names only.
+            const path = this.state.filePath;
+            const norm = (p) => String(p || '').replace(/\\/g, '/').toLowerCase();
+            if (!payload || !path || norm(this.getFilenameFromPath(payload.path)) !== norm(this.getFilenameFromPath(path))) return;
+            const name = this.getFilenameFromPath(path);
+            if (removed) {
+                showToast(`${name} was moved or deleted outside CDPaint`, 'warning');
+                return;
+            }
+            const modal = document.getElementById('modal-file-changed');
+            const text = document.getElementById('file-changed-text');
+            if (!modal || !text || modal.style.display === 'flex') return;
+            text.textContent = this.hasUnsavedChanges()
+                ? `${name} was changed outside CDPaint. Reloading it discards your unsaved changes.`
+                : `${name} was changed outside CDPaint.`;
+            modal.style.display = 'flex';
+            const close = () => { modal.style.display = 'none'; };
+            document.getElementById('file-changed-reload').onclick = async () => {
+                close();
+                this.state.isDirty = false;
+                if (this.state.projectFile && norm(this.state.projectFile) === norm(path)) {
+                    await this.openProjectImage(path, this.state.projectPalNodes || []);
+                } else {
+                    await this.openFileFromPath(path, true);
+                }
+            };
+            document.getElementById('file-changed-keep').onclick = close;
+        }
         async titleBarMinimize() {
             const win = this.getTauriWindow();
             if (win && win.minimize) {
@@ -20341,6 +20692,7 @@ void main() {
             const force15 = document.getElementById('export-15bit');
             const split = document.getElementById('export-split');
             const genPal = document.getElementById('export-gen-pal');
+            const genTiles = document.getElementById('export-gen-tiles');
             const paletteGrid = document.getElementById('export-palette-grid');
 
             if (force15) {
@@ -20352,6 +20704,9 @@ void main() {
                 genPal.checked = cfg.genPal;
                 genPal.disabled = !cfg.indexed;
             }
+            if (genTiles) genTiles.disabled = !cfg.indexed || !this.getTauriInvokeFn();
+            const lzTiles = document.getElementById('export-lz-tiles');
+            if (lzTiles) lzTiles.disabled = !!(genTiles && genTiles.disabled);
 
             if (cfg.indexed && cfg.paletteSize) {
                 this.rebuildExportPalette(cfg.paletteSize);
@@ -20441,6 +20796,16 @@ void main() {
                 if (prefixChk) prefixChk.checked = true;
                 const genChk = document.getElementById('export-gen-pal');
                 if (genChk) genChk.checked = true;
+                // A hooked decomp knows exactly where this asset lives: export back
+                // into the species folder under the decomp's own file names.
+                const asset = window.PokeProject && window.PokeProject.findSpeciesAsset
+                    ? window.PokeProject.findSpeciesAsset(projPath) : null;
+                if (asset) {
+                    if (nameInput) nameInput.value = asset.species.name.toLowerCase();
+                    if (prefixChk) prefixChk.checked = false;
+                    const kindType = /anim_front/.test(lc) ? 'anim_front' : (asset.kind === 'still_front' ? 'front' : asset.kind);
+                    if (typeSelect && typeSelect.querySelector('option[value="' + kindType + '"]')) typeSelect.value = kindType;
+                }
             }
 
             this.updateExportOutputInfo();
@@ -20520,6 +20885,9 @@ void main() {
             const isShiny = document.getElementById('export-is-shiny') && document.getElementById('export-is-shiny').checked;
             const assetType = document.getElementById('export-asset-type').value;
             const genPal = document.getElementById('export-gen-pal') && document.getElementById('export-gen-pal').checked;
+            const genTilesChk = document.getElementById('export-gen-tiles');
+            const genTiles = !!(genTilesChk && genTilesChk.checked && !genTilesChk.disabled);
+            const lzTiles = !!(document.getElementById('export-lz-tiles') && document.getElementById('export-lz-tiles').checked);
 
             const prefix = usePrefix ? `${pkmnName}_` : ``;
             const shinySuffix = isShiny ? '_shiny' : '';
@@ -20537,6 +20905,12 @@ void main() {
             if (genPal) {
                 filesToGenerate.push({ key: 'pal', name: `${prefix}${isShiny ? 'shiny' : 'normal'}.pal` });
             }
+            if (genTiles) {
+                filesToGenerate.filter(f => /\.png$/.test(f.name)).forEach(f => {
+                    filesToGenerate.push({ key: `${f.key}-4bpp`, name: f.name.replace(/\.png$/, lzTiles ? '.4bpp.lz' : '.4bpp') });
+                });
+                filesToGenerate.push({ key: 'gbapal', name: `${prefix}${isShiny ? 'shiny' : 'normal'}.gbapal` });
+            }
 
             const prevSelection = this.state.exportFileSelection || {};
             this.state.exportFileSelection = {};
@@ -20662,7 +21036,36 @@ void main() {
                 this.updateExportPreview();
             }
         }
+        /* Indexed PNGs are encoded in Rust under Tauri (src-tauri png.rs):
+           minimal bit depth, trimmed tRNS, byte-stable output. The JS encoder
+           below stays as the browser build's path. */
         async generateIndexedPNG(w, h, indices, palette, bitDepth, trns) {
+            if (this.getTauriInvokeFn()) {
+                try {
+                    return await this.tauriEncodeIndexedPng(w, h, indices, palette, bitDepth, trns);
+                } catch (err) {
+                    if (!/unknown command|not found/i.test(String(err && err.message || err))) throw err;
+                }
+            }
+            return this.generateIndexedPNGInBrowser(w, h, indices, palette, bitDepth, trns);
+        }
+        async tauriEncodeIndexedPng(w, h, indices, palette, bitDepth, trns, extra = {}) {
+            const alpha = (trns !== undefined && trns !== null)
+                ? Array.from(trns)
+                : palette.map((c) => (c.a === undefined ? 255 : c.a));
+            const options = {
+                width: w,
+                height: h,
+                palette: palette.map((c) => [c.r, c.g, c.b]),
+                alpha,
+                bit_depth: [1, 2, 4, 8].includes(bitDepth) ? bitDepth : null,
+                ...extra
+            };
+            const body = indices instanceof Uint8Array ? indices : Uint8Array.from(indices);
+            const data = await this.tauriInvoke('encode_indexed_png', body, this.tauriBinaryHeaders({ options: JSON.stringify(options) }));
+            return data instanceof Uint8Array ? data : new Uint8Array(data);
+        }
+        async generateIndexedPNGInBrowser(w, h, indices, palette, bitDepth, trns) {
             const ihdr = new Uint8Array(13);
             const ihdrView = new DataView(ihdr.buffer);
             ihdrView.setUint32(0, w, false);
@@ -20808,7 +21211,11 @@ void main() {
             }
             try {
                 const bytes = await this.tauriReadImageBytes(normalizedPath);
-                return await this.applyProjectImageBytes(bytes, this.getFilenameFromPath(normalizedPath), normalizedPath, palNodes);
+                // Exact indices and the full palette; null when the file isn't an indexed PNG.
+                const decoded = await this.tauriDecodeIndexedPng(normalizedPath).catch(() => null);
+                const opened = await this.applyProjectImageBytes(bytes, this.getFilenameFromPath(normalizedPath), normalizedPath, palNodes, decoded);
+                if (opened) this.tauriKeepPngChunks(normalizedPath);
+                return opened;
             } catch (err) {
                 console.error('Failed to open project image', err);
                 showToast('Failed to open project image: ' + this.getErrorText(err), 'error');
@@ -20816,8 +21223,9 @@ void main() {
                 return false;
             }
         }
-        async applyProjectImageBytes(bytes, fallbackName, sourcePath, palNodes) {
+        async applyProjectImageBytes(bytes, fallbackName, sourcePath, palNodes, decoded = null) {
             const meta = this.parsePngPalette(bytes);
+            if (decoded && (decoded.width !== meta.width || decoded.height !== meta.height)) decoded = null;
             if (meta.colorType !== 3 || !meta.palette || !meta.palette.length) {
                 if (sourcePath) {
                     showToast('Project image is not an indexed PNG; opening normally', 'warning');
@@ -20830,17 +21238,20 @@ void main() {
             const bmp = await createImageBitmap(blob);
             const w = meta.width, h = meta.height;
             this.state.projectFile = sourcePath || fallbackName;
+            this.state.projectPalNodes = palNodes;
             this.state.history = [];
             this.state.step = -1;
             if (this.state.selection) this.cancelSelection();
-            const embeddedColors = meta.palette.map(c => ({ r: c.r, g: c.g, b: c.b, a: c.a === undefined ? 255 : c.a }));
+            const embeddedColors = decoded
+                ? decoded.palette.map((c, i) => ({ r: c[0], g: c[1], b: c[2], a: decoded.alpha[i] }))
+                : meta.palette.map(c => ({ r: c.r, g: c.g, b: c.b, a: c.a === undefined ? 255 : c.a }));
             const palettes = await this.buildProjectPalettes(embeddedColors, palNodes);
             const active = palettes[0];
             this.palette = active ? active.colors : embeddedColors;
             this.basePalette = this.palette;
             this.paletteLab = null;
             this.bitDepth = 24;
-            this.state.projectBitDepth = meta.bitDepth || 4;
+            this.state.projectBitDepth = (decoded && decoded.bit_depth) || meta.bitDepth || 4;
             this.paletteLocked = false;
             this.state.palettes = palettes;
             this.state.activePaletteId = active ? active.id : null;
@@ -20848,10 +21259,15 @@ void main() {
             this.setSize(w, h);
             this.ctx.drawImage(bmp, 0, 0);
             await this.applyCurrentModeToCanvasAsync(this.ctx, w, h, false);
-            try {
-                const _snap = this.ctx.getImageData(0, 0, w, h);
-                this.spriteIndices = this.quantizeToIndices(_snap.data, w, h, this.basePalette || this.palette);
-            } catch (e) { this.spriteIndices = null; }
+            if (decoded) {
+                // Stored indices keep duplicate palette colors distinct.
+                this.spriteIndices = decoded.indices;
+            } else {
+                try {
+                    const _snap = this.ctx.getImageData(0, 0, w, h);
+                    this.spriteIndices = this.quantizeToIndices(_snap.data, w, h, this.basePalette || this.palette);
+                } catch (e) { this.spriteIndices = null; }
+            }
             this.state.hasDocument = true;
             this.state.filePath = sourcePath || '';
             this.state.fileName = fallbackName || this.getFilenameFromPath(sourcePath || '');
@@ -20904,9 +21320,13 @@ void main() {
                 exact.set((palette[i].r << 16) | (palette[i].g << 8) | palette[i].b, i);
             }
             const indices = new Uint8Array(w * h);
+            // Indices decoded on open win wherever the pixel still has that slot's
+            // color, so duplicate palette entries keep their original slots.
+            const known = (this.spriteIndices && this.spriteIndices.length === w * h) ? this.spriteIndices : null;
             for (let p = 0, q = 0; p < d.length; p += 4, q++) {
                 const key = (d[p] << 16) | (d[p + 1] << 8) | d[p + 2];
-                let idx = exact.get(key);
+                const prev = known ? palette[known[q]] : null;
+                let idx = (prev && ((prev.r << 16) | (prev.g << 8) | prev.b) === key) ? known[q] : exact.get(key);
                 if (idx === undefined) idx = this._nearestPaletteIndex(d[p], d[p + 1], d[p + 2]);
                 indices[q] = idx;
             }
@@ -20999,28 +21419,591 @@ void main() {
             }
             return null;
         }
-        async tauriInvoke(command, payload = {}) {
+        async tauriInvoke(command, payload = {}, options) {
             const invoke = this.getTauriInvokeFn();
             if (!invoke) {
                 throw new Error('Tauri invoke API is unavailable');
             }
-            return invoke(command, payload);
+            return invoke(command, payload, options);
+        }
+        /* Byte-carrying commands take the bytes as a raw IPC body and their
+           other arguments as percent-encoded headers (see src-tauri ipc.rs). */
+        tauriBinaryHeaders(fields) {
+            const headers = {};
+            for (const [key, value] of Object.entries(fields || {})) {
+                if (value === null || value === undefined || value === '') continue;
+                headers[key] = encodeURIComponent(String(value));
+            }
+            return { headers };
+        }
+        tauriExportFilesBody(files) {
+            const list = (files || []).map((file) => ({
+                name: String((file && file.name) || ''),
+                bytes: this.toUint8Array(file && file.bytes)
+            }));
+            const total = list.reduce((sum, f) => sum + f.bytes.length, 0);
+            const body = new Uint8Array(total);
+            let offset = 0;
+            for (const f of list) {
+                body.set(f.bytes, offset);
+                offset += f.bytes.length;
+            }
+            const manifest = JSON.stringify(list.map((f) => ({ name: f.name, size: f.bytes.length })));
+            return { body, manifest };
+        }
+        /* Framed responses (src-tauri ipc.rs): [u32 LE json length][json][blobs]. */
+        tauriParseFramedResponse(data) {
+            const bytes = data instanceof Uint8Array ? data : new Uint8Array(data);
+            const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+            const jsonLen = view.getUint32(0, true);
+            const meta = JSON.parse(new TextDecoder().decode(bytes.subarray(4, 4 + jsonLen)));
+            return { meta, blobs: bytes.subarray(4 + jsonLen) };
+        }
+        async tauriDecodeIndexedPng(path) {
+            const data = await this.tauriInvoke('decode_indexed_png', { path });
+            const { meta, blobs } = this.tauriParseFramedResponse(data);
+            meta.indices = blobs.slice(0, meta.width * meta.height);
+            return meta;
+        }
+        async tauriDecodeIndexedBmp(path) {
+            const data = await this.tauriInvoke('decode_indexed_bmp', { path });
+            const { meta, blobs } = this.tauriParseFramedResponse(data);
+            meta.indices = blobs.slice(0, meta.width * meta.height);
+            return meta;
+        }
+        async tauriEncodeIndexedBmp(w, h, indices, palette, bitDepth) {
+            const options = {
+                width: w,
+                height: h,
+                palette: palette.map((c) => [c.r, c.g, c.b]),
+                bit_depth: bitDepth === 8 ? 8 : null
+            };
+            const body = indices instanceof Uint8Array ? indices : Uint8Array.from(indices);
+            const data = await this.tauriInvoke('encode_indexed_bmp', body, this.tauriBinaryHeaders({ options: JSON.stringify(options) }));
+            return data instanceof Uint8Array ? data : new Uint8Array(data);
+        }
+        /* gbagfx-identical tiles + .gbapal; returns { tiles, gbapal } */
+        async tauriExportGbaTiles(w, h, indices, palette, bitDepth = 4, compress = false) {
+            const options = {
+                width: w,
+                height: h,
+                bit_depth: bitDepth,
+                palette: palette.map((c) => [c.r, c.g, c.b]),
+                compress
+            };
+            const body = indices instanceof Uint8Array ? indices : Uint8Array.from(indices);
+            const data = await this.tauriInvoke('export_gba_tiles', body, this.tauriBinaryHeaders({ options: JSON.stringify(options) }));
+            const { meta, blobs } = this.tauriParseFramedResponse(data);
+            return {
+                tiles: blobs.slice(0, meta.tiles_len),
+                gbapal: blobs.slice(meta.tiles_len, meta.tiles_len + meta.palette_len)
+            };
+        }
+        /* A palette file's bytes (format as in src-tauri palette.rs), for
+           exports that bundle it with tiles */
+        async tauriEncodePalette(palette, format = 'gbapal') {
+            const colors = palette.map((c) => [c.r, c.g, c.b, 255]);
+            const data = await this.tauriInvoke('encode_palette', { colors, format });
+            return data instanceof Uint8Array ? data : new Uint8Array(data);
+        }
+        /* width null: take it from a same-named PNG beside the tiles */
+        async tauriReadGbaTiles(path, width) {
+            const data = await this.tauriInvoke('read_gba_tiles', { path, width });
+            const { meta, blobs } = this.tauriParseFramedResponse(data);
+            meta.indices = blobs.slice(0, meta.width * meta.height);
+            return meta;
+        }
+        askTileWidth(defaultWidth) {
+            const modal = document.getElementById('modal-tile-width');
+            const input = document.getElementById('tile-width');
+            if (!modal || !input) return Promise.resolve(null);
+            input.value = String(defaultWidth);
+            modal.style.display = 'flex';
+            input.focus();
+            return new Promise((resolve) => {
+                const finish = (value) => {
+                    modal.style.display = 'none';
+                    resolve(value);
+                };
+                document.getElementById('tile-width-ok').onclick = () => {
+                    const w = parseInt(input.value, 10);
+                    if (!Number.isFinite(w) || w < 8 || w % 8 !== 0) {
+                        showToast('Width must be a multiple of 8.', 'warning');
+                        return;
+                    }
+                    finish(w);
+                };
+                document.getElementById('tile-width-cancel').onclick = () => finish(null);
+            });
+        }
+        askGbaSourceOptions() {
+            const modal = document.getElementById('modal-gba-source');
+            if (!modal) return Promise.resolve(null);
+            modal.style.display = 'flex';
+            return new Promise((resolve) => {
+                const finish = (value) => {
+                    modal.style.display = 'none';
+                    resolve(value);
+                };
+                document.getElementById('gba-source-ok').onclick = () => finish({
+                    format: document.getElementById('gba-source-format').value,
+                    tilemap: document.getElementById('gba-source-tilemap').checked,
+                    paletteOnly: document.getElementById('gba-source-palette-only').checked
+                });
+                document.getElementById('gba-source-cancel').onclick = () => finish(null);
+            });
+        }
+        async tauriExportGbaSource(path, indices, options, policy = '') {
+            const body = indices instanceof Uint8Array ? indices : Uint8Array.from(indices);
+            return this.tauriInvoke('export_gba_source', body, this.tauriBinaryHeaders({ path, options: JSON.stringify(options), policy }));
+        }
+        /* The command refuses to replace changed sources by default; ask and
+           retry with the chosen policy. Returns null when cancelled. */
+        async writeGbaSource(path, indices, options) {
+            try {
+                return await this.tauriExportGbaSource(path, indices, options);
+            } catch (e) {
+                const m = /would overwrite changed files: (.*)$/.exec(this.getErrorText(e));
+                if (!m) throw e;
+                const conflicts = m[1].split(', ').map((name) => ({ name }));
+                const policy = await this.askExportConflicts(conflicts);
+                if (!policy) return null;
+                return this.tauriExportGbaSource(path, indices, options, policy);
+            }
+        }
+        /* Image or palette as C arrays or GAS data, written next to the PNG.
+           Unsaved images, or ones outside an opened folder, ask for a folder. */
+        async exportGbaSource() {
+            if (!this.getTauriInvokeFn()) {
+                showToast('Source export needs the desktop app', 'warning');
+                return;
+            }
+            if (this.bitDepth > 8 || !this.palette || !this.palette.length) {
+                showToast('Switch to an indexed color mode to export source', 'warning');
+                return;
+            }
+            const choice = await this.askGbaSourceOptions();
+            if (!choice) return;
+            const w = this.config.width, h = this.config.height;
+            const bitDepth = this.bitDepth <= 4 ? 4 : 8;
+            const palette = this.palette.slice(0, 1 << bitDepth);
+            let indices = new Uint8Array(0);
+            if (!choice.paletteOnly) {
+                if (w % 8 || h % 8) {
+                    showToast('Tiles need a width and height that are multiples of 8', 'warning');
+                    return;
+                }
+                const d = this.ctx.getImageData(0, 0, w, h).data;
+                indices = this.quantizeToIndices(d, w, h, palette);
+                for (let i = 0; i < indices.length; i++) {
+                    if (d[i * 4 + 3] < 128) indices[i] = 0;
+                }
+            }
+            const options = {
+                format: choice.format,
+                width: choice.paletteOnly ? 0 : w,
+                height: choice.paletteOnly ? 0 : h,
+                bit_depth: bitDepth,
+                palette: palette.map((c) => [c.r, c.g, c.b]),
+                tilemap: choice.tilemap
+            };
+            const fileName = this.state.fileName || 'untitled.png';
+            try {
+                let path = this.state.filePath;
+                let report = null;
+                let denied = !path;
+                if (path) {
+                    try {
+                        report = await this.writeGbaSource(path, indices, options);
+                    } catch (e) {
+                        if (!/access denied/i.test(this.getErrorText(e))) throw e;
+                        denied = true;
+                    }
+                }
+                if (denied) {
+                    const dir = await this.pickExportDirectoryForDesktop();
+                    if (!dir) return;
+                    path = this.joinPath(dir, this.getFilenameFromPath(path || fileName));
+                    report = await this.writeGbaSource(path, indices, options);
+                }
+                if (!report) return;
+                const names = report.files.map((f) => f.name).join(', ');
+                showToast(`Exported ${names}`, 'info');
+            } catch (e) {
+                showToast('Source export failed: ' + this.getErrorText(e), 'error');
+            }
+        }
+        /* The global color table (or the first frame's local one) becomes
+           the document palette, in file order. */
+        applyGifPalette(gif) {
+            const colors = gif.global_palette || (gif.frames[0] && gif.frames[0].palette);
+            if (!colors || !colors.length) return;
+            this.palette = colors.slice(0, 256).map((c) => ({ r: c[0], g: c[1], b: c[2], a: 255 }));
+            this.paletteLab = null;
+            this.bitDepth = colors.length <= 2 ? 1 : (colors.length <= 16 ? 4 : 8);
+            this.updateModeButtons();
+            this.renderQuantPalette();
+        }
+        async tauriDecodeGifFrames(path) {
+            const data = await this.tauriInvoke('decode_gif_frames', { path });
+            const { meta, blobs } = this.tauriParseFramedResponse(data);
+            const frameLen = meta.width * meta.height * 4;
+            meta.frames.forEach((frame, i) => {
+                frame.rgba = blobs.slice(i * frameLen, (i + 1) * frameLen);
+            });
+            return meta;
+        }
+        async tauriEncodeIndexedGif(w, h, frames, palette, transparentIndex = null, loopCount = null) {
+            const body = new Uint8Array(w * h * frames.length);
+            frames.forEach((frame, i) => body.set(frame.indices, i * w * h));
+            const options = {
+                width: w,
+                height: h,
+                palette: palette.map((c) => [c.r, c.g, c.b]),
+                transparent_index: transparentIndex,
+                loop_count: loopCount,
+                frames: frames.map((frame) => ({ delay_ms: frame.delayMs || 0 }))
+            };
+            const data = await this.tauriInvoke('encode_indexed_gif', body, this.tauriBinaryHeaders({ options: JSON.stringify(options) }));
+            return data instanceof Uint8Array ? data : new Uint8Array(data);
+        }
+        async tauriReadAsepriteDocument(path) {
+            const data = await this.tauriInvoke('read_aseprite_document', { path });
+            const { meta, blobs } = this.tauriParseFramedResponse(data);
+            for (const frame of meta.frames) {
+                for (const cel of frame.cels) {
+                    cel.pixels = blobs.subarray(cel.pixel_offset, cel.pixel_offset + cel.pixel_len);
+                }
+            }
+            return meta;
+        }
+        /* One cel -> canvas of its own size, in the document's color depth
+           (32 RGBA, 16 gray+alpha, 8 palette indices). */
+        asepriteCelToCanvas(doc, cel, layer) {
+            const rgba = new Uint8ClampedArray(cel.width * cel.height * 4);
+            const px = cel.pixels;
+            for (let i = 0; i < cel.width * cel.height; i++) {
+                const o = i * 4;
+                if (doc.color_depth === 32) {
+                    rgba.set(px.subarray(o, o + 4), o);
+                } else if (doc.color_depth === 16) {
+                    rgba[o] = rgba[o + 1] = rgba[o + 2] = px[i * 2];
+                    rgba[o + 3] = px[i * 2 + 1];
+                } else {
+                    const idx = px[i];
+                    if (idx === doc.transparent_index && !layer.background) continue;
+                    const c = doc.palette[idx] || [0, 0, 0, 255];
+                    rgba[o] = c[0]; rgba[o + 1] = c[1]; rgba[o + 2] = c[2]; rgba[o + 3] = c[3];
+                }
+            }
+            return this.rgbaToCanvas(cel.width, cel.height, rgba);
+        }
+        /* Single-frame documents open as a layer stack; animations open with
+           one flattened layer per frame, like GIFs. Group and tilemap layers
+           are not editable here and are skipped. */
+        loadAsepriteDocument(doc, fileName) {
+            const w = doc.width, h = doc.height;
+            const blendModes = { normal: 'source-over', addition: 'lighter', subtract: 'source-over', divide: 'source-over' };
+            const blendOf = (layer) => blendModes[layer.blend_mode] || layer.blend_mode;
+            // A layer shows only if it and every group above it are visible.
+            const shown = doc.layers.map((layer, i) => {
+                let level = layer.child_level;
+                for (let j = i + 1; j < doc.layers.length && level > 0; j++) {
+                    const parent = doc.layers[j];
+                    if (parent.kind === 'group' && parent.child_level < level) {
+                        if (!parent.visible) return false;
+                        level = parent.child_level;
+                    }
+                }
+                return layer.visible;
+            });
+            const layerCanvas = (frame, layerIndex) => {
+                const c = document.createElement('canvas');
+                c.width = w;
+                c.height = h;
+                const cel = frame.cels.find((x) => x.layer === layerIndex);
+                if (cel) {
+                    const cCtx = this.get2dContext(c);
+                    cCtx.globalAlpha = cel.opacity / 255;
+                    cCtx.drawImage(this.asepriteCelToCanvas(doc, cel, doc.layers[layerIndex]), cel.x, cel.y);
+                }
+                return c;
+            };
+            const paintLayers = doc.layers.map((layer, i) => i).filter((i) => doc.layers[i].kind === 'normal');
+            let layerDefs;
+            if (doc.frames.length === 1) {
+                layerDefs = paintLayers.map((i, n) => ({
+                    canvas: layerCanvas(doc.frames[0], i),
+                    name: doc.layers[i].name || `Layer ${n + 1}`,
+                    opacity: doc.layers[i].opacity / 255,
+                    visible: shown[i],
+                    blendMode: blendOf(doc.layers[i]),
+                    locked: false,
+                    alphaLock: false,
+                    isBase: n === 0
+                }));
+            } else {
+                layerDefs = doc.frames.map((frame, f) => {
+                    const c = document.createElement('canvas');
+                    c.width = w;
+                    c.height = h;
+                    const cCtx = this.get2dContext(c);
+                    for (const i of paintLayers) {
+                        if (!shown[i]) continue;
+                        cCtx.globalAlpha = doc.layers[i].opacity / 255;
+                        cCtx.globalCompositeOperation = blendOf(doc.layers[i]);
+                        cCtx.drawImage(layerCanvas(frame, i), 0, 0);
+                    }
+                    const tags = doc.tags.filter((t) => f >= t.from && f <= t.to).map((t) => t.name);
+                    return {
+                        canvas: c,
+                        name: `Frame ${f + 1} (${frame.duration_ms} ms)` + (tags.length ? ` [${tags.join(', ')}]` : ''),
+                        opacity: 1,
+                        visible: f === 0,
+                        blendMode: 'source-over',
+                        locked: false,
+                        alphaLock: false,
+                        isBase: f === 0
+                    };
+                });
+            }
+            if (!layerDefs.length) throw new Error('Aseprite file has no paint layers');
+            this.loadLayerStack(w, h, layerDefs, 0, fileName);
+            if (doc.color_depth === 8 && doc.palette.length) {
+                // Indexed documents keep their exact palette.
+                this.palette = doc.palette.map((c) => ({ r: c[0], g: c[1], b: c[2], a: c[3] }));
+                this.paletteLab = null;
+                this.bitDepth = this.palette.length <= 16 ? 4 : 8;
+                this.updateModeButtons();
+                this.renderQuantPalette();
+            }
+        }
+        rgbaToCanvas(w, h, rgba) {
+            const c = document.createElement('canvas');
+            c.width = w;
+            c.height = h;
+            const cCtx = this.get2dContext(c);
+            const imgData = cCtx.createImageData(w, h);
+            imgData.data.set(rgba);
+            cCtx.putImageData(imgData, 0, 0);
+            return c;
+        }
+        /* Each layer (or the canvas alone) becomes one frame, all sharing the
+           current palette; a fully transparent palette entry becomes the GIF's
+           transparent index. Frame delays come from "(N ms)" in layer names. */
+        async exportAnimatedGif() {
+            if (!this.getTauriInvokeFn()) {
+                showToast('Animated GIF export needs the desktop app', 'warning');
+                return;
+            }
+            if (this.bitDepth > 8 || !this.palette || !this.palette.length) {
+                showToast('Switch to an indexed color mode to export a GIF', 'warning');
+                return;
+            }
+            const w = this.config.width, h = this.config.height;
+            const palette = this.palette.slice(0, 256);
+            const transparent = palette.findIndex((c) => c.a === 0);
+            const mgr = this.layerMgr;
+            const sources = mgr && mgr.active && mgr.layers.length > 1
+                ? mgr.layers.map((l) => ({ ctx: l.ctx, name: l.name }))
+                : [{ ctx: this.ctx, name: '' }];
+            const frames = sources.map(({ ctx, name }) => {
+                const d = ctx.getImageData(0, 0, w, h).data;
+                const indices = this.quantizeToIndices(d, w, h, palette);
+                if (transparent >= 0) {
+                    for (let i = 0; i < indices.length; i++) {
+                        if (d[i * 4 + 3] < 128) indices[i] = transparent;
+                    }
+                }
+                const delay = /\((\d+)\s*ms\)/i.exec(name || '');
+                return { indices, delayMs: delay ? parseInt(delay[1], 10) : 100 };
+            });
+            try {
+                const bytes = await this.tauriEncodeIndexedGif(w, h, frames, palette, transparent >= 0 ? transparent : null);
+                const base = (this.state.fileName || 'animation').replace(/\.[^.]+$/, '');
+                await this.tauriWriteExportFileWithSaveDialog({ name: `${base}.gif`, bytes });
+            } catch (e) {
+                showToast('GIF export failed: ' + this.getErrorText(e), 'error');
+            }
+        }
+        async tauriGenerateTilemap(w, h, indices, bitDepth, allowFlips = true) {
+            const options = { width: w, height: h, bit_depth: bitDepth, allow_flips: allowFlips };
+            const body = indices instanceof Uint8Array ? indices : Uint8Array.from(indices);
+            const data = await this.tauriInvoke('generate_tilemap', body, this.tauriBinaryHeaders({ options: JSON.stringify(options) }));
+            const { meta, blobs } = this.tauriParseFramedResponse(data);
+            return {
+                info: meta,
+                tileset: blobs.slice(0, meta.tileset_len),
+                map: blobs.slice(meta.tileset_len, meta.tileset_len + meta.map_len)
+            };
+        }
+        /* Background export: deduplicated tileset, .bin screen entries and
+           .gbapal. In 4bpp an index's high nibble picks the palette bank. */
+        async exportTilemap() {
+            if (!this.getTauriInvokeFn()) {
+                showToast('Tilemap export needs the desktop app', 'warning');
+                return;
+            }
+            if (this.bitDepth > 8 || !this.palette || !this.palette.length) {
+                showToast('Switch to an indexed color mode to export a tilemap', 'warning');
+                return;
+            }
+            const w = this.config.width, h = this.config.height;
+            if (w % 8 || h % 8) {
+                showToast('Tilemaps need a width and height that are multiples of 8', 'warning');
+                return;
+            }
+            const bitDepth = this.bitDepth <= 4 ? 4 : 8;
+            const palette = this.palette.slice(0, 256);
+            const d = this.ctx.getImageData(0, 0, w, h).data;
+            const indices = this.quantizeToIndices(d, w, h, palette);
+            for (let i = 0; i < indices.length; i++) {
+                if (d[i * 4 + 3] < 128) indices[i] = 0; // color 0 is transparent on the GBA
+            }
+            try {
+                const out = await this.tauriGenerateTilemap(w, h, indices, bitDepth);
+                const { unique_tiles: unique, tile_limit: limit, tile_count: total } = out.info;
+                if (out.info.over_limit) {
+                    showToast(`${unique} unique tiles is over the ${limit}-tile limit; reduce detail or split the map.`, 'error');
+                    return;
+                }
+                const gbapal = await this.tauriEncodePalette(palette);
+                const base = (this.state.fileName || 'tilemap').replace(/\.[^.]+$/, '');
+                const result = await this.tauriWriteExportFilesWithDialog([
+                    { name: `${base}.${bitDepth}bpp`, bytes: out.tileset },
+                    { name: `${base}.bin`, bytes: out.map },
+                    { name: `${base}.gbapal`, bytes: gbapal }
+                ]);
+                if (result) showToast(`Tilemap exported: ${unique} of ${limit} tiles used (${total} before deduplication)`, 'info');
+            } catch (e) {
+                showToast('Tilemap export failed: ' + this.getErrorText(e), 'error');
+            }
+        }
+        async tauriSliceOam(w, h, indices, bitDepth, originX = 0, originY = 0) {
+            const options = { width: w, height: h, bit_depth: bitDepth, origin_x: originX, origin_y: originY };
+            const body = indices instanceof Uint8Array ? indices : Uint8Array.from(indices);
+            const data = await this.tauriInvoke('slice_oam_sprite', body, this.tauriBinaryHeaders({ options: JSON.stringify(options) }));
+            const { meta, blobs } = this.tauriParseFramedResponse(data);
+            return {
+                info: meta,
+                pieces: meta.pieces.map((p) => ({ ...p, tiles: blobs.slice(p.tiles_offset, p.tiles_offset + p.tiles_len) }))
+            };
+        }
+        /* Sprite export as OBJ pieces: one tile file per piece, the .gbapal
+           and a text layout with offsets from the sprite's center. */
+        async exportOamPieces() {
+            if (!this.getTauriInvokeFn()) {
+                showToast('OAM export needs the desktop app', 'warning');
+                return;
+            }
+            if (this.bitDepth > 8 || !this.palette || !this.palette.length) {
+                showToast('Switch to an indexed color mode to export OAM pieces', 'warning');
+                return;
+            }
+            const w = this.config.width, h = this.config.height;
+            const bitDepth = this.bitDepth <= 4 ? 4 : 8;
+            const palette = this.palette.slice(0, 1 << bitDepth);
+            const d = this.ctx.getImageData(0, 0, w, h).data;
+            const indices = this.quantizeToIndices(d, w, h, palette);
+            for (let i = 0; i < indices.length; i++) {
+                if (d[i * 4 + 3] < 128) indices[i] = 0;
+            }
+            try {
+                const originX = Math.floor(w / 2), originY = Math.floor(h / 2);
+                const { info, pieces } = await this.tauriSliceOam(w, h, indices, bitDepth, originX, originY);
+                if (!pieces.length) {
+                    showToast('Nothing to export: the sprite is fully transparent', 'warning');
+                    return;
+                }
+                const base = (this.state.fileName || 'sprite').replace(/\.[^.]+$/, '');
+                const hex = (v) => '0x' + v.toString(16).toUpperCase().padStart(4, '0');
+                const lines = [`# ${w}x${h}, offsets from (${originX}, ${originY}); ${info.tile_count} tiles, ${pieces.length} pieces`];
+                const files = pieces.map((p, i) => {
+                    const name = `${base}_${i}.${bitDepth}bpp`;
+                    lines.push(`${name}: x ${p.x} y ${p.y} ${p.width}x${p.height} shape ${p.shape} size ${p.size} attr0 ${hex(p.attr0)} attr1 ${hex(p.attr1)}`);
+                    return { name, bytes: p.tiles };
+                });
+                const gbapal = await this.tauriEncodePalette(palette);
+                files.push({ name: `${base}.gbapal`, bytes: gbapal });
+                files.push({ name: `${base}_oam.txt`, bytes: new TextEncoder().encode(lines.join('\n') + '\n') });
+                const result = await this.tauriWriteExportFilesWithDialog(files);
+                if (result) showToast(`Exported ${pieces.length} OBJ pieces (${info.tile_count} tiles)`, 'info');
+            } catch (e) {
+                showToast('OAM export failed: ' + this.getErrorText(e), 'error');
+            }
+        }
+        /* Decoded index buffer + [r, g, b] palette -> canvas. */
+        indexedImageToCanvas(decoded) {
+            const c = document.createElement('canvas');
+            c.width = decoded.width;
+            c.height = decoded.height;
+            const cCtx = this.get2dContext(c);
+            const imgData = cCtx.createImageData(decoded.width, decoded.height);
+            const d = imgData.data;
+            for (let i = 0; i < decoded.indices.length; i++) {
+                const color = decoded.palette[decoded.indices[i]];
+                const alpha = decoded.alpha ? decoded.alpha[decoded.indices[i]] : 255;
+                d[i * 4] = color[0];
+                d[i * 4 + 1] = color[1];
+                d[i * 4 + 2] = color[2];
+                d[i * 4 + 3] = alpha;
+            }
+            cCtx.putImageData(imgData, 0, 0);
+            return c;
+        }
+        /* Chunks of an opened PNG document that the canvas can't carry
+           (text notes, private chunks) come back on save; see src-tauri
+           png_chunks.rs. Best effort. */
+        tauriKeepPngChunks(path) {
+            if (!/\.png$/i.test(path)) return;
+            this.tauriInvoke('keep_png_chunks', { path }).catch((e) => console.warn('Could not keep PNG chunks', e));
         }
         async tauriReadImageBytes(path) {
             const data = await this.tauriInvoke('read_image_file', { path });
             return data instanceof Uint8Array ? data : new Uint8Array(data);
         }
-        async tauriWriteAllowedFile(path, data) {
+        async tauriWriteAllowedFile(path, data, editorMetadata = null) {
             const bytes = this.toUint8Array(data);
-            await this.tauriInvoke('write_allowed_file', { path, data: Array.from(bytes) });
+            const metadata = editorMetadata ? JSON.stringify(editorMetadata) : '';
+            await this.tauriInvoke('write_allowed_file', bytes, this.tauriBinaryHeaders({ path, 'cdpaint-metadata': metadata }));
+        }
+        /* Editing state saved inside PNGs (src-tauri editor_meta.rs) so it
+           comes back when the file is reopened. */
+        collectEditorMetadata() {
+            const typeSelect = document.getElementById('export-asset-type');
+            return {
+                bit_depth: this.bitDepth,
+                palette_locked: !!this.paletteLocked,
+                tile_size: this.tileSize,
+                export_asset_type: typeSelect ? typeSelect.value : null
+            };
         }
-        async tauriWriteExportFiles(directory, files) {
+        async applyEditorMetadata(meta) {
+            if (!meta) return;
+            const modes = { 24: 'full', 16: 'rgb565', 15: 'rgb555', 8: '256', 4: '16', 1: '2' };
+            if (meta.bit_depth && modes[meta.bit_depth] && meta.bit_depth !== this.bitDepth) {
+                await this.setMode(modes[meta.bit_depth]);
+            }
+            if (meta.palette_locked) this.paletteLocked = true;
+            if (meta.tile_size) this.setTileSize(meta.tile_size);
+            const typeSelect = document.getElementById('export-asset-type');
+            if (typeSelect && meta.export_asset_type &&
+                Array.from(typeSelect.options).some((o) => o.value === meta.export_asset_type)) {
+                typeSelect.value = meta.export_asset_type;
+                this.updateExportOutputInfo();
+            }
+        }
+        /* Export `policy` is 'overwrite' (default), 'skip-existing' or
+           'fail-on-difference'; see src-tauri export.rs. */
+        async tauriPreviewExportFiles(directory, files) {
             const dir = this.normalizeExportDirectoryPath(directory);
-            const payloadFiles = (files || []).map((file) => ({
-                name: String(file.name || ''),
-                data: Array.from(this.toUint8Array(file.bytes))
-            }));
-            await this.tauriInvoke('write_export_files', { directory: dir, files: payloadFiles });
+            const { body, manifest } = this.tauriExportFilesBody(files);
+            return this.tauriInvoke('preview_export_files', body, this.tauriBinaryHeaders({ directory: dir, files: manifest }));
+        }
+        async tauriWriteExportFiles(directory, files, policy = '') {
+            const dir = this.normalizeExportDirectoryPath(directory);
+            const { body, manifest } = this.tauriExportFilesBody(files);
+            return this.tauriInvoke('write_export_files', body, this.tauriBinaryHeaders({ directory: dir, files: manifest, policy }));
         }
         async tauriWriteExportFilesPerFile(directory, files) {
             const dir = this.normalizeExportDirectoryPath(directory);
@@ -21033,13 +22016,10 @@ void main() {
                 await this.tauriWriteAllowedFile(outputPath, file.bytes);
             }
         }
-        async tauriWriteExportFilesWithDialog(files) {
-            const payloadFiles = (files || []).map((file) => ({
-                name: String(file.name || ''),
-                data: Array.from(this.toUint8Array(file.bytes))
-            }));
+        async tauriWriteExportFilesWithDialog(files, policy = '') {
+            const { body, manifest } = this.tauriExportFilesBody(files);
             try {
-                const result = await this.tauriInvoke('write_export_files_with_dialog', { files: payloadFiles });
+                const result = await this.tauriInvoke('write_export_files_with_dialog', body, this.tauriBinaryHeaders({ files: manifest, policy }));
                 if (!result) return null;
                 return true;
             } catch (e) {
@@ -21050,71 +22030,41 @@ void main() {
                 throw e;
             }
         }
-        async tauriWriteExportFilesWithSaveDialog(files) {
-            const payloadFiles = (files || []).map((file) => ({
-                name: String(file.name || ''),
-                data: Array.from(this.toUint8Array(file.bytes))
-            }));
+        async tauriWriteExportFilesWithSaveDialog(files, policy = '') {
+            const { body, manifest } = this.tauriExportFilesBody(files);
             const suggested = files && files.length ? String(files[0].name || '') : '';
-            const attempts = [
-                { files: payloadFiles, suggestedName: suggested },
-                { files: payloadFiles, suggested_name: suggested }
-            ];
-            let lastArgError = null;
-            for (const payload of attempts) {
-                try {
-                    const result = await this.tauriInvoke('write_export_files_with_save_dialog', payload);
-                    if (!result) return null;
-                    return true;
-                } catch (e) {
-                    const msg = String((e && e.message) || e || '');
-                    if (/unknown command|not found|write_export_files_with_save_dialog/i.test(msg)) {
-                        return undefined;
-                    }
-                    if (/invalid args|missing required key|unknown field|invalid type/i.test(msg)) {
-                        lastArgError = e;
-                        continue;
-                    }
-                    throw e;
+            try {
+                const result = await this.tauriInvoke('write_export_files_with_save_dialog', body,
+                    this.tauriBinaryHeaders({ files: manifest, 'suggested-name': suggested, policy }));
+                if (!result) return null;
+                return true;
+            } catch (e) {
+                const msg = String((e && e.message) || e || '');
+                if (/unknown command|not found|write_export_files_with_save_dialog/i.test(msg)) {
+                    return undefined;
                 }
+                throw e;
             }
-            if (lastArgError) throw lastArgError;
-            return undefined;
         }
         async tauriWriteExportFileWithSaveDialog(file, defaultDirectory = '') {
-            const payloadFile = {
-                name: String((file && file.name) || ''),
-                data: Array.from(this.toUint8Array(file && file.bytes))
-            };
-            const suggested = String(payloadFile.name || '');
+            const name = String((file && file.name) || '');
+            const bytes = this.toUint8Array(file && file.bytes);
             const defaultDir = this.normalizeExportDirectoryPath(defaultDirectory);
-            const attempts = [
-                { file: payloadFile, suggestedName: suggested, defaultDirectory: defaultDir || null },
-                { file: payloadFile, suggested_name: suggested, default_directory: defaultDir || null }
-            ];
-            let lastArgError = null;
-            for (const payload of attempts) {
-                try {
-                    const result = await this.tauriInvoke('write_export_file_with_save_dialog', payload);
-                    if (result === null) return null;
-                    if (typeof result === 'string') {
-                        return this.normalizeExportDirectoryPath(result);
-                    }
-                    return '';
-                } catch (e) {
-                    const msg = String((e && e.message) || e || '');
-                    if (/unknown command|not found|write_export_file_with_save_dialog/i.test(msg)) {
-                        return undefined;
-                    }
-                    if (/invalid args|missing required key|unknown field|invalid type/i.test(msg)) {
-                        lastArgError = e;
-                        continue;
-                    }
-                    throw e;
+            try {
+                const result = await this.tauriInvoke('write_export_file_with_save_dialog', bytes,
+                    this.tauriBinaryHeaders({ name, 'suggested-name': name, 'default-directory': defaultDir }));
+                if (result === null) return null;
+                if (typeof result === 'string') {
+                    return this.normalizeExportDirectoryPath(result);
                 }
+                return '';
+            } catch (e) {
+                const msg = String((e && e.message) || e || '');
+                if (/unknown command|not found|write_export_file_with_save_dialog/i.test(msg)) {
+                    return undefined;
+                }
+                throw e;
             }
-            if (lastArgError) throw lastArgError;
-            return undefined;
         }
         async tauriPickExportFolderNative() {
             try {
@@ -21126,47 +22076,15 @@ void main() {
                 return undefined;
             }
         }
+        /* Native dialogs go through the Rust `show_file_dialog` command, which
+           grants file access to whatever the user picks (see grants.rs). */
         async tauriOpenDirectoryDialog(options = {}) {
-            const tauri = window.__TAURI__;
-            if (!tauri) return undefined;
-            if (tauri.dialog && typeof tauri.dialog.open === 'function') {
-                return tauri.dialog.open(options);
-            }
-            const invoke = this.getTauriInvokeFn();
-            if (invoke) {
-                try {
-                    return await invoke('plugin:dialog|open', { options });
-                } catch (e) {
-                    try {
-                        // Some runtimes may expect options to be passed directly.
-                        return await invoke('plugin:dialog|open', options);
-                    } catch (_) {
-                        throw e;
-                    }
-                }
-            }
-            return undefined;
+            if (!this.getTauriInvokeFn()) return undefined;
+            return this.tauriInvoke('show_file_dialog', { options: { ...options, mode: 'open' } });
         }
         async tauriSaveFileDialog(options = {}) {
-            const tauri = window.__TAURI__;
-            if (!tauri) return undefined;
-            if (tauri.dialog && typeof tauri.dialog.save === 'function') {
-                return tauri.dialog.save(options);
-            }
-            const invoke = this.getTauriInvokeFn();
-            if (invoke) {
-                try {
-                    return await invoke('plugin:dialog|save', { options });
-                } catch (e) {
-                    try {
-                        // Some runtimes may expect options to be passed directly.
-                        return await invoke('plugin:dialog|save', options);
-                    } catch (_) {
-                        throw e;
-                    }
-                }
-            }
-            return undefined;
+            if (!this.getTauriInvokeFn()) return undefined;
+            return this.tauriInvoke('show_file_dialog', { options: { ...options, mode: 'save' } });
         }
         async pickExportDirectoryForDesktop() {
             const defaultPath = this.normalizeExportDirectoryPath(this.state.exportDir || '');
@@ -21253,11 +22171,68 @@ void main() {
             if (dirHint) this.state.exportDir = dirHint;
             return true;
         }
+        askExportConflicts(conflicts) {
+            const modal = document.getElementById('modal-export-conflicts');
+            const list = document.getElementById('export-conflicts-list');
+            if (!modal || !list) return Promise.resolve('fail-on-difference');
+            list.innerHTML = '';
+            conflicts.forEach((e) => {
+                const row = document.createElement('div');
+                row.style.margin = '4px 0';
+                if (e.size == null) {
+                    row.textContent = e.name;
+                } else {
+                    const when = e.existing_modified_ms ? new Date(e.existing_modified_ms).toLocaleString() : 'unknown date';
+                    row.textContent = `${e.name}: ${this.formatBytes(e.existing_size || 0)} from ${when} \u2192 ${this.formatBytes(e.size)}`;
+                }
+                row.title = e.path || e.name;
+                list.appendChild(row);
+            });
+            modal.style.display = 'flex';
+            return new Promise((resolve) => {
+                const finish = (value) => {
+                    modal.style.display = 'none';
+                    resolve(value);
+                };
+                document.getElementById('export-conflicts-overwrite').onclick = () => finish('overwrite');
+                document.getElementById('export-conflicts-skip').onclick = () => finish('skip-existing');
+                document.getElementById('export-conflicts-cancel').onclick = () => finish(null);
+            });
+        }
+        /* Dry-run the export into `directory` and, when it would replace files
+           that differ from what is being written, let the user choose the
+           policy. An explicit `policy` is used as is; null means cancelled. */
+        async resolveExportPolicy(directory, files, policy = '') {
+            if (policy) return policy;
+            let plan;
+            try {
+                plan = await this.tauriPreviewExportFiles(directory, files);
+            } catch (e) {
+                // Without a preview, never replace a file that differs.
+                console.warn('Export preview failed', e);
+                return 'fail-on-difference';
+            }
+            const conflicts = plan.filter((e) => e.action === 'overwrite-different');
+            if (!conflicts.length) return 'overwrite';
+            return this.askExportConflicts(conflicts);
+        }
         async saveExportFiles(files, preselectedDir = null, options = {}) {
             if (!Array.isArray(files) || files.length === 0) return true;
             const hasTauriWrite = !!this.getTauriInvokeFn();
             const opts = options && typeof options === 'object' ? options : {};
             const skipDirectoryPrompt = !!opts.skipDirectoryPrompt;
+            const policy = typeof opts.policy === 'string' ? opts.policy : '';
+            // Batch writes are all-or-nothing and honour the policy; per-file and
+            // browser writes do neither, so they only stand in for runtimes that
+            // lack the batch command, never for a batch that was refused.
+            const strictPolicy = !!policy && policy !== 'overwrite';
+            let batchRefused = false;
+            const mustNotFallBack = (err) => {
+                const msg = this.getErrorText(err);
+                if (/unknown command|not found|write_export_files/i.test(msg)) return false;
+                batchRefused = true;
+                return true;
+            };
             const tauriFailures = [];
             const rememberTauriFailure = (step, err) => {
                 const msg = this.getErrorText(err);
@@ -21284,11 +22259,14 @@ void main() {
                     }
                 }
                 if (chosenDir) {
+                    const chosenPolicy = await this.resolveExportPolicy(chosenDir, files, policy);
+                    if (chosenPolicy === null) return null;
                     try {
-                        await this.tauriWriteExportFiles(chosenDir, files);
+                        await this.tauriWriteExportFiles(chosenDir, files, chosenPolicy);
                         return true;
                     } catch (e) {
                         rememberTauriFailure('native batch write', e);
+                        if (mustNotFallBack(e)) throw e;
                         try {
                             await this.tauriWriteExportFilesPerFile(chosenDir, files);
                             return true;
@@ -21297,29 +22275,38 @@ void main() {
                         }
                     }
                 }
+                // The folder is picked inside these commands, so there is no
+                // preview to ask about; refuse to replace differing files.
+                const dialogPolicy = policy || 'fail-on-difference';
+                const refusedByPolicy = (err) => /would overwrite changed files/.test(this.getErrorText(err));
                 try {
-                    const wroteViaSaveDialog = await this.tauriWriteExportFilesWithSaveDialog(files);
+                    const wroteViaSaveDialog = await this.tauriWriteExportFilesWithSaveDialog(files, dialogPolicy);
                     if (wroteViaSaveDialog === true) return true;
                     if (wroteViaSaveDialog === null) return null;
                 } catch (e) {
                     rememberTauriFailure('save dialog write', e);
+                    if (refusedByPolicy(e)) throw e;
                 }
                 try {
-                    const wroteViaDialog = await this.tauriWriteExportFilesWithDialog(files);
+                    const wroteViaDialog = await this.tauriWriteExportFilesWithDialog(files, dialogPolicy);
                     if (wroteViaDialog === true) return true;
                     if (wroteViaDialog === null) return null;
                 } catch (e) {
                     rememberTauriFailure('folder dialog write', e);
+                    if (refusedByPolicy(e)) throw e;
                 }
                 try {
                     const nativeDir = await this.tauriPickExportFolderNative();
                     if (nativeDir === null) return null;
                     if (typeof nativeDir === 'string' && nativeDir) {
+                        const nativePolicy = await this.resolveExportPolicy(nativeDir, files, policy);
+                        if (nativePolicy === null) return null;
                         try {
-                            await this.tauriWriteExportFiles(nativeDir, files);
+                            await this.tauriWriteExportFiles(nativeDir, files, nativePolicy);
                             return true;
                         } catch (batchError) {
                             rememberTauriFailure('native picker batch write', batchError);
+                            if (mustNotFallBack(batchError)) throw batchError;
                             await this.tauriWriteExportFilesPerFile(nativeDir, files);
                             return true;
                         }
@@ -21337,10 +22324,13 @@ void main() {
                         if (!selected) return null;
                         const dir = this.normalizeDialogPathSelection(selected);
                         if (!dir) return null;
+                        const dirPolicy = await this.resolveExportPolicy(dir, files, policy);
+                        if (dirPolicy === null) return null;
                         try {
-                            await this.tauriWriteExportFiles(dir, files);
+                            await this.tauriWriteExportFiles(dir, files, dirPolicy);
                         } catch (batchError) {
                             rememberTauriFailure('dialog picker batch write', batchError);
+                            if (mustNotFallBack(batchError)) throw batchError;
                             await this.tauriWriteExportFilesPerFile(dir, files);
                         }
                         return true;
@@ -21348,6 +22338,7 @@ void main() {
                 } catch (e) {
                     rememberTauriFailure('dialog picker write', e);
                 }
+                if (strictPolicy || batchRefused) throw new Error(tauriFailures.join('\n') || 'Export was not written');
             }
             if (window.showDirectoryPicker) {
                 try {
@@ -21403,6 +22394,9 @@ void main() {
             const isShiny = document.getElementById('export-is-shiny') && document.getElementById('export-is-shiny').checked;
             const assetType = document.getElementById('export-asset-type').value;
             const genPal = document.getElementById('export-gen-pal').checked;
+            const genTilesChk = document.getElementById('export-gen-tiles');
+            const genTiles = !!(genTilesChk && genTilesChk.checked && !genTilesChk.disabled);
+            const lzTiles = !!(document.getElementById('export-lz-tiles') && document.getElementById('export-lz-tiles').checked);
             const force15 = document.getElementById('export-15bit').checked;
             const prefix = usePrefix ? `${pkmnName}_` : ``;
             const shinySuffix = isShiny ? '_shiny' : '';
@@ -21472,32 +22466,44 @@ void main() {
                 });
             };
 
-            const savePng = async (x, y, subW, subH, filename) => {
+            const shouldExport = (key) => {
+                const checkbox = document.getElementById(`export-file-${key}`);
+                return checkbox ? checkbox.checked : true;
+            };
+            // The 4bpp tiles and .gbapal gbagfx would build from the PNG.
+            const tilePalette = finalPalette.slice(0, 16);
+
+            const savePng = async (x, y, subW, subH, filename, key) => {
                 const subIndices = new Uint8Array(subW * subH);
                 for (let row = 0; row < subH; row++) {
                     for (let col = 0; col < subW; col++) {
                         subIndices[row * subW + col] = indices[(y + row) * w + (x + col)];
                     }
                 }
-                const pngBytes = await this.generateIndexedPNG(subW, subH, subIndices, finalPalette, 4, new Uint8Array([0])); // index 0 = transparent (GBA contract); 4bpp for decomps
-                queueFile(pngBytes, filename);
-            };
-
-            const shouldExport = (key) => {
-                const checkbox = document.getElementById(`export-file-${key}`);
-                return checkbox ? checkbox.checked : true;
+                if (shouldExport(key)) {
+                    const pngBytes = await this.generateIndexedPNG(subW, subH, subIndices, finalPalette, 4, new Uint8Array([0])); // index 0 = transparent (GBA contract); 4bpp for decomps
+                    queueFile(pngBytes, filename);
+                }
+                if (genTiles && shouldExport(`${key}-4bpp`)) {
+                    const { tiles } = await this.tauriExportGbaTiles(subW, subH, subIndices, tilePalette, 4, lzTiles);
+                    queueFile(tiles, filename.replace(/\.png$/, lzTiles ? '.4bpp.lz' : '.4bpp'));
+                }
             };
 
             try {
                 // Execute the saves based on asset type
                 if (assetType === 'front-back' && w === 128 && h === 64) {
-                    if (shouldExport('front')) await savePng(0, 0, 64, 64, `${prefix}front${shinySuffix}.png`);
-                    if (shouldExport('back')) await savePng(64, 0, 64, 64, `${prefix}back${shinySuffix}.png`);
+                    await savePng(0, 0, 64, 64, `${prefix}front${shinySuffix}.png`, 'front');
+                    await savePng(64, 0, 64, 64, `${prefix}back${shinySuffix}.png`, 'back');
                 } else if (assetType === 'custom') {
-                    if (shouldExport('main')) await savePng(0, 0, w, h, `${pkmnName}${shinySuffix}.png`);
+                    await savePng(0, 0, w, h, `${pkmnName}${shinySuffix}.png`, 'main');
                 } else {
                     // front, back, anim_front, footprint, icon
-                    if (shouldExport('main')) await savePng(0, 0, w, h, `${prefix}${assetType}${shinySuffix}.png`);
+                    await savePng(0, 0, w, h, `${prefix}${assetType}${shinySuffix}.png`, 'main');
+                }
+                if (genTiles && shouldExport('gbapal')) {
+                    const gbapal = await this.tauriEncodePalette(tilePalette);
+                    queueFile(gbapal, `${prefix}${isShiny ? 'shiny' : 'normal'}.gbapal`);
                 }
 
                 if (genPal && shouldExport('pal')) {
@@ -22958,7 +23964,7 @@ self.onmessage = function(e) {
                         title: 'Open Image',
                         directory: false,
                         multiple: false,
-                        filters: [{ name: 'Images & Layers', extensions: ['png', 'jpg', 'jpeg', 'bmp', 'gif', 'webp', 'ora'] }]
+                        filters: [{ name: 'Images & Layers', extensions: ['png', 'jpg', 'jpeg', 'bmp', 'gif', 'webp', 'ora', 'ase', 'aseprite', '4bpp', '8bpp', 'lz'] }]
                     });
                     if (typeof picked === 'string' && picked) {
                         await this.openFileFromPath(picked);
@@ -23026,18 +24032,80 @@ self.onmessage = function(e) {
             }
         }
 
-        promptImportPalette() {
+        /* Palette files Rust's read_palette_file / write_palette_file handle */
+        getPaletteFileFilters() {
+            return [
+                { name: 'Palettes', extensions: ['pal', 'gpl', 'act', 'aco', 'txt', 'hex', 'gbapal'] },
+                { name: 'JASC-PAL', extensions: ['pal'] },
+                { name: 'GIMP Palette', extensions: ['gpl'] },
+                { name: 'Adobe Color Table', extensions: ['act'] },
+                { name: 'Adobe Swatches', extensions: ['aco'] },
+                { name: 'Paint.NET Palette', extensions: ['txt'] },
+                { name: 'Hex List', extensions: ['hex'] },
+                { name: 'GBA Palette', extensions: ['gbapal'] }
+            ];
+        }
+        /* read_palette_file result -> { colors, isGba }, slot 0 transparent
+           like the JASC parsers below */
+        paletteFromFile(pal) {
+            const colors = (pal && pal.colors || []).map((c, i) => ({ r: c[0], g: c[1], b: c[2], a: i === 0 ? 0 : c[3] }));
+            if (!colors.length) throw new Error('Palette file had no valid colors');
+            return { colors, isGba: !!pal.scaled_from_5bit };
+        }
+        async promptImportPalette() {
+            if (this.getTauriInvokeFn()) {
+                try {
+                    const picked = await this.tauriOpenDirectoryDialog({
+                        title: 'Import Palette',
+                        directory: false,
+                        multiple: false,
+                        filters: this.getPaletteFileFilters()
+                    });
+                    if (picked === null) return;
+                    if (typeof picked === 'string' && picked) {
+                        const pal = await this.tauriInvoke('read_palette_file', { path: this.normalizeIncomingPath(picked) });
+                        this.applyImportedPalette(this.paletteFromFile(pal).colors);
+                        return;
+                    }
+                } catch (e) {
+                    showToast('Palette import failed: ' + this.getErrorText(e), 'warning');
+                    return;
+                }
+            }
             const input = document.getElementById('pal-upload');
             if (!input) return;
             input.value = '';
             input.click();
         }
 
-        exportPalette() {
+        async exportPalette() {
             if (this.bitDepth === 24) {
                 const { list } = this.extractPalette(this.ctx, this.config.width, this.config.height);
                 this.palette = list;
             }
+            if (this.getTauriInvokeFn()) {
+                try {
+                    const picked = await this.tauriSaveFileDialog({
+                        title: 'Export Palette',
+                        defaultPath: 'palette.pal',
+                        filters: this.getPaletteFileFilters().slice(1)
+                    });
+                    if (picked === null) return;
+                    if (typeof picked === 'string' && picked) {
+                        // Same 5-bit snapping as the JASC download below.
+                        const colors = this.palette.map(c => [
+                            Math.floor(c.r / 8) * 8, Math.floor(c.g / 8) * 8, Math.floor(c.b / 8) * 8,
+                            c.a === undefined ? 255 : c.a
+                        ]);
+                        await this.tauriInvoke('write_palette_file', { path: this.normalizeIncomingPath(picked), colors });
+                        showToast('Palette exported', 'info');
+                        return;
+                    }
+                } catch (e) {
+                    showToast('Palette export failed: ' + this.getErrorText(e), 'error');
+                    return;
+                }
+            }
             let out = "JASC-PAL\r\n0100\r\n" + this.palette.length + "\r\n";
             for(let c of this.palette) {
                 let r = Math.floor(c.r / 8) * 8;
@@ -23074,7 +24142,8 @@ self.onmessage = function(e) {
                 }
             }
             const isGba = maxVal > 0 && maxVal <= 31;
-            const scale = isGba ? (255 / 31) : 1;
+            // 5-bit components scale up as gbagfx does (palette.rs upconvert).
+            const scale = (v) => (isGba ? Math.floor(v * 255 / 31) : v);
             const pal = [];
             for (let i = 3; i < 3 + count; i++) {
                 if (!lines[i]) continue;
@@ -23082,7 +24151,7 @@ self.onmessage = function(e) {
                 if (parts.length < 3) continue;
                 const r = Number(parts[0]), g = Number(parts[1]), b = Number(parts[2]);
                 if (![r, g, b].every(v => Number.isFinite(v) && v >= 0 && v <= (isGba ? 31 : 255))) continue;
-                pal.push({ r: Math.round(r * scale), g: Math.round(g * scale), b: Math.round(b * scale), a: i === 3 ? 0 : 255 });
+                pal.push({ r: scale(r), g: scale(g), b: scale(b), a: i === 3 ? 0 : 255 });
             }
             if (!pal.length) throw new Error('Palette file had no valid colors');
             return { colors: pal, isGba };
@@ -23103,10 +24172,11 @@ self.onmessage = function(e) {
             this.saveState();
             showToast('Palette loaded' + (isGba ? ' (GBA 15-bit scaled)' : ''), 'info');
         }
-        loadProjectPalette(name, text) {
+        /* `source` is JASC-PAL text or a read_palette_file result */
+        loadProjectPalette(name, source) {
             let parsed;
             try {
-                parsed = this.parseGbaPaletteText(text);
+                parsed = typeof source === 'string' ? this.parseGbaPaletteText(source) : this.paletteFromFile(source);
             } catch (e) {
                 showToast(e.message, 'warning');
                 return;
@@ -23142,15 +24212,15 @@ self.onmessage = function(e) {
             const n = (name || '').toLowerCase();
             if (n === 'normal.pal') return 'Normal';
             if (n === 'shiny.pal') return 'Shiny';
-            return (name || 'palette').replace(/\.pal$/i, '');
+            return (name || 'palette').replace(/\.(pal|gpl|act|aco|txt|hex|gbapal)$/i, '');
         }
-        async readPalNodeText(node) {
+        async readPalNode(node) {
             if (node && node.handle) {
                 const file = node.handle instanceof File ? node.handle : (await node.handle.getFile());
-                return await file.text();
+                return this.parseGbaPaletteText(await file.text());
             }
             if (node && node.path) {
-                return await this.tauriInvoke('read_text_file', { path: this.normalizeIncomingPath(node.path) });
+                return this.paletteFromFile(await this.tauriInvoke('read_palette_file', { path: this.normalizeIncomingPath(node.path) }));
             }
             throw new Error('No palette source');
         }
@@ -23161,15 +24231,16 @@ self.onmessage = function(e) {
                 for (let i = 0; i < palNodes.length; i++) {
                     const node = palNodes[i];
                     try {
-                        const text = await this.readPalNodeText(node);
-                        const parsed = this.parseGbaPaletteText(text);
+                        const parsed = await this.readPalNode(node);
                         palettes.push({
                             id: 'pal-' + i + '-' + node.name,
                             name: this.labelForProjectPal(node.name),
                             colors: parsed.colors,
                             source: 'pal',
                             handle: node.handle || null,
-                            path: node.path || null
+                            path: node.path || null,
+                            // order[slot] = slot in the file; applyPaletteOrderToProject writes it back.
+                            order: parsed.colors.map((_, j) => j)
                         });
                     } catch (e) {
                         console.warn('Failed to load palette', node && node.name, e);
@@ -23470,6 +24541,7 @@ self.onmessage = function(e) {
             if (from < 0 || from >= arr.length || to < 0 || to >= arr.length || from === to) return;
             const moved = arr.splice(from, 1)[0];
             arr.splice(to, 0, moved);
+            if (e.order) e.order.splice(to, 0, e.order.splice(from, 1)[0]);
             this.paletteLab = null;
             if (paletteId === this.state.activePaletteId) this.renderQuantPalette();
             if (this.state.previewPaletteId) this._recolorPreview();
@@ -23488,6 +24560,7 @@ self.onmessage = function(e) {
             const e = this.getPaletteById(paletteId);
             if (!e) return;
             e.colors.push({ r: color.r, g: color.g, b: color.b, a: 255 });
+            e.order = null;
             this.paletteLab = null;
             if (paletteId === this.state.activePaletteId) this.renderQuantPalette();
             if (this.state.previewPaletteId) this._recolorPreview();
@@ -23500,11 +24573,113 @@ self.onmessage = function(e) {
             const moved = src.colors.splice(srcIdx, 1)[0];
             if (dstIdx < 0 || dstIdx > dst.colors.length) dstIdx = dst.colors.length;
             dst.colors.splice(dstIdx, 0, moved);
+            src.order = null;
+            dst.order = null;
             this.paletteLab = null;
             if (srcId === this.state.activePaletteId || dstId === this.state.activePaletteId) this.renderQuantPalette();
             if (this.state.previewPaletteId) this._recolorPreview();
             if (this.onPalettesChanged) this.onPalettesChanged();
         }
+        isPaletteReordered(paletteId) {
+            const e = this.getPaletteById(paletteId);
+            return !!(e && e.path && e.order && e.order.some((old, i) => old !== i));
+        }
+        /* Resolves with the files left checked ({ path, sha256 }), or null.
+           The palette itself (listed first) can't be unchecked. */
+        confirmPaletteReorder(files) {
+            const modal = document.getElementById('modal-palette-reorder');
+            const list = document.getElementById('palette-reorder-list');
+            if (!modal || !list) return Promise.resolve(null);
+            const strip = (colors) => {
+                const wrap = document.createElement('span');
+                wrap.style.display = 'inline-flex';
+                colors.forEach(([r, g, b]) => {
+                    const sw = document.createElement('span');
+                    sw.style.width = '10px';
+                    sw.style.height = '12px';
+                    sw.style.background = `rgb(${r}, ${g}, ${b})`;
+                    wrap.appendChild(sw);
+                });
+                return wrap;
+            };
+            list.innerHTML = '';
+            const boxes = files.map((f, i) => {
+                const row = document.createElement('div');
+                row.style.margin = '6px 0';
+                const title = document.createElement('label');
+                title.style.display = 'block';
+                const box = document.createElement('input');
+                box.type = 'checkbox';
+                box.checked = true;
+                box.disabled = i === 0;
+                const detail = f.kind === 'png' ? `${f.changed_pixels} pixels remapped` : 'palette';
+                title.appendChild(box);
+                title.appendChild(document.createTextNode(` ${this.getFilenameFromPath(f.path)} (${detail})`));
+                title.title = f.path;
+                row.appendChild(title);
+                row.appendChild(strip(f.before));
+                row.appendChild(document.createTextNode(' \u2192 '));
+                row.appendChild(strip(f.after));
+                list.appendChild(row);
+                return box;
+            });
+            modal.style.display = 'flex';
+            return new Promise((resolve) => {
+                const finish = (value) => {
+                    modal.style.display = 'none';
+                    resolve(value);
+                };
+                document.getElementById('palette-reorder-ok').onclick = () => finish(
+                    files.filter((_, i) => boxes[i].checked).map((f) => ({ path: f.path, sha256: f.sha256 }))
+                );
+                document.getElementById('palette-reorder-cancel').onclick = () => finish(null);
+            });
+        }
+        /* Write a dragged palette order back to the palette file and the PNGs
+           and counterpart palette beside it the user keeps, then bring the open image's
+           palettes and decoded indices in line with the rewritten files. */
+        async applyPaletteOrderToProject(paletteId) {
+            const e = this.getPaletteById(paletteId);
+            if (!e || !this.isPaletteReordered(paletteId)) return;
+            if (!this.getTauriInvokeFn()) {
+                showToast('Reordering project files needs the desktop app', 'warning');
+                return;
+            }
+            const path = this.normalizeIncomingPath(e.path);
+            const order = e.order.slice();
+            try {
+                const preview = await this.tauriInvoke('preview_palette_reorder', { path, order });
+                const files = await this.confirmPaletteReorder(preview);
+                if (!files) return;
+                const written = await this.tauriInvoke('apply_palette_reorder', { path, order, files });
+                const norm = (p) => String(p || '').replace(/\\/g, '/').toLowerCase();
+                const rewritten = new Set(written.map((f) => norm(f.path)));
+                const permute = (arr) => {
+                    const before = arr.slice();
+                    order.forEach((old, i) => { arr[i] = before[old]; });
+                };
+                e.order = e.colors.map((_, i) => i);
+                this.state.palettes.forEach((other) => {
+                    if (other === e || other.source !== 'pal' || !rewritten.has(norm(other.path))) return;
+                    permute(other.colors);
+                });
+                if (this.state.projectFile && rewritten.has(norm(this.state.projectFile))) {
+                    const embedded = this.getPaletteById('embedded');
+                    if (embedded) permute(embedded.colors);
+                    if (this.spriteIndices) {
+                        const newIndex = [];
+                        order.forEach((old, i) => { newIndex[old] = i; });
+                        this.spriteIndices = this.spriteIndices.map((v) => (v < order.length ? newIndex[v] : v));
+                    }
+                }
+                this.paletteLab = null;
+                this.renderQuantPalette();
+                if (this.onPalettesChanged) this.onPalettesChanged();
+                showToast(`Reordered ${written.length} files`, 'info');
+            } catch (err) {
+                showToast('Palette reorder failed: ' + this.getErrorText(err), 'error');
+            }
+        }
         renderPalettePreviewInto(canvas, paletteId) {
             if (!canvas) return;
             const target = this.getPaletteById(paletteId);
@@ -23709,7 +24884,7 @@ self.onmessage = function(e) {
                 if(lines[0] !== 'JASC-PAL' || lines[1] !== '0100') { showToast('Invalid JASC-PAL file', 'warning'); return; }
                 const count = parseInt(lines[2], 10);
                 if (!Number.isFinite(count) || count < 0) { showToast('Invalid JASC-PAL file', 'warning'); return; }
-                this.palette = [];
+                const colors = [];
                 for(let i=3; i<3+count; i++) {
                     if(!lines[i]) continue;
                     const parts = lines[i].trim().split(/\s+/);
@@ -23719,16 +24894,20 @@ self.onmessage = function(e) {
                     const b = Number(parts[2]);
                     if (![r, g, b].every(v => Number.isFinite(v) && v >= 0 && v <= 255)) continue;
                     let alpha = 255;
-                    if (this.palette.length === 0) alpha = 0;
-                    this.palette.push({r:Math.round(r), g:Math.round(g), b:Math.round(b), a:alpha});
+                    if (colors.length === 0) alpha = 0;
+                    colors.push({r:Math.round(r), g:Math.round(g), b:Math.round(b), a:alpha});
                 }
-                if (!this.palette.length) { showToast('Palette file had no valid colors', 'warning'); return; }
-                this.paletteLab = null;
-                this.enforcePalette(this.ctx, this.config.width, this.config.height, {set: new Set(), list: this.palette});
-                this.saveState();
+                if (!colors.length) { showToast('Palette file had no valid colors', 'warning'); return; }
+                this.applyImportedPalette(colors);
             };
             reader.readAsText(file);
         }
+        applyImportedPalette(colors) {
+            this.palette = colors;
+            this.paletteLab = null;
+            this.enforcePalette(this.ctx, this.config.width, this.config.height, {set: new Set(), list: this.palette});
+            this.saveState();
+        }
 
         async saveAsFile() {
             /* If layers are active, always save as ORA */
@@ -23785,8 +24964,17 @@ self.onmessage = function(e) {
                     if (!this.isSupportedImagePath(normalizedPath)) {
                         throw new Error(`Refusing to write non-image path: ${normalizedPath}`);
                     }
-                    const bytes = new Uint8Array(await blob.arrayBuffer());
-                    await this.tauriWriteAllowedFile(normalizedPath, bytes);
+                    if (/\.bmp$/i.test(normalizedPath) && [1, 4, 8].includes(this.bitDepth) && this.palette && this.palette.length) {
+                        // Indexed modes save as a paletted BMP with the current palette.
+                        const w = this.config.width, h = this.config.height;
+                        const indices = this.quantizeToIndices(this.ctx.getImageData(0, 0, w, h).data, w, h, this.palette);
+                        const bmpBytes = await this.tauriEncodeIndexedBmp(w, h, indices, this.palette, this.bitDepth);
+                        await this.tauriWriteAllowedFile(normalizedPath, bmpBytes);
+                    } else {
+                        const bytes = new Uint8Array(await blob.arrayBuffer());
+                        const editorMeta = /\.png$/i.test(normalizedPath) ? this.collectEditorMetadata() : null;
+                        await this.tauriWriteAllowedFile(normalizedPath, bytes, editorMeta);
+                    }
                     this.state.filePath = normalizedPath;
                     this.markSaved(this.getFilenameFromPath(normalizedPath));
                     this.resetSaveReminderTimer();
@@ -26056,8 +27244,10 @@ self.onmessage = function(e) {
         };
         const _oraToBlend = Object.fromEntries(Object.entries(_blendToOra).map(([k,v])=>[v,k]));
 
-        /* ── SAVE ORA ────────────────────────────────────────────────────── */
-        app.saveAsORA = async function () {
+        /* ── BUILD ORA ───────────────────────────────────────────────────── */
+        /* The layer stack as ORA archive bytes; used for saving and for the
+           crash-recovery snapshots. */
+        app.buildORABytes = async function () {
             const w = this.config.width, h = this.config.height;
             const layers = mgr.active && mgr.layers.length ? mgr.layers : null;
             const enc    = new TextEncoder();
@@ -26133,11 +27323,27 @@ self.onmessage = function(e) {
             tCanvas.getContext('2d').drawImage(mergeCanvas, 0, 0, tw, th);
             entries.push({ name: 'Thumbnails/thumbnail.png', data: await _canvasToPngBytes(tCanvas), store: false });
 
-            /* Build ZIP and download */
-            const zipBytes = await _oraZip.build(entries);
-            const blob = new Blob([zipBytes], { type: 'image/openraster' });
+            return _oraZip.build(entries);
+        };
+
+        /* ── SAVE ORA ────────────────────────────────────────────────────── */
+        app.saveAsORA = async function () {
+            /* Desktop writes the archive through the Rust file commands
+               (which validate it), the browser downloads it */
+            const zipBytes = await this.buildORABytes();
             const baseName = (this.state.fileName || 'untitled').replace(/\.[^/.]+$/, '');
             const fname = baseName + '.ora';
+            if (this.getTauriInvokeFn && this.getTauriInvokeFn()) {
+                const dir = await this.tauriWriteExportFileWithSaveDialog({ name: fname, bytes: zipBytes }, this.state.exportDir || '');
+                if (dir === null) return;
+                if (dir !== undefined) {
+                    if (dir) this.state.exportDir = dir;
+                    this.markSaved(fname);
+                    this.resetSaveReminderTimer();
+                    return;
+                }
+            }
+            const blob = new Blob([zipBytes], { type: 'image/openraster' });
             const url = URL.createObjectURL(blob);
             const a = document.createElement('a');
             a.href = url; a.download = fname; a.click();
@@ -26153,6 +27359,20 @@ self.onmessage = function(e) {
                 app.showOpenConfirm(() => app.loadORAFile(f), 'Opening a new file');
                 return;
             }
+            /* The desktop app parses the archive in Rust (src-tauri ora.rs),
+               which checks entry paths and caps decompressed sizes. */
+            if (this.getTauriInvokeFn()) {
+                try {
+                    const zipBytes = new Uint8Array(await file.arrayBuffer());
+                    const data = await this.tauriInvoke('parse_ora_document', zipBytes,
+                        this.tauriBinaryHeaders({ 'flatten-groups': 'true' }));
+                    await this.loadORADocument(this.tauriParseFramedResponse(data), file.name);
+                } catch (err) {
+                    showToast('Failed to open ORA file: ' + this.getErrorText(err), 'error');
+                    console.error('[ORA load]', err);
+                }
+                return;
+            }
             try {
                 const zipBytes = new Uint8Array(await file.arrayBuffer());
                 const files    = _parseZip(zipBytes);
@@ -26246,14 +27466,52 @@ self.onmessage = function(e) {
                     });
                 }
                 if (!layerDefs.length) throw new Error('No usable layers found in ORA');
+                this.loadLayerStack(fileW, fileH, layerDefs, activeIdxAttr, file.name);
+
+            } catch (err) {
+                showToast('Failed to open ORA file: ' + err.message, 'error');
+                console.error('[ORA] load failed, about to show toast:', err);
+                console.error('[ORA load]', err);
+            }
+        };
+
+        /* A framed `read_ora_document` / `parse_ora_document` response (groups
+           flattened) onto the layer stack. Layers are placed at their offsets
+           on a full-size canvas. */
+        app.loadORADocument = async function ({ meta, blobs }, fileName) {
+            const layerDefs = [];
+            for (const node of meta.layers) {
+                if (node.kind !== 'layer') continue;
+                const blob = new Blob([blobs.subarray(node.png_offset, node.png_offset + node.png_len)], { type: 'image/png' });
+                const img = await createImageBitmap(blob).catch(() => null);
+                if (!img) { console.warn('[ORA] failed to decode layer image', node.src); continue; }
+                const lc = document.createElement('canvas');
+                lc.width = meta.width; lc.height = meta.height;
+                lc.getContext('2d').drawImage(img, node.x, node.y);
+                layerDefs.push({
+                    canvas:    lc,
+                    name:      node.name || 'Layer',
+                    opacity:   node.opacity,
+                    visible:   node.visible,
+                    blendMode: _oraToBlend[node.composite_op] || 'source-over',
+                    locked:    node.locked,
+                    alphaLock: node.alpha_lock,
+                    isBase:    node.is_base,
+                });
+            }
+            if (!layerDefs.length) throw new Error('No usable layers found in ORA');
+            this.loadLayerStack(meta.width, meta.height, layerDefs, meta.active_layer || 0, fileName);
+        };
 
-                /* ── Apply to app ── */
+        /* Replace the document with a stack of layer canvases (bottom to top).
+           Shared by the ORA loader and GIF frame import. */
+        app.loadLayerStack = function (fileW, fileH, layerDefs, activeIdxAttr, fileName) {
                 /* Reset app state */
                 this.state.history   = [];
                 this.state.step      = -1;
                 this.state.fileHandle = null;
                 this.state.filePath   = null;
-                this.state.fileName   = file.name;
+                this.state.fileName   = fileName;
                 if (this.state.selection) this.cancelSelection();
 
                 /* Resize canvas */
@@ -26306,19 +27564,13 @@ self.onmessage = function(e) {
 
                 this.state.hasDocument = true;
                 this.saveState();
-                this.markSaved(file.name);
+                this.markSaved(fileName);
                 this.updateTitleFilename();
                 _refreshList();
                 _syncBtns();
                 _openPanel(true);
                 _schedThumb();
                 this.requestGlobalOverlayUpdate();
-
-            } catch (err) {
-                showToast('Failed to open ORA file: ' + err.message, 'error');
-                console.error('[ORA] load failed, about to show toast:', err);
-                console.error('[ORA load]', err);
-            }
         };
 
         /* ── ZIP parser (central-directory walk) ─────────────────────────── */
@@ -153,6 +153,13 @@
             shinyBtn.onclick = function () { if (app.generateShinyPalette) app.generateShinyPalette(palette.id); };
             actions.appendChild(shinyBtn);
         }
+        if (app.isPaletteReordered && app.isPaletteReordered(palette.id)) {
+            var applyBtn = el('button', 'pp-btn', 'Apply order');
+            applyBtn.type = 'button';
+            applyBtn.title = 'Reorder this palette file and remap every PNG and palette beside it that uses it';
+            applyBtn.onclick = function () { if (app.applyPaletteOrderToProject) app.applyPaletteOrderToProject(palette.id); };
+            actions.appendChild(applyBtn);
+        }
         card.appendChild(actions);
 
         var slotCount = Math.max(
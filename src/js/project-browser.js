@@ -1,14 +1,16 @@
 /*
  * pokeemerald project asset browser.
  * Hooks a `graphics/` folder, shows indexed PNG assets with lazy thumbnails,
- * groups sibling `.pal` files (normal.pal / shiny.pal / <basename>.pal) as
- * PAL badges, and opens/saves assets preserving the exact palette + indices.
+ * groups sibling palette files (normal.pal / shiny.pal / <basename>.pal, or
+ * .gpl/.act/.aco/.txt/.hex/.gbapal) as PAL badges, and opens/saves assets preserving the exact palette + indices.
  */
 (function () {
     'use strict';
 
     var STORAGE_KEY = 'cdpaint.projectRoot';
     var SHARED_PALS = ['normal.pal', 'shiny.pal'];
+    // Palette formats read_palette_file understands.
+    var PAL_EXT_RE = /\.(pal|gpl|act|aco|txt|hex|gbapal)$/i;
 
     var panel = document.getElementById('project-panel');
     var treeEl = document.getElementById('project-tree');
@@ -16,6 +18,7 @@
     var expandBtn = document.getElementById('project-expand');
     var hookBtn = document.getElementById('project-hook');
     var refreshBtn = document.getElementById('project-refresh');
+    var validateBtn = document.getElementById('project-validate');
     var unhookBtn = document.getElementById('project-unhook');
     var collapseBtn = document.getElementById('project-collapse');
     var closeBtn = document.getElementById('project-close');
@@ -23,6 +26,9 @@
     var MAX_SCAN_DEPTH = 12;
     var DENYLIST = ['.git', 'node_modules', 'target', '.vscode', '.idea', 'dist'];
     var currentRoot = null;
+    // index_decomp_project result for the hooked folder, when it is a decomp.
+    var speciesIndex = null;
+    var SPECIES_ASSETS = ['front', 'still_front', 'back', 'icon', 'footprint'];
 
     function getApp() { return window.PaintApp; }
 
@@ -55,6 +61,7 @@
             else localStorage.removeItem(STORAGE_KEY);
         } catch (e) { /* ignore */ }
         if (refreshBtn) refreshBtn.disabled = !v;
+        if (validateBtn) validateBtn.disabled = !v || !isTauriEnv();
         if (unhookBtn) unhookBtn.disabled = !v;
     }
 
@@ -159,9 +166,9 @@
     }
 
     function loadPal(node) {
-        var load = function (text) {
+        var load = function (source) {
             var app = getApp();
-            if (app && typeof app.loadProjectPalette === 'function') app.loadProjectPalette(node.name, text);
+            if (app && typeof app.loadProjectPalette === 'function') app.loadProjectPalette(node.name, source);
             else showToast('Palette loaded', 'info');
         };
         if (node && node.handle) {
@@ -173,7 +180,7 @@
             });
             return;
         }
-        tauriInvoke('read_text_file', { path: node.path || '' }).then(load).catch(function (e) {
+        tauriInvoke('read_palette_file', { path: node.path || '' }).then(load).catch(function (e) {
             showToast('Failed to read palette: ' + (e && e.message ? e.message : e), 'error');
         });
     }
@@ -233,7 +240,7 @@
         return row;
     }
 
-    function dirRow(node) {
+    function dirRow(node, fill) {
         var wrap = document.createElement('div');
         wrap.className = 'proj-dir';
 
@@ -253,7 +260,7 @@
         children.className = 'proj-dir-children';
         children.style.display = 'none';
         children.dataset.collapsed = '1';
-        renderChildren(node, children);
+        (fill || renderChildren)(node, children);
         wrap.appendChild(children);
 
         var collapsed = true;
@@ -276,7 +283,7 @@
             if (lower.endsWith('.png')) {
                 var base = c.name.slice(0, -4);
                 pngByBase[base] = c;
-            } else if (lower.endsWith('.pal')) {
+            } else if (PAL_EXT_RE.test(lower)) {
                 if (SHARED_PALS.indexOf(lower) >= 0) shared.push(c);
                 else pals.push(c);
             }
@@ -287,7 +294,7 @@
             var png = pngByBase[base];
             var assoc = [];
             pals.forEach(function (pn) {
-                if (pn.name.slice(0, -4).toLowerCase() === base.toLowerCase()) assoc.push(pn);
+                if (pn.name.replace(PAL_EXT_RE, '').toLowerCase() === base.toLowerCase()) assoc.push(pn);
             });
             shared.forEach(function (pn) { assoc.push(pn); });
             container.appendChild(fileRow(png, assoc));
@@ -295,7 +302,7 @@
 
         // Standalone pal files (no matching png, not normal/shiny).
         pals.forEach(function (pn) {
-            var base = pn.name.slice(0, -4).toLowerCase();
+            var base = pn.name.replace(PAL_EXT_RE, '').toLowerCase();
             if (!pngByBase[base]) container.appendChild(palOnlyRow(pn));
         });
 
@@ -305,11 +312,127 @@
         });
     }
 
+    function speciesPalNodes(sp) {
+        var nodes = [];
+        if (sp.normal_palette) nodes.push({ name: 'normal.pal', kind: 'file', path: sp.normal_palette });
+        if (sp.shiny_palette) nodes.push({ name: 'shiny.pal', kind: 'file', path: sp.shiny_palette });
+        return nodes;
+    }
+
+    function fillSpecies(sp, container) {
+        var pals = speciesPalNodes(sp);
+        SPECIES_ASSETS.forEach(function (kind) {
+            var path = sp[kind];
+            if (!path) return;
+            container.appendChild(fileRow({ name: baseName(path), kind: 'file', path: path }, pals));
+        });
+    }
+
+    function fillSpeciesList(index, container) {
+        index.species.forEach(function (sp) {
+            var label = (sp.id != null ? '#' + String(sp.id).padStart(3, '0') + ' ' : '') + sp.name;
+            container.appendChild(dirRow({ name: label }, function (node, el) { fillSpecies(sp, el); }));
+        });
+    }
+
+    function baseName(path) {
+        var parts = String(path || '').split(/[\\/]+/);
+        return parts[parts.length - 1] || '';
+    }
+
+    function normPath(path) {
+        return String(path || '').replace(/\\/g, '/').toLowerCase();
+    }
+
+    /* Species and asset kind an image path belongs to, from the decomp index. */
+    function findSpeciesAsset(path) {
+        if (!speciesIndex || !path) return null;
+        var target = normPath(path);
+        for (var i = 0; i < speciesIndex.species.length; i++) {
+            var sp = speciesIndex.species[i];
+            for (var k = 0; k < SPECIES_ASSETS.length; k++) {
+                var kind = SPECIES_ASSETS[k];
+                if (sp[kind] && normPath(sp[kind]) === target) return { species: sp, kind: kind, path: sp[kind] };
+            }
+        }
+        return null;
+    }
+
+    function loadSpeciesIndex(root) {
+        speciesIndex = null;
+        return tauriInvoke('index_decomp_project', { path: root }).then(function (index) {
+            if (!index || !index.species || !index.species.length) return null;
+            speciesIndex = index;
+            var section = dirRow({ name: 'Species (' + index.species.length + ')' }, function (node, el) { fillSpeciesList(index, el); });
+            treeEl.insertBefore(section, treeEl.firstChild);
+            if (currentQuery) applyFilter(currentQuery);
+            return index;
+        }).catch(function () {
+            // Not a decomp (or its headers are outside the hooked folder): the tree alone still works.
+            return null;
+        });
+    }
+
+    function diagnosticRow(d) {
+        var row = document.createElement('div');
+        row.className = 'proj-row proj-row-diag';
+        var sev = document.createElement('span');
+        sev.className = 'proj-diag-severity proj-diag-' + d.severity;
+        sev.textContent = d.severity;
+        row.appendChild(sev);
+        var label = document.createElement('span');
+        label.className = 'proj-name';
+        label.textContent = relativeToRoot(d.path) + ': ' + d.message;
+        label.title = d.path;
+        row.appendChild(label);
+        row.addEventListener('click', function () {
+            var name = baseName(d.path);
+            if (PAL_EXT_RE.test(name)) { loadPal({ name: name, path: d.path }); return; }
+            var app = getApp();
+            if (app && typeof app.openProjectImage === 'function') app.openProjectImage(d.path, []);
+        });
+        return row;
+    }
+
+    function relativeToRoot(path) {
+        var root = getRoot();
+        var p = String(path || '');
+        if (root && normPath(p).indexOf(normPath(root)) === 0) return p.slice(root.length).replace(/^[\\/]+/, '');
+        return p;
+    }
+
+    var problemsSection = null;
+
+    function validate() {
+        var root = getRoot();
+        if (!root || !isTauriEnv()) { showToast('No project hooked', 'warning'); return; }
+        setLoading(true);
+        tauriInvoke('validate_project_palettes', { path: root }).then(function (diagnostics) {
+            setLoading(false);
+            if (problemsSection && problemsSection.parentNode) problemsSection.parentNode.removeChild(problemsSection);
+            problemsSection = null;
+            var errors = diagnostics.filter(function (d) { return d.severity === 'error'; }).length;
+            if (!diagnostics.length) {
+                showToast('Palettes are consistent', 'info');
+                return;
+            }
+            problemsSection = dirRow({ name: 'Problems (' + errors + ' errors, ' + (diagnostics.length - errors) + ' warnings)' }, function (node, el) {
+                diagnostics.forEach(function (d) { el.appendChild(diagnosticRow(d)); });
+            });
+            treeEl.insertBefore(problemsSection, treeEl.firstChild);
+            problemsSection.querySelector('.proj-dir-header').click();
+            showToast(diagnostics.length + ' palette problems found', errors ? 'error' : 'warning');
+        }).catch(function (e) {
+            setLoading(false);
+            showToast('Validation failed: ' + (e && e.message ? e.message : e), 'error');
+        });
+    }
+
     function showHint() {
         treeEl.textContent = '';
         var hint = document.createElement('div');
         hint.className = 'proj-empty';
-        hint.textContent = 'No project hooked. Click "Hook" to connect a pokeemerald graphics/ folder.';
+        hint.textContent = 'No project hooked. Click "Hook" to connect a pokeemerald root or graphics/ folder.';
         treeEl.appendChild(hint);
         setStatus('Not connected');
     }
@@ -378,7 +501,7 @@
         if (!root || !root.children || !root.children.length) {
             var empty = document.createElement('div');
             empty.className = 'proj-empty';
-            empty.textContent = 'No .png / .pal assets found.';
+            empty.textContent = 'No .png / palette assets found.';
             treeEl.appendChild(empty);
             return;
         }
@@ -395,6 +518,11 @@
             setStatus(root + '  (' + count + ' assets)');
             setRoot(root);
             setLoading(false);
+            // Outside edits to the tree come back as project-tree-changed.
+            tauriInvoke('watch_project', { path: root }).catch(function () {});
+            return loadSpeciesIndex(root).then(function (index) {
+                if (index) setStatus(root + '  (' + count + ' assets, ' + index.species.length + ' species)');
+            });
         }).catch(function (e) {
             setStatus('Scan failed: ' + (e && e.message ? e.message : e));
             showToast('Project scan failed: ' + (e && e.message ? e.message : e), 'error');
@@ -404,7 +532,7 @@
 
     function isAssetName(name) {
         var lower = (name || '').toLowerCase();
-        return lower.endsWith('.png') || lower.endsWith('.pal');
+        return lower.endsWith('.png') || PAL_EXT_RE.test(lower);
     }
 
     async function scanFsa(handle, relPath, depth) {
@@ -573,7 +701,7 @@
 
     function hook() {
         if (isTauriEnv()) {
-            tauriInvoke('pick_export_folder').then(function (path) {
+            tauriInvoke('hook_project_folder').then(function (path) {
                 if (!path) return;
                 openPanel();
                 return scan(path);
@@ -612,15 +740,38 @@
     }
 
     function unhook() {
+        var root = getRoot();
+        if (root && isTauriEnv()) {
+            // Unhooking also withdraws the app's access to the folder.
+            tauriInvoke('unwatch_project').catch(function () {});
+            tauriInvoke('revoke_file_grant', { path: root }).catch(function () {});
+        }
         setRoot('');
+        speciesIndex = null;
         treeEl.textContent = '';
         setStatus('');
         setLoading(false);
         closePanel();
     }
 
+    // Files added, removed or rewritten outside CDPaint: rescan once the
+    // burst settles. The watcher only tells this window about its own root,
+    // which it reports canonicalized, so the hooked path isn't compared.
+    var rescanTimer = null;
+    function onTreeChanged(event) {
+        var root = getRoot();
+        if (!root || !(event && event.payload)) return;
+        clearTimeout(rescanTimer);
+        rescanTimer = setTimeout(function () { scan(root); }, 500);
+    }
+    var tauriEvents = window.__TAURI__ && window.__TAURI__.event;
+    if (tauriEvents && typeof tauriEvents.listen === 'function') {
+        tauriEvents.listen('project-tree-changed', onTreeChanged);
+    }
+
     if (hookBtn) hookBtn.addEventListener('click', hook);
     if (refreshBtn) refreshBtn.addEventListener('click', refresh);
+    if (validateBtn) validateBtn.addEventListener('click', validate);
     if (unhookBtn) unhookBtn.addEventListener('click', unhook);
     if (collapseBtn) collapseBtn.addEventListener('click', collapsePanel);
     if (closeBtn) closeBtn.addEventListener('click', closePanel);
@@ -628,6 +779,7 @@
     setupSearch();
 
     window.PokeProject = {
+        findSpeciesAsset: findSpeciesAsset,
         open: openPanel,
         close: closePanel,
         toggle: function () {